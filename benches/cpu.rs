@@ -0,0 +1,84 @@
+//! The CPU hot paths over a pseudo-random 1008 word IPL3, with the default Y and X bits. Run with
+//! `cargo bench --features bench`
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use ipl3hasher_new::{CPUHasher, bench, known_answer::KnownAnswer};
+use std::hint::black_box;
+
+const SEED: u8 = 0x3F;
+const IPL3_WORDS: u32 = 1008;
+
+fn ipl3() -> Vec<u8> {
+    KnownAnswer {
+        generator: 0x6C078965,
+        ipl3_words: IPL3_WORDS as usize,
+        seed: SEED,
+        checksum: 0,
+    }
+    .get_image()
+}
+
+/// Bit offsets into the IPL3 of a whole IPL3 word, most significant bit first
+fn word_bits(word: u32) -> Vec<u32> {
+    ((word * 32)..((word + 1) * 32)).collect()
+}
+
+fn cpu(c: &mut Criterion) {
+    let mut cpu = CPUHasher::new(&ipl3(), SEED);
+    let y_bits = word_bits(IPL3_WORDS - 2);
+    let x_bits = word_bits(IPL3_WORDS - 1);
+
+    c.bench_function("calculate", |b| {
+        b.iter(|| bench::calculate(black_box(&cpu)))
+    });
+
+    c.bench_function("verify", |b| {
+        b.iter(|| {
+            cpu.verify(
+                &y_bits,
+                black_box(0x12345678),
+                &x_bits,
+                black_box(0x9ABCDEF0),
+            )
+        })
+    });
+
+    // The matches of a dispatch, a few X values for each of a few Y values
+    let candidates: Vec<(u32, u32)> = (0..64)
+        .map(|i| (0x12345678 + (i / 16), 0x9ABCDEF0 + (i * 0x01010101)))
+        .collect();
+    c.bench_function("verify_batch", |b| {
+        b.iter(|| cpu.verify_batch(&y_bits, &x_bits, black_box(&candidates), 1))
+    });
+
+    let state = bench::calculate(&cpu);
+    c.bench_function("finalize", |b| {
+        b.iter(|| bench::finalize(black_box(&state)))
+    });
+
+    bench::set_y_bits(&mut cpu, &y_bits);
+    c.bench_function("y_round", |b| {
+        b.iter(|| bench::y_round(&cpu, &y_bits, black_box(0x12345678)))
+    });
+
+    // A whole CPU search round with the unmatchable checksum, single-threaded and on every core
+    let y_round = bench::y_round(&cpu, &y_bits, 0x12345678);
+    let mut group = c.benchmark_group("x_round");
+    group.sample_size(10);
+    for threads in [
+        1,
+        std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+    ] {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_function(format!("{threads} threads"), |b| {
+            b.iter(|| bench::x_round(&cpu, &pool, black_box(y_round), u64::MAX))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cpu);
+criterion_main!(benches);
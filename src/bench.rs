@@ -0,0 +1,42 @@
+use crate::cpu::CPUHasher;
+
+/// The word loop over the whole IPL3 from the initial state
+pub fn calculate(cpu: &CPUHasher) -> [u32; 16] {
+    cpu.final_state()
+}
+
+/// Calculates the state before the first Y word for `y_round`, as the search does once
+pub fn set_y_bits(cpu: &mut CPUHasher, y_bits: &[u32]) {
+    cpu.set_y_bits(y_bits);
+}
+
+/// The state of the Y round handed to the X search
+pub fn y_round(cpu: &CPUHasher, y_bits: &[u32], y: u32) -> (u32, [u32; 16]) {
+    cpu.y_round(y_bits, y)
+}
+
+/// The final mix of a state into the 48-bit checksum
+pub fn finalize(state: &[u32; 16]) -> u64 {
+    CPUHasher::finalize(state)
+}
+
+/// A round of the X word search from the state of the Y round on the thread pool, the match if
+/// there is one
+pub fn x_round(
+    cpu: &CPUHasher,
+    pool: &rayon::ThreadPool,
+    (y_offset, state): (u32, [u32; 16]),
+    target_checksum: u64,
+) -> Option<u32> {
+    match CPUHasher::x_round(
+        target_checksum,
+        y_offset,
+        0,
+        state,
+        cpu.get_ipl3_words(),
+        pool,
+    ) {
+        crate::cpu::CPUHasherResult::Found(x) => Some(x),
+        _ => None,
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    error::HasherError,
+    hasher::{Hasher, YOrder},
+};
+use std::io::Write;
+
+/// Where a search is at and the parameters it was started with, which a resumed search must match
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// The Y of the round being searched
+    pub y: u32,
+    /// The X offset in the round the search continues from
+    pub x_offset: u32,
+    /// The order the Y range is searched in
+    pub y_order: YOrder,
+    /// The position of the Y in the order, saved for the random and Gray orders and the offset of the
+    /// Y into the range in the sequential order
+    pub y_position: u32,
+    /// The first Y value of the range, from --y-init or the shard
+    pub y_first: u32,
+    /// The last Y value of the range, from --y-end or the shard
+    pub y_end: u32,
+    /// The odd multiplier of the X offsets, see `Hasher::set_x_stride`
+    pub x_stride: u32,
+    /// The Y bit offsets into the IPL3
+    pub y_bits: Vec<u32>,
+    /// The X bit offsets into the IPL3
+    pub x_bits: Vec<u32>,
+    /// The CIC seed
+    pub seed: u8,
+    /// The checksum searched for
+    pub target_checksum: u64,
+    /// SHA-256 of the IPL3 the search was started on, in hex
+    pub ipl3_sha256: String,
+}
+
+impl Checkpoint {
+    /// Parses a checkpoint file written by `save`
+    pub fn load(path: &std::path::Path) -> Result<Self, HasherError> {
+        let contents = std::fs::read_to_string(path)?;
+
+        let mut values = std::collections::HashMap::new();
+
+        for line in contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(HasherError::CheckpointParseError(format!(
+                    "invalid line: {line}"
+                )))?;
+            values.insert(key.trim(), value.trim());
+        }
+
+        let get = |key: &str| {
+            values
+                .get(key)
+                .copied()
+                .ok_or(HasherError::CheckpointParseError(format!(
+                    "missing key: {key}"
+                )))
+        };
+
+        let parse_hex = |key: &str| -> Result<u64, HasherError> {
+            let value = get(key)?;
+            u64::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| {
+                HasherError::CheckpointParseError(format!("invalid value for {key}: {value}"))
+            })
+        };
+
+        let parse_bits = |key: &str| -> Result<Vec<u32>, HasherError> {
+            get(key)?
+                .split(',')
+                .map(|bit| {
+                    bit.parse().map_err(|_| {
+                        HasherError::CheckpointParseError(format!("invalid value for {key}: {bit}"))
+                    })
+                })
+                .collect()
+        };
+
+        // Checkpoints saved before the Y order was recorded always use the sequential order
+        let y_order = match values.get("order").copied() {
+            None | Some("sequential") => YOrder::Sequential,
+            Some("random") => YOrder::Random(parse_hex("order_seed")?),
+            Some("gray") => YOrder::Gray,
+            Some(order) => {
+                return Err(HasherError::CheckpointParseError(format!(
+                    "invalid value for order: {order}"
+                )));
+            }
+        };
+
+        // Checkpoints saved before the Y range was recorded search all of the Y bits
+        let y_bits = parse_bits("y_bits")?;
+        let y_first = match values.get("y_first") {
+            Some(_) => parse_hex("y_first")? as u32,
+            None => 0,
+        };
+        let y_end = match values.get("y_end") {
+            Some(_) => parse_hex("y_end")? as u32,
+            None => Hasher::get_y_max(&y_bits),
+        };
+
+        let y = parse_hex("y")? as u32;
+        let y_position = match y_order {
+            YOrder::Sequential => y.saturating_sub(y_first),
+            _ => parse_hex("y_position")? as u32,
+        };
+
+        // Saved only for strides other than 1, the X offset counts in the stride order
+        let x_stride = match values.get("x_stride") {
+            Some(_) => parse_hex("x_stride")? as u32,
+            None => 1,
+        };
+
+        Ok(Self {
+            y,
+            x_offset: parse_hex("x_offset")? as u32,
+            y_order,
+            y_position,
+            y_first,
+            y_end,
+            x_stride,
+            y_bits,
+            x_bits: parse_bits("x_bits")?,
+            seed: parse_hex("seed")? as u8,
+            target_checksum: parse_hex("target_checksum")?,
+            ipl3_sha256: get("ipl3_sha256")?.to_string(),
+        })
+    }
+
+    /// Writes the checkpoint to a temporary file renamed over the path, so an interrupted save never
+    /// leaves a partial checkpoint behind
+    pub fn save(&self, path: &std::path::Path) -> Result<(), HasherError> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = std::path::PathBuf::from(temp_path);
+
+        let y_bits: Vec<String> = self.y_bits.iter().map(|bit| bit.to_string()).collect();
+        let x_bits: Vec<String> = self.x_bits.iter().map(|bit| bit.to_string()).collect();
+
+        let mut f = std::fs::File::create(&temp_path)?;
+
+        writeln!(f, "y=0x{:08X}", self.y)?;
+        writeln!(f, "x_offset=0x{:08X}", self.x_offset)?;
+        match self.y_order {
+            YOrder::Sequential => writeln!(f, "order=sequential")?,
+            YOrder::Random(seed) => {
+                writeln!(f, "order=random")?;
+                writeln!(f, "order_seed=0x{seed:016X}")?;
+                writeln!(f, "y_position=0x{:08X}", self.y_position)?;
+            }
+            YOrder::Gray => {
+                writeln!(f, "order=gray")?;
+                writeln!(f, "y_position=0x{:08X}", self.y_position)?;
+            }
+        }
+        writeln!(f, "y_first=0x{:08X}", self.y_first)?;
+        writeln!(f, "y_end=0x{:08X}", self.y_end)?;
+        if self.x_stride != 1 {
+            writeln!(f, "x_stride=0x{:08X}", self.x_stride)?;
+        }
+        writeln!(f, "y_bits={}", y_bits.join(","))?;
+        writeln!(f, "x_bits={}", x_bits.join(","))?;
+        writeln!(f, "seed=0x{:02X}", self.seed)?;
+        writeln!(f, "target_checksum=0x{:012X}", self.target_checksum)?;
+        writeln!(f, "ipl3_sha256={}", self.ipl3_sha256)?;
+
+        f.sync_all()?;
+
+        std::fs::rename(temp_path, path)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn check_parameters(&self, current: &Checkpoint) -> Result<(), HasherError> {
+        if self.ipl3_sha256 != current.ipl3_sha256 {
+            return Err(HasherError::CheckpointMismatch("IPL3 contents"));
+        }
+        if self.y_bits != current.y_bits {
+            return Err(HasherError::CheckpointMismatch("Y bits"));
+        }
+        if self.x_bits != current.x_bits {
+            return Err(HasherError::CheckpointMismatch("X bits"));
+        }
+        if self.seed != current.seed {
+            return Err(HasherError::CheckpointMismatch("seed"));
+        }
+        if self.target_checksum != current.target_checksum {
+            return Err(HasherError::CheckpointMismatch("target checksum"));
+        }
+        if (self.y_first, self.y_end) != (current.y_first, current.y_end) {
+            return Err(HasherError::CheckpointMismatch("Y range"));
+        }
+        if self.y_order != current.y_order {
+            return Err(HasherError::CheckpointMismatch("Y order"));
+        }
+        if self.x_stride != current.x_stride {
+            return Err(HasherError::CheckpointMismatch("X stride"));
+        }
+        Ok(())
+    }
+}
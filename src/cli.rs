@@ -1,41 +1,649 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
+
+const EXIT_STATUS_HELP: &str = "Exit status:
+    0  Success, or a collision was found (and the ROM was signed if requested)
+    1  Error (including invalid arguments)
+    2  --time-limit reached before the search finished
+    3  --max-rounds reached before the search finished
+    4  Searched space exhausted without a match
+    5  The checksum doesn't match the target (verify, sign), or no seed does (compute --seed-scan)
+  130  Interrupted (Ctrl+C / SIGTERM), resume position printed or checkpoint saved";
 
 #[derive(Parser)]
+#[command(args_override_self = true, after_help = EXIT_STATUS_HELP)]
 pub struct Cli {
-    /// Path to the source ROM file with IPL3 to be brute forced
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Print progress and results as JSON objects, one per line
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Append timestamped round timings and results to this file, regardless of --quiet and --json
+    #[arg(long, global = true)]
+    pub log_file: Option<std::path::PathBuf>,
+
+    /// Only print the final result
+    #[arg(short = 'q', long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print additional details like GPU limits and per-dispatch timings
+    #[arg(short = 'v', long, global = true)]
+    pub verbose: bool,
+
+    /// Read default option values from this TOML file (ipl3hasher.toml in the working directory is used if present)
+    #[arg(long, global = true)]
+    pub config: Option<std::path::PathBuf>,
+
+    /// Check the checksum algorithm against the built-in known-answer vectors before running the command
+    #[arg(long, global = true)]
+    pub self_test: bool,
+
+    /// Only warn when the --self-test checksums don't match instead of stopping
+    #[arg(long, global = true, requires = "self_test")]
+    pub ignore_self_test: bool,
+
+    /// Options that got their value from the config file
+    #[arg(skip)]
+    pub config_values: Vec<String>,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Brute force a checksum collision (default when no command is given)
+    Search(Box<SearchArgs>),
+
+    /// Sign the ROM with a known Y/X pair, only if the pair matches the target checksum
+    Sign(SignArgs),
+
+    /// Verify a known Y/X pair against the ROM on the CPU
+    Verify(VerifyArgs),
+
+    /// Print the checksum of the ROM as-is
+    Compute(ComputeArgs),
+
+    /// List available GPU adapters
+    Gpus(GpusArgs),
+
+    /// Cross-check the GPU shaders against the CPU on random IPL3s, seeds, bits and Y/X values
+    #[command(visible_alias = "fuzz")]
+    DifferentialTest(DifferentialTestArgs),
+
+    /// List known CICs with their seeds and checksums
+    Cics,
+
+    /// Print a shell completion script to stdout
+    Completions {
+        /// The shell to generate the completion script for
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct RomArgs {
+    /// Path to the source ROM file with IPL3, or - to read it from stdin
     pub rom: std::path::PathBuf,
 
+    /// The ROM file byte order, detected from the first four bytes by default
+    #[arg(long, visible_alias = "endianness", default_value("auto"))]
+    pub format: FormatType,
+
+    /// Byte offset of the IPL3 in the ROM file (must be 4-byte aligned)
+    #[arg(long, default_value("64"), value_parser = ipl3_offset_parser)]
+    pub ipl3_offset: u64,
+
+    /// Treat the source file as a raw IPL3 without a ROM header (implied for files of the IPL3 size, 4032 bytes by default)
+    #[arg(long, conflicts_with = "ipl3_offset")]
+    pub raw_ipl3: bool,
+
+    /// The IPL3 length in 32-bit words, hashed with the same algorithm (the Y and X bits must be within it)
+    #[arg(long, default_value("1008"), value_parser = clap::value_parser!(u32).range(4..=MAX_IPL3_WORDS as i64))]
+    pub ipl3_words: u32,
+}
+
+#[derive(clap::Args)]
+pub struct TargetArgs {
+    /// The CIC for which a checksum must be calculated (detected from the bootcode in the ROM, 6102 if it isn't a known one)
+    #[arg(short = 'c', long, value_parser = cic_parser)]
+    pub cic: Option<(u8, u64)>,
+
+    /// Custom seed byte in hex, used instead of a known CIC (requires --checksum)
+    #[arg(long = "seed", value_name = "SEED", value_parser = seed_parser, requires = "custom_checksum", conflicts_with = "cic")]
+    pub custom_seed: Option<u8>,
+
+    /// Custom 48-bit target checksum in hex, used instead of a known CIC (requires --seed or --seed-scan)
+    #[arg(long = "checksum", value_name = "CHECKSUM", value_parser = checksum_parser, conflicts_with = "cic")]
+    pub custom_checksum: Option<u64>,
+}
+
+#[derive(clap::Args)]
+pub struct BitsArgs {
+    /// Y bits to use: 32-bit word indices (decimal, 0x hex or @ROM byte address) and bit ranges (eg: 40[16..8],0x38[24..12],@0xFF8[..15]) in either order ([31..0] and [0..31] select the same bits, always stored most significant bit first), with optional excluded bits (eg: 1022[31..0]!{14,15}), or a named preset (eg: preset:last-word).
+    #[arg(short = 'b', long, default_value("1022[31..0]"), value_parser = y_bits_parser)]
+    pub y_bits: std::vec::Vec<u32>,
+
+    /// X bits to use, in the same format as the Y bits (the X search space is 2^bits per Y)
+    #[arg(long, default_value("1023[31..0]"), value_parser = x_bits_parser)]
+    pub x_bits: std::vec::Vec<u32>,
+}
+
+#[derive(clap::Args)]
+pub struct PairArgs {
+    /// The Y value in hex
+    #[arg(long = "y", value_name = "Y", value_parser = u32_from_hex_str)]
+    pub y: u32,
+
+    /// The X value in hex
+    #[arg(long = "x", value_name = "X", value_parser = u32_from_hex_str)]
+    pub x: u32,
+}
+
+#[derive(clap::Args)]
+pub struct BackupArgs {
+    /// Sign the source ROM file in place without copying it to <ROM>.bak first
+    #[arg(long, conflicts_with = "force_backup")]
+    pub no_backup: bool,
+
+    /// Overwrite the <ROM>.bak backup of the source ROM file if it already exists
+    #[arg(long)]
+    pub force_backup: bool,
+}
+
+#[derive(clap::Args)]
+pub struct GpuArgs {
+    /// The GPU to use (0 for first, 1 for second, etc.) or a case-insensitive part of its name, comma separated to search on multiple GPUs (eg: 0,1)
+    #[arg(short = 'd', long, default_value("0"), value_delimiter = ',', action = clap::ArgAction::Set, value_parser = gpu_adapter_parser)]
+    pub gpu_adapter: Vec<crate::gpu::GPUAdapterSelector>,
+
+    /// The GPU backend to use
+    #[arg(short = 'g', long, default_value("auto"))]
+    pub backend: BackendType,
+
+    /// Order the GPU adapters by type so that index 0 is the discrete GPU (high-performance) or the integrated one (low-power), for hybrid graphics laptops
+    #[arg(long, default_value("none"))]
+    pub power_preference: PowerPreferenceType,
+
+    /// Also use software GPU adapters (lavapipe, llvmpipe, WARP), running on the CPU, for testing the GPU path on machines without a GPU
+    #[arg(long)]
+    pub allow_software_adapter: bool,
+}
+
+#[derive(clap::Args)]
+#[command(group = clap::ArgGroup::new("checkpoint_file").multiple(true))]
+pub struct SearchArgs {
+    #[command(flatten)]
+    pub rom: RomArgs,
+
     /// Sign the source ROM file with found collision data
     #[arg(short = 's', long)]
     pub sign: bool,
 
-    /// The CIC for which a checksum must be calculated
-    #[arg(short = 'c', long, default_value("6102"), value_parser = cic_parser)]
-    pub cic: (u8, u64),
+    /// Keep searching after a collision is found and list all of them at the end
+    #[arg(long, conflicts_with = "sign")]
+    pub find_all: bool,
 
-    /// Y bits to use: 32-bit word indices and bit ranges (eg: 40[16..8],56[24..12]).
-    #[arg(short = 'b', long, default_value("1022[31..0]"), value_parser = y_bits_parser)]
-    pub y_bits: std::vec::Vec<u32>,
+    /// UNSAFE: trust the GPU results without verifying them on the CPU (ignored with --sign)
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// Sign a copy of the source ROM file written to this path, leaving the source untouched
+    #[arg(short = 'o', long, requires = "sign")]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Overwrite the output ROM file if it already exists
+    #[arg(short = 'f', long, requires = "output")]
+    pub force: bool,
+
+    /// Recalculate the CRC1/CRC2 pair in the ROM header for the CIC seed after signing, for the emulators and flashcarts checking it
+    #[arg(long, requires = "sign")]
+    pub fix_header_crc: bool,
+
+    #[command(flatten)]
+    pub backup: BackupArgs,
+
+    /// Append every verified collision to this file as a line of JSON (time, ROM name, IPL3 SHA-256, seed, checksum, bits, Y and X), for sign --result
+    #[arg(long)]
+    pub results_file: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    pub target: TargetArgs,
+
+    /// Try every seed byte from 0x00 to 0xFF against the target checksum instead of a single seed
+    #[arg(long, conflicts_with_all = ["custom_seed", "benchmark", "checkpoint", "resume"])]
+    pub seed_scan: bool,
+
+    #[command(flatten)]
+    pub bits: BitsArgs,
 
     /// The Y coordinate to start with
     #[arg(short = 'y', long, default_value("0"))]
     pub y_init: u32,
 
-    /// The GPU to use (0 for first, 1 for second, etc.)
-    #[arg(short = 'd', long, default_value("0"))]
-    pub gpu_adapter: usize,
+    /// The X offset to start the first Y round with (requires --y-init)
+    #[arg(short = 'x', long, default_value("0"), requires = "y_init")]
+    pub x_init: u32,
+
+    /// The last Y coordinate to check (defaults to the end of the Y bits space)
+    #[arg(short = 'e', long)]
+    pub y_end: Option<u32>,
+
+    /// The order in which the Y values are visited
+    #[arg(long, default_value("sequential"))]
+    pub order: OrderType,
 
-    /// The number of workgroups to use (x,y,z format, total threads = x*y*z*256)
-    #[arg(short = 'w', long, default_value("256,256,256"), value_parser = workgroups_parser)]
-    pub workgroups: (u32, u32, u32),
+    /// Seed of the pseudo-random Y permutation, the same seed always gives the same order
+    #[arg(long, required_if_eq("order", "random"), value_parser = u64_from_dec_or_hex_str)]
+    pub order_seed: Option<u64>,
 
-    /// The shader module to use
-    #[arg(short = 'z', long, default_value("glsl"))]
+    /// Search only slice i of the Y space split into n contiguous slices (i/n format, eg: 0/3)
+    #[arg(long, value_parser = shard_parser, conflicts_with_all = ["y_init", "y_end"])]
+    pub shard: Option<(u32, u32)>,
+
+    /// Run the X search on all CPU cores instead of the GPU (much slower)
+    #[arg(long)]
+    pub cpu_only: bool,
+
+    /// Run the X search on the CPU instead of failing when no GPU adapter is found
+    #[arg(long, conflicts_with_all = ["cpu_only", "shader_check", "auto_tune"])]
+    pub fallback_cpu: bool,
+
+    /// The number of CPU threads to search and to verify GPU matches on (defaults to the number of logical cores)
+    #[arg(short = 't', long, value_parser = threads_parser)]
+    pub threads: Option<usize>,
+
+    #[command(flatten)]
+    pub gpu: GpuArgs,
+
+    /// The number of workgroups to use (x,y,z format, total threads = x*y*z*local size), or auto to derive them from the GPU limits and speed, colon separated for each GPU (eg: 256,256,256:auto)
+    #[arg(short = 'w', long, default_value("256,256,256"), value_delimiter = ':', action = clap::ArgAction::Set, value_parser = workgroups_parser)]
+    pub workgroups: Vec<crate::gpu::GPUWorkgroupsSelector>,
+
+    /// The number of threads in a single workgroup, comma separated for each GPU (eg: 256,128)
+    #[arg(short = 'l', long, default_value("256"), value_delimiter = ',', action = clap::ArgAction::Set, value_parser = local_size_parser)]
+    pub local_size: Vec<u32>,
+
+    /// The shader module to use, auto picks WGSL on backends without SPIR-V input (GL) and GLSL elsewhere
+    #[arg(
+        short = 'z',
+        long,
+        visible_alias = "shader-lang",
+        default_value("auto")
+    )]
     pub shader: ShaderType,
+
+    /// Compile this GLSL file at startup instead of the embedded hasher.glsl, for shader development
+    #[arg(long, value_name = "FILE")]
+    pub shader_path: Option<std::path::PathBuf>,
+
+    /// Periodically save the search progress to this file
+    #[arg(long, group = "checkpoint_file")]
+    pub checkpoint: Option<std::path::PathBuf>,
+
+    /// Interval in seconds between checkpoint file writes
+    #[arg(long, default_value("60"), requires = "checkpoint_file")]
+    pub checkpoint_interval: u64,
+
+    /// Resume the search from a checkpoint file (keeps checkpointing to it unless --checkpoint is given)
+    #[arg(long, group = "checkpoint_file", conflicts_with_all = ["y_init", "x_init"])]
+    pub resume: Option<std::path::PathBuf>,
+
+    /// Stop the search cleanly after this much wall-clock time, e.g. 2h30m (saves a checkpoint if enabled)
+    #[arg(long, value_parser = duration_parser, conflicts_with = "benchmark")]
+    pub time_limit: Option<std::time::Duration>,
+
+    /// Stop after this many completed Y rounds and print the Y to continue from
+    #[arg(long, value_parser = max_rounds_parser, conflicts_with_all = ["seed_scan", "benchmark"])]
+    pub max_rounds: Option<u32>,
+
+    /// Interval in seconds between periodic hash rate statistics lines (0 disables them)
+    #[arg(long, default_value("0"))]
+    pub stats_interval: u64,
+
+    /// Show the search progress with hash rate and estimated time remaining instead of per-round lines
+    #[arg(short = 'p', long)]
+    pub progress: bool,
+
+    /// Initialize everything, print the search plan and exit without dispatching any work
+    #[arg(long, conflicts_with = "benchmark")]
+    pub dry_run: bool,
+
+    /// Measure the hash rate of the current configuration without searching and exit
+    #[arg(long, conflicts_with_all = ["sign", "checkpoint", "resume"])]
+    pub benchmark: bool,
+
+    /// The number of timed dispatches to run for each benchmarked configuration
+    #[arg(long, default_value("8"), requires = "benchmark")]
+    pub benchmark_dispatches: u32,
+
+    /// Benchmark dispatches that find a match at their first X value, with the early exit disabled and with --early-exit-period, to measure what it saves
+    #[arg(long, requires = "benchmark", conflicts_with = "cpu_only")]
+    pub benchmark_early_match: bool,
+
+    /// Benchmark a range of workgroup and local size configurations and rank them
+    #[arg(long, requires = "benchmark", conflicts_with = "cpu_only")]
+    pub benchmark_sweep: bool,
+
+    /// Run a few thousand X values through both GPU shaders, compare them with the CPU and exit
+    #[arg(long, conflicts_with_all = ["cpu_only", "benchmark", "dry_run", "auto_tune"])]
+    pub shader_check: bool,
+
+    /// Print the CPU states for the --y and --x values with the state each GPU calculates before the final mix and exit, to find where a shader diverges from the CPU
+    #[arg(long, requires = "dump_y", conflicts_with_all = ["benchmark", "dry_run", "shader_check", "auto_tune"])]
+    pub dump_state: bool,
+
+    /// The Y value in hex for --dump-state
+    #[arg(long = "y", value_name = "Y", value_parser = u32_from_hex_str, requires = "dump_state")]
+    pub dump_y: Option<u32>,
+
+    /// The X value in hex for --dump-state (0 by default)
+    #[arg(long = "x", value_name = "X", value_parser = u32_from_hex_str, requires = "dump_state")]
+    pub dump_x: Option<u32>,
+
+    /// Benchmark a few workgroup and local size configurations before the search and use the fastest
+    #[arg(long, conflicts_with_all = ["cpu_only", "benchmark", "dry_run", "workgroups", "local_size"])]
+    pub auto_tune: bool,
+
+    /// Compile the target checksum into the GLSL shader as constants instead of reading it from the input (with --benchmark both variants are measured)
+    #[arg(long, conflicts_with = "cpu_only")]
+    pub specialize_shader: bool,
+
+    /// Multiply 32-bit halves in the shaders even when the GPU supports 64-bit integers
+    #[arg(long, conflicts_with = "cpu_only")]
+    pub no_shader_int64: bool,
+
+    /// Let wgpu translate the GLSL shader even when the GPU takes SPIR-V as it is, which shifts the 64-bit products instead of splitting them with unpackUint2x32
+    #[arg(long, conflicts_with = "cpu_only")]
+    pub no_spirv_passthrough: bool,
+
+    /// Don't warn when an integrated GPU or a CPU adapter is selected while a discrete GPU is available
+    #[arg(long, conflicts_with = "cpu_only")]
+    pub no_adapter_warning: bool,
+
+    /// Enable the GPU validation layers, print every GPU error with the objects involved and compile the GLSL shader without optimizations (slow, for troubleshooting)
+    #[arg(long, conflicts_with = "cpu_only")]
+    pub gpu_debug: bool,
+
+    /// Don't run a throwaway dispatch before the first one, to measure the cold start of the GPU (shader compilation on the first dispatch)
+    #[arg(long, conflicts_with = "cpu_only")]
+    pub no_warmup: bool,
+
+    /// Don't check a few random Y and X values on the GPU against the CPU before the search
+    #[arg(long, conflicts_with = "cpu_only")]
+    pub skip_self_test: bool,
+
+    /// Seed of the random Y and X values of the GPU self-test, to rerun a failed self-test with the same values (random by default)
+    #[arg(long, value_parser = u64_from_dec_or_hex_str, conflicts_with_all = ["cpu_only", "skip_self_test"])]
+    pub self_test_seed: Option<u64>,
+
+    /// Let GPU invocations return early once a match was found in their dispatch, the X bits search checks again every this many words of its loop (0 disables it, skipped X values would be lost with --find-all)
+    #[arg(long, default_value("0"), conflicts_with_all = ["cpu_only", "find_all"])]
+    pub early_exit_period: u32,
+
+    /// Hash the X offset times this odd stride (wrapping in the X space) in every GPU invocation
+    ///
+    /// The stride still visits every X value. X offsets in the output, checkpoints and --x-init count in the stride order. The CPU search doesn't apply the stride, so it can't be used with --cpu-only or --fallback-cpu
+    #[arg(long, default_value("1"), value_parser = x_stride_parser, conflicts_with_all = ["cpu_only", "fallback_cpu"])]
+    pub x_stride: u32,
+
+    /// Search the X bits of this many consecutive Y values in every GPU dispatch, for X bits spaces smaller than a dispatch (at most 1024; not used with a whole X word)
+    #[arg(long, default_value("1"), value_parser = y_batch_parser, conflicts_with = "cpu_only")]
+    pub y_batch: u32,
+
+    /// Time the GPU kernel with timestamp queries and report it for each dispatch in verbose output
+    #[arg(long, conflicts_with = "cpu_only")]
+    pub gpu_timings: bool,
+
+    /// How many times in a row a lost GPU device is recreated and the dispatch retried before giving up
+    #[arg(long, default_value("3"), conflicts_with = "cpu_only")]
+    pub max_device_resets: u32,
+
+    /// Split dispatches into several GPU submissions of at most this many milliseconds each, to stay under the OS GPU watchdog (0 disables it)
+    #[arg(long, default_value("500"), conflicts_with = "cpu_only")]
+    pub max_dispatch_ms: u64,
+
+    /// Scale the workgroups after every dispatch so that the dispatches take about this many milliseconds, following GPU clock changes and the cost of the X search
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["cpu_only", "benchmark", "auto_tune"])]
+    pub target_dispatch_ms: Option<u64>,
+}
+
+#[derive(clap::Args)]
+pub struct SignArgs {
+    #[command(flatten)]
+    pub rom: RomArgs,
+
+    #[command(flatten)]
+    pub target: TargetArgs,
+
+    #[command(flatten)]
+    pub bits: BitsArgs,
+
+    #[command(flatten)]
+    pub pair: Option<PairArgs>,
+
+    /// Sign with the entry at this index of the --results-file instead of --y/--x, along with its seed, checksum and bits (0 is the first entry, -1 the last one)
+    #[arg(
+        long,
+        requires = "results_file",
+        required_unless_present_any = ["y", "x"],
+        conflicts_with_all = ["y", "x"],
+        allow_negative_numbers = true
+    )]
+    pub result: Option<i64>,
+
+    /// The results file written by search --results-file to read the --result entry from
+    #[arg(long, requires = "result")]
+    pub results_file: Option<std::path::PathBuf>,
+
+    /// Sign a copy of the source ROM file written to this path, leaving the source untouched
+    #[arg(short = 'o', long)]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Overwrite the output ROM file if it already exists
+    #[arg(short = 'f', long, requires = "output")]
+    pub force: bool,
+
+    /// Recalculate the CRC1/CRC2 pair in the ROM header for the CIC seed after signing, for the emulators and flashcarts checking it
+    #[arg(long)]
+    pub fix_header_crc: bool,
+
+    #[command(flatten)]
+    pub backup: BackupArgs,
+}
+
+#[derive(clap::Args)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    pub rom: RomArgs,
+
+    #[command(flatten)]
+    pub target: TargetArgs,
+
+    #[command(flatten)]
+    pub bits: BitsArgs,
+
+    #[command(flatten)]
+    pub pair: PairArgs,
+}
+
+#[derive(clap::Args)]
+pub struct ComputeArgs {
+    #[command(flatten)]
+    pub rom: RomArgs,
+
+    #[command(flatten)]
+    pub target: TargetArgs,
+
+    /// Try every seed byte from 0x00 to 0xFF and list the ones matching the target checksum
+    #[arg(long, conflicts_with = "custom_seed")]
+    pub seed_scan: bool,
+}
+
+#[derive(clap::Args)]
+pub struct GpusArgs {
+    #[command(flatten)]
+    pub gpu: GpuArgs,
+}
+
+#[derive(clap::Args)]
+pub struct DifferentialTestArgs {
+    #[command(flatten)]
+    pub gpu: GpuArgs,
+
+    /// The number of workgroups to use (x,y,z format), or auto, colon separated for each GPU. Every case is a single dispatch of this size
+    #[arg(short = 'w', long, default_value("256,16,1"), value_delimiter = ':', action = clap::ArgAction::Set, value_parser = workgroups_parser)]
+    pub workgroups: Vec<crate::gpu::GPUWorkgroupsSelector>,
+
+    /// The number of threads in a single workgroup, comma separated for each GPU (eg: 256,128)
+    #[arg(short = 'l', long, default_value("256"), value_delimiter = ',', action = clap::ArgAction::Set, value_parser = local_size_parser)]
+    pub local_size: Vec<u32>,
+
+    /// The shader module to test, auto picks WGSL on backends without SPIR-V input (GL) and GLSL elsewhere
+    #[arg(short = 'z', long, default_value("auto"))]
+    pub shader: ShaderType,
+
+    /// The number of random cases to run (100 unless --duration is given)
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub iterations: Option<u64>,
+
+    /// Stop after this much wall-clock time, e.g. 10m
+    #[arg(long, value_parser = duration_parser)]
+    pub duration: Option<std::time::Duration>,
+
+    /// Seed of the first case, the following cases take the next seeds (random by default)
+    #[arg(long, value_parser = u64_from_dec_or_hex_str)]
+    pub random_seed: Option<u64>,
+
+    /// Let wgpu translate the GLSL shader even when the GPU takes SPIR-V as it is, to test the path without unpackUint2x32
+    #[arg(long)]
+    pub no_spirv_passthrough: bool,
+}
+
+pub struct BitsPreset {
+    pub name: &'static str,
+    pub bits: &'static str,
+}
+
+pub const BITS_PRESETS: &[BitsPreset] = &[
+    BitsPreset {
+        name: "last-word",
+        bits: "1022[31..0]",
+    },
+    BitsPreset {
+        name: "last-word-low",
+        bits: "1022[15..0]",
+    },
+    BitsPreset {
+        name: "padding-1022-1021",
+        bits: "1021[15..0],1022[15..0]",
+    },
+];
+
+pub struct Cic {
+    pub names: &'static [&'static str],
+    pub seed: u8,
+    /// The checksum of the stock bootcode with the seed, which also identifies the bootcode
+    pub checksum: u64,
+}
+
+impl Cic {
+    pub fn get_name(&self) -> String {
+        self.names.join("/")
+    }
+}
+
+/// The CIC used when neither --cic is given nor the bootcode is a known one
+pub const DEFAULT_CIC: &str = "6102";
+
+pub const CICS: &[Cic] = &[
+    Cic {
+        names: &["6101"],
+        seed: 0x3F,
+        checksum: 0x45CC73EE317A,
+    },
+    Cic {
+        names: &["6102", "7101"],
+        seed: 0x3F,
+        checksum: 0xA536C0F1D859,
+    },
+    Cic {
+        names: &["6103", "7103"],
+        seed: 0x78,
+        checksum: 0x586FD4709867,
+    },
+    Cic {
+        names: &["6105", "7105"],
+        seed: 0x91,
+        checksum: 0x8618A45BC2D3,
+    },
+    Cic {
+        names: &["6106", "7106"],
+        seed: 0x85,
+        checksum: 0x2BBAD4E6EB74,
+    },
+    Cic {
+        names: &["8303"],
+        seed: 0xDD,
+        checksum: 0x32B294E2AB90,
+    },
+    Cic {
+        names: &["8401"],
+        seed: 0xDD,
+        checksum: 0x6EE8D9E84970,
+    },
+    Cic {
+        names: &["5167"],
+        seed: 0xDD,
+        checksum: 0x083C6C77E0B1,
+    },
+    Cic {
+        names: &["DDUS"],
+        seed: 0xDE,
+        checksum: 0x05BA2EF0A5F1,
+    },
+];
+
+#[derive(Clone, clap::ValueEnum)]
+pub enum FormatType {
+    Auto,
+    /// Big-endian, also accepted as "big"
+    #[value(alias = "big")]
+    Z64,
+    /// Byteswapped 16-bit words, also accepted as "byteswapped"
+    #[value(alias = "byteswapped")]
+    V64,
+    /// Little-endian 32-bit words, also accepted as "little"
+    #[value(alias = "little")]
+    N64,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+pub enum BackendType {
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+pub enum PowerPreferenceType {
+    HighPerformance,
+    LowPower,
+    None,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+pub enum OrderType {
+    Sequential,
+    /// Pseudo-random permutation of the Y range (requires --order-seed)
+    Random,
+    /// Gray code order, only one Y bit changes between rounds which speeds up the Y round on the CPU
+    Gray,
 }
 
 #[derive(Clone, clap::ValueEnum)]
 pub enum ShaderType {
+    Auto,
     Glsl,
     Wgsl,
 }
@@ -44,87 +652,374 @@ fn u32_from_str(str: &str) -> Result<u32, String> {
     u32::from_str_radix(str, 10).map_err(|e| e.to_string())
 }
 
+fn u64_from_hex_str(str: &str) -> Result<u64, String> {
+    let digits = str
+        .strip_prefix("0x")
+        .or(str.strip_prefix("0X"))
+        .unwrap_or(str);
+    u64::from_str_radix(digits, 16).map_err(|e| e.to_string())
+}
+
+fn u32_from_dec_or_hex_str(str: &str) -> Result<u32, String> {
+    if str.starts_with("0x") || str.starts_with("0X") {
+        u32_from_hex_str(str)
+    } else {
+        u32_from_str(str)
+    }
+}
+
+fn u64_from_dec_or_hex_str(str: &str) -> Result<u64, String> {
+    if str.starts_with("0x") || str.starts_with("0X") {
+        u64_from_hex_str(str)
+    } else {
+        str.parse()
+            .map_err(|e: std::num::ParseIntError| e.to_string())
+    }
+}
+
+fn u32_from_hex_str(str: &str) -> Result<u32, String> {
+    let value = u64_from_hex_str(str)?;
+    u32::try_from(value).map_err(|_| format!("value doesn't fit in 32 bits: 0x{value:X}"))
+}
+
+fn seed_parser(str: &str) -> Result<u8, String> {
+    let seed = u64_from_hex_str(str)?;
+    u8::try_from(seed).map_err(|_| format!("seed doesn't fit in 8 bits: 0x{seed:X}"))
+}
+
+fn checksum_parser(str: &str) -> Result<u64, String> {
+    let checksum = u64_from_hex_str(str)?;
+    if checksum > 0xFFFFFFFFFFFF {
+        return Err(format!("checksum doesn't fit in 48 bits: 0x{checksum:X}"));
+    }
+    Ok(checksum)
+}
+
+fn gpu_adapter_parser(str: &str) -> Result<crate::gpu::GPUAdapterSelector, String> {
+    if str.is_empty() {
+        return Err(String::from("empty GPU adapter name"));
+    }
+    Ok(match str.parse() {
+        Ok(index) => crate::gpu::GPUAdapterSelector::Index(index),
+        Err(_) => crate::gpu::GPUAdapterSelector::Name(str.to_string()),
+    })
+}
+
+fn max_rounds_parser(str: &str) -> Result<u32, String> {
+    let max_rounds = u32_from_str(str)?;
+    if max_rounds == 0 {
+        return Err(String::from("max rounds must be greater than 0"));
+    }
+    Ok(max_rounds)
+}
+
+fn x_stride_parser(str: &str) -> Result<u32, String> {
+    let x_stride = u32_from_dec_or_hex_str(str)?;
+    if x_stride % 2 == 0 {
+        return Err(format!(
+            "X stride must be odd to visit every X value: {x_stride}"
+        ));
+    }
+    Ok(x_stride)
+}
+
+fn y_batch_parser(str: &str) -> Result<u32, String> {
+    const MAX_Y_BATCH: u32 = 1024;
+
+    let y_batch = u32_from_str(str)?;
+    if y_batch == 0 || y_batch > MAX_Y_BATCH {
+        return Err(format!("Y batch must be between 1 and {MAX_Y_BATCH}"));
+    }
+    Ok(y_batch)
+}
+
+fn shard_parser(str: &str) -> Result<(u32, u32), String> {
+    let (index, count) = str
+        .split_once('/')
+        .ok_or(String::from("invalid shard format, expected i/n"))?;
+    let (index, count) = (u32_from_str(index)?, u32_from_str(count)?);
+    if count == 0 {
+        return Err(String::from("shard count must be greater than 0"));
+    }
+    if index >= count {
+        return Err(format!("shard index must be lower than {count}"));
+    }
+    Ok((index, count))
+}
+
+fn duration_parser(str: &str) -> Result<std::time::Duration, String> {
+    let mut seconds: u64 = 0;
+    let mut rest = str.trim();
+
+    if rest.is_empty() {
+        return Err(String::from("empty duration"));
+    }
+
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("expected a number in duration: {str}"));
+        }
+        let value: u64 = rest[..digits].parse().map_err(|e| format!("{e}"))?;
+        rest = &rest[digits..];
+
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let multiplier = match rest[..unit].trim() {
+            "d" => 24 * 60 * 60,
+            "h" => 60 * 60,
+            "m" | "min" => 60,
+            "s" | "sec" => 1,
+            "" => return Err(format!("missing unit in duration: {str}")),
+            unit => return Err(format!("unknown duration unit: {unit}")),
+        };
+        rest = rest[unit..].trim_start();
+
+        seconds = value
+            .checked_mul(multiplier)
+            .and_then(|value| seconds.checked_add(value))
+            .ok_or(format!("duration is too long: {str}"))?;
+    }
+
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
+pub fn find_cic(name: &str) -> Option<&'static Cic> {
+    CICS.iter().find(|cic| cic.names.contains(&name))
+}
+
+/// The CIC whose stock bootcode the IPL3 is, None for a modified or homebrew IPL3
+pub fn detect_cic(ipl3: &[u8]) -> Option<&'static Cic> {
+    if ipl3.len() != crate::rom::IPL3_WORDS * 4 {
+        return None;
+    }
+
+    CICS.iter()
+        .find(|cic| crate::hasher::Hasher::compute_ipl3(ipl3, cic.seed) == cic.checksum)
+}
+
 fn cic_parser(str: &str) -> Result<(u8, u64), String> {
-    let (seed, target_checksum) = match str {
-        "6101" => (0x3F, 0x45CC73EE317A),
-        "6102" | "7101" => (0x3F, 0xA536C0F1D859),
-        "6103" | "7103" => (0x78, 0x586FD4709867),
-        "6105" | "7105" => (0x91, 0x8618A45BC2D3),
-        "6106" | "7106" => (0x85, 0x2BBAD4E6EB74),
-        "8303" => (0xDD, 0x32B294E2AB90),
-        "8401" => (0xDD, 0x6EE8D9E84970),
-        "5167" => (0xDD, 0x083C6C77E0B1),
-        "DDUS" => (0xDE, 0x05BA2EF0A5F1),
-        _ => return Err(format!("Unknown CIC")),
+    find_cic(str)
+        .map(|cic| (cic.seed, cic.checksum))
+        .ok_or(format!("Unknown CIC"))
+}
+
+fn bits_index_parser(str: &str, name: &str) -> Result<u32, String> {
+    match str.strip_prefix('@') {
+        Some(address) => {
+            let address = u32_from_dec_or_hex_str(address)?;
+            if address % 4 != 0 {
+                return Err(format!("unaligned {name} bits address: 0x{address:X}"));
+            }
+            Ok(address / 4)
+        }
+        None => u32_from_dec_or_hex_str(str),
+    }
+}
+
+fn bits_range_parser(str: &str, name: &str, index: u32) -> Result<(u32, u32), String> {
+    let (first, last) = str.split_once("..").ok_or(format!(
+        "invalid {name} bits range format for index {index}"
+    ))?;
+
+    let first = if first.is_empty() {
+        0
+    } else {
+        u32_from_str(first)?
+    };
+    let last = if last.is_empty() {
+        31
+    } else {
+        u32_from_str(last)?
     };
-    Ok((seed, target_checksum))
+
+    let (start, end) = (first.min(last), first.max(last));
+
+    if end >= 32 {
+        return Err(format!(
+            "invalid {name} bits range for index {index}: 0 <= {start} <= {end} < 32"
+        ));
+    }
+
+    Ok((start, end))
 }
 
-fn y_bits_parser(str: &str) -> Result<Vec<u32>, String> {
-    let slices: Vec<&str> = str.split(',').collect();
+fn bits_preset_parser(str: &str, name: &str) -> Result<String, String> {
+    let (preset, exclude) = match str.split_once('!') {
+        Some((preset, exclude)) => (preset, Some(exclude)),
+        None => (str, None),
+    };
+
+    let preset = BITS_PRESETS
+        .iter()
+        .find(|preset_bits| preset_bits.name == preset)
+        .ok_or_else(|| {
+            let names: Vec<&str> = BITS_PRESETS.iter().map(|preset| preset.name).collect();
+            format!(
+                "unknown {name} bits preset: {preset} (available: {})",
+                names.join(", ")
+            )
+        })?;
+
+    // Exclusions apply to every word of the preset
+    Ok(bits_slices(preset.bits)
+        .iter()
+        .map(|slice| match exclude {
+            Some(exclude) => format!("{slice}!{exclude}"),
+            None => slice.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(","))
+}
 
+fn bits_slices(str: &str) -> Vec<&str> {
+    let mut slices = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in str.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                slices.push(&str[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    slices.push(&str[start..]);
+
+    slices
+}
+
+fn bits_exclude_parser(str: &str, name: &str, index: u32) -> Result<Vec<u32>, String> {
+    let list = str
+        .strip_prefix('{')
+        .and_then(|list| list.strip_suffix('}'))
+        .ok_or(format!(
+            "invalid {name} bits exclusion format for index {index}"
+        ))?;
+
+    let mut bits = vec![];
+
+    for item in list.split(',') {
+        let (start, end) = if item.contains("..") {
+            bits_range_parser(item, name, index)?
+        } else {
+            let bit = u32_from_str(item)?;
+            (bit, bit)
+        };
+        if end >= 32 {
+            return Err(format!(
+                "invalid {name} bits exclusion for index {index}: {end} >= 32"
+            ));
+        }
+        bits.extend(start..=end);
+    }
+
+    Ok(bits)
+}
+
+fn bits_parser(str: &str, name: &str, max_index: u32) -> Result<Vec<u32>, String> {
     let mut values = vec![];
 
-    let mut push_y_bits = |index: u32, start: u32, end: u32| {
-        for i in start..=end {
-            values.push(((index - 16) * 32) + (31 - i));
+    let mut slices = vec![];
+    for slice in bits_slices(str) {
+        match slice.strip_prefix("preset:") {
+            Some(preset) => slices.push(bits_preset_parser(preset, name)?),
+            None => slices.push(slice.to_string()),
         }
-    };
+    }
+    let slices = slices.join(",");
+
+    for slice in bits_slices(&slices) {
+        let (slice, exclude) = match slice.split_once('!') {
+            Some((slice, exclude)) => (slice, Some(exclude)),
+            None => (slice, None),
+        };
 
-    for slice in slices.iter() {
-        let parts: Vec<&str> = slice.split('[').collect();
+        let (index, range) = match slice.split_once('[') {
+            Some((index, range)) => (index, Some(range)),
+            None => (slice, None),
+        };
 
-        if parts.len() == 0 {
-            return Err(format!("empty Y bits index"));
+        if index.is_empty() {
+            return Err(format!("empty {name} bits index"));
         }
 
-        let index = u32_from_str(parts[0])?;
+        let index = bits_index_parser(index, name)?;
 
-        if (index <= 16) || (index >= 1023) {
-            return Err(format!("invalid Y bits index: {index}"));
+        if (index <= 16) || (index > max_index) {
+            return Err(format!("invalid {name} bits index: {index}"));
         }
 
-        match parts.len() {
-            1 => {
-                push_y_bits(index, 0, 31);
-            }
-            2 => {
-                let range_parts: Vec<&str> = parts[1].split("..").collect();
+        let (start, end) = match range {
+            None => (0, 31),
+            Some(range) => bits_range_parser(
+                range
+                    .strip_suffix(']')
+                    .ok_or(format!("invalid {name} bits format for index {index}"))?,
+                name,
+                index,
+            )?,
+        };
 
-                if range_parts.len() != 2 {
-                    return Err(format!("invalid Y bits range format for index {index}"));
-                }
+        let exclude = match exclude {
+            Some(exclude) => bits_exclude_parser(exclude, name, index)?,
+            None => vec![],
+        };
 
-                let end = u32_from_str(range_parts[0])?;
-                let start = u32_from_str(
-                    range_parts[1]
-                        .strip_suffix(']')
-                        .ok_or(format!("invalid Y bits format for index {index}"))?,
-                )?;
-
-                if (start > end) || (end >= 32) {
-                    return Err(format!(
-                        "invalid Y bits range for index {index}: 0 < {start} <= {end} < 32"
-                    ));
-                }
+        if let Some(bit) = exclude.iter().find(|bit| !(start..=end).contains(*bit)) {
+            return Err(format!(
+                "excluded {name} bit {bit} isn't in the range for index {index}"
+            ));
+        }
 
-                push_y_bits(index, start, end);
+        for bit in (start..=end).filter(|bit| !exclude.contains(bit)) {
+            let value = ((index - 16) * 32) + (31 - bit);
+            if values.contains(&value) {
+                return Err(format!(
+                    "overlapping {name} bits at index {index}, bit {bit}"
+                ));
             }
-            _ => return Err(format!("invalid Y bits format for index {index}")),
+            values.push(value);
         }
     }
 
     values.sort();
-    values.dedup();
+
+    if values.is_empty() {
+        return Err(format!("no {name} bits selected"));
+    }
 
     if values.len() > 32 {
-        return Err(format!("too many Y bits: {} (max: 32)", values.len()));
+        return Err(format!("too many {name} bits: {} (max: 32)", values.len()));
     }
 
     Ok(values)
 }
 
-fn workgroups_parser(str: &str) -> Result<(u32, u32, u32), String> {
+/// The longest IPL3 that can be hashed, the bits are checked against the actual length once the
+/// ROM is read
+const MAX_IPL3_WORDS: u32 = 0x10000;
+
+pub fn y_bits_parser(str: &str) -> Result<Vec<u32>, String> {
+    bits_parser(str, "Y", 16 + MAX_IPL3_WORDS - 2)
+}
+
+pub fn x_bits_parser(str: &str) -> Result<Vec<u32>, String> {
+    bits_parser(str, "X", 16 + MAX_IPL3_WORDS - 1)
+}
+
+fn workgroups_parser(str: &str) -> Result<crate::gpu::GPUWorkgroupsSelector, String> {
+    if str == "auto" {
+        return Ok(crate::gpu::GPUWorkgroupsSelector::Auto);
+    }
+
     let slices: Vec<&str> = str.split(',').collect();
 
     if slices.len() > 3 {
@@ -137,9 +1032,514 @@ fn workgroups_parser(str: &str) -> Result<(u32, u32, u32), String> {
         values[i] = u32_from_str(&slice)?;
     }
 
-    Ok((values[0], values[1], values[2]))
+    if values.contains(&0) {
+        return Err(String::from("number of workgroups must be greater than 0"));
+    }
+
+    Ok(crate::gpu::GPUWorkgroupsSelector::Size(
+        values[0], values[1], values[2],
+    ))
+}
+
+fn ipl3_offset_parser(str: &str) -> Result<u64, String> {
+    let offset = str.parse::<u64>().map_err(|e| e.to_string())?;
+
+    if offset % 4 != 0 {
+        return Err(format!("IPL3 offset must be 4-byte aligned: {offset}"));
+    }
+
+    Ok(offset)
+}
+
+fn threads_parser(str: &str) -> Result<usize, String> {
+    let threads = str.parse::<usize>().map_err(|e| e.to_string())?;
+
+    if threads == 0 {
+        return Err(String::from("number of threads must be greater than 0"));
+    }
+
+    Ok(threads)
+}
+
+fn local_size_parser(str: &str) -> Result<u32, String> {
+    let local_size = u32_from_str(str)?;
+
+    if local_size == 0 {
+        return Err(String::from("local size must be greater than 0"));
+    }
+
+    Ok(local_size)
+}
+
+pub fn print_completions(shell: clap_complete::Shell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+}
+
+/// Returns the position of the subcommand, skipping any global options of the command before it
+fn subcommand_index(command: &clap::Command, args: &[std::ffi::OsString]) -> usize {
+    let globals: Vec<&clap::Arg> = command
+        .get_arguments()
+        .filter(|arg| arg.is_global_set())
+        .collect();
+    let takes_value = |arg: &clap::Arg| arg.get_action().takes_values();
+    let mut index = 1;
+
+    while let Some(arg) = args.get(index).map(|arg| arg.to_string_lossy()) {
+        let takes_next = if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = long
+                .split_once('=')
+                .map_or((long, None), |(name, value)| (name, Some(value)));
+            match globals
+                .iter()
+                .find(|global| global.get_long() == Some(name))
+            {
+                Some(global) => takes_value(global) && value.is_none(),
+                None => break,
+            }
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            // A cluster like -vq, where an option taking a value takes the rest of it or the next argument
+            let mut takes_next = None;
+            for (position, short) in shorts.char_indices() {
+                match globals
+                    .iter()
+                    .find(|global| global.get_short() == Some(short))
+                {
+                    Some(global) if takes_value(global) => {
+                        takes_next = Some(position + short.len_utf8() == shorts.len());
+                        break;
+                    }
+                    Some(_) => takes_next = Some(false),
+                    None => {
+                        takes_next = None;
+                        break;
+                    }
+                }
+            }
+            match takes_next {
+                Some(takes_next) => takes_next,
+                None => break,
+            }
+        } else {
+            break;
+        };
+
+        index += if takes_next { 2 } else { 1 };
+    }
+
+    index
+}
+
+fn config_path(args: &[std::ffi::OsString]) -> Option<std::path::PathBuf> {
+    let mut args = args.iter().skip(1).map(|arg| arg.to_string_lossy());
+
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(|path| path.to_string().into());
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.into());
+        }
+    }
+
+    let path = std::path::Path::new(crate::config::DEFAULT_PATH);
+
+    path.exists().then(|| path.to_path_buf())
+}
+
+/// Like clap::Error::exit, but usage errors exit with the common error code instead of 2, which is
+/// taken by the time limit
+fn exit(error: clap::Error) -> ! {
+    let _ = error.print();
+    std::process::exit(if error.use_stderr() {
+        crate::ExitStatus::ERROR_CODE.into()
+    } else {
+        0
+    })
+}
+
+fn exit_with_error(kind: clap::error::ErrorKind, message: impl std::fmt::Display) -> ! {
+    exit(Cli::command().error(kind, message))
+}
+
+fn validate_target(target: &TargetArgs, seed_scan: bool) {
+    if target.custom_checksum.is_some() && target.custom_seed.is_none() && !seed_scan {
+        exit_with_error(
+            clap::error::ErrorKind::MissingRequiredArgument,
+            "--checksum requires either --seed or --seed-scan",
+        );
+    }
+}
+
+fn validate_bits(bits: &BitsArgs) {
+    if let Some(bit) = bits.x_bits.iter().find(|bit| bits.y_bits.contains(bit)) {
+        exit_with_error(
+            clap::error::ErrorKind::ValueValidation,
+            format!(
+                "X bits overlap with Y bits at index {}, bit {}",
+                (bit / 32) + 16,
+                31 - (bit % 32)
+            ),
+        );
+    }
+}
+
+fn validate_sign(rom: &RomArgs) {
+    if crate::rom::is_stdin(&rom.rom) {
+        exit_with_error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "can't sign a ROM read from stdin, there is no file to write it back to",
+        );
+    }
+}
+
+fn validate_gpu_values(gpus: usize, workgroups: usize, local_size: usize) {
+    for (name, count) in [("workgroups", workgroups), ("local size", local_size)] {
+        if count != 1 && count != gpus {
+            exit_with_error(
+                clap::error::ErrorKind::WrongNumberOfValues,
+                format!("expected 1 or {gpus} {name} values, one for each GPU: {count}"),
+            );
+        }
+    }
+}
+
+fn validate_search(args: &SearchArgs) {
+    validate_target(&args.target, args.seed_scan);
+    validate_bits(&args.bits);
+
+    if args.sign {
+        validate_sign(&args.rom);
+    }
+
+    if args.shader_path.is_some() && matches!(args.shader, ShaderType::Wgsl) {
+        exit_with_error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--shader-path takes a GLSL file and can't be used with the WGSL shader",
+        );
+    }
+
+    let gpus = args.gpu.gpu_adapter.len();
+
+    validate_gpu_values(gpus, args.workgroups.len(), args.local_size.len());
+
+    for (name, enabled) in [
+        ("--benchmark-sweep", args.benchmark_sweep),
+        ("--auto-tune", args.auto_tune),
+    ] {
+        if enabled && gpus > 1 {
+            exit_with_error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!("{name} works with a single GPU adapter only"),
+            );
+        }
+    }
+
+    if (args.x_init as u64) >> args.bits.x_bits.len() != 0 {
+        exit_with_error(
+            clap::error::ErrorKind::ValueValidation,
+            format!(
+                "X init doesn't fit in {} X bits: {}",
+                args.bits.x_bits.len(),
+                args.x_init
+            ),
+        );
+    }
+
+    if let Some((_, count)) = args.shard
+        && count as u64 > crate::hasher::Hasher::get_y_max(&args.bits.y_bits) as u64 + 1
+    {
+        exit_with_error(
+            clap::error::ErrorKind::ValueValidation,
+            format!(
+                "shard count is larger than the {} Y bits space: {count}",
+                args.bits.y_bits.len()
+            ),
+        );
+    }
+
+    if let Some(y_end) = args.y_end {
+        let y_max = crate::hasher::Hasher::get_y_max(&args.bits.y_bits);
+
+        if y_end > y_max {
+            exit_with_error(
+                clap::error::ErrorKind::ValueValidation,
+                format!(
+                    "Y end doesn't fit in {} Y bits: {y_end} (max: {y_max})",
+                    args.bits.y_bits.len()
+                ),
+            );
+        }
+
+        if y_end < args.y_init {
+            exit_with_error(
+                clap::error::ErrorKind::ValueValidation,
+                format!("Y end is lower than Y init: {y_end} < {}", args.y_init),
+            );
+        }
+    }
 }
 
 pub fn parse() -> Cli {
-    Cli::parse()
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let mut command = Cli::command();
+    command.build();
+
+    // A bare ROM path (or search option) runs the search, like before the subcommands existed
+    let index = subcommand_index(&command, &args);
+    let subcommand = match args.get(index).map(|arg| arg.to_string_lossy().to_string()) {
+        Some(arg) if command.find_subcommand(&arg).is_some() => Some(arg),
+        Some(arg) if ["-h", "--help"].contains(&arg.as_str()) => None,
+        Some(_) => {
+            args.insert(index, "search".into());
+            Some(String::from("search"))
+        }
+        None => None,
+    };
+
+    let config = subcommand
+        .as_ref()
+        .filter(|name| !["completions", "help"].contains(&name.as_str()))
+        .and_then(|_| config_path(&args));
+    let mut config_options = vec![];
+    let mut config_args = 0;
+
+    if let (Some(path), Some(name)) = (&config, &subcommand) {
+        let config = crate::config::load(path, &command, name)
+            .unwrap_or_else(|error| exit_with_error(clap::error::ErrorKind::InvalidValue, error));
+        config_args = config.args.len();
+        config_options = config.options;
+        // Config values go right after the subcommand so the command line arguments override them
+        args.splice(
+            index + 1..index + 1,
+            config.args.into_iter().map(std::ffi::OsString::from),
+        );
+    }
+
+    let matches = command
+        .try_get_matches_from(args)
+        .unwrap_or_else(|error| exit(error));
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| exit(error));
+
+    // Subcommand argument indices start at the subcommand name, followed by the config values
+    if let Some((_, matches)) = matches.subcommand() {
+        cli.config_values = config_options
+            .into_iter()
+            .filter(|(id, _)| {
+                matches
+                    .indices_of(id)
+                    .and_then(|mut indices| indices.next_back())
+                    .is_some_and(|index| index <= config_args)
+            })
+            .map(|(_, long)| long)
+            .collect();
+    }
+    cli.config = config;
+
+    match &cli.command {
+        Command::Search(args) => validate_search(args),
+        Command::Sign(args) => {
+            validate_target(&args.target, false);
+            validate_bits(&args.bits);
+            validate_sign(&args.rom);
+        }
+        Command::Verify(args) => {
+            validate_target(&args.target, false);
+            validate_bits(&args.bits);
+        }
+        Command::Compute(args) => validate_target(&args.target, args.seed_scan),
+        Command::DifferentialTest(args) => validate_gpu_values(
+            args.gpu.gpu_adapter.len(),
+            args.workgroups.len(),
+            args.local_size.len(),
+        ),
+        Command::Gpus(_) | Command::Cics | Command::Completions { .. } => {}
+    }
+
+    cli
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bit offsets into the IPL3 of the bits of a ROM word, from the first bit to the last one
+    fn word_bits(index: u32, first: u32, last: u32) -> Vec<u32> {
+        let mut bits: Vec<u32> = (last..=first)
+            .map(|bit| ((index - 16) * 32) + (31 - bit))
+            .collect();
+        bits.sort();
+        bits
+    }
+
+    #[test]
+    fn bits_in_every_index_and_range_format() {
+        let word = word_bits(1022, 31, 0);
+
+        assert_eq!(y_bits_parser("1022[31..0]").unwrap(), word);
+        assert_eq!(y_bits_parser("1022[0..31]").unwrap(), word);
+        assert_eq!(y_bits_parser("0x3FE[0..31]").unwrap(), word);
+        assert_eq!(y_bits_parser("@0xFF8[0..31]").unwrap(), word);
+        assert_eq!(y_bits_parser("1022").unwrap(), word);
+        assert_eq!(y_bits_parser("1022[..15]").unwrap(), word_bits(1022, 15, 0));
+        assert_eq!(
+            y_bits_parser("1022[16..]").unwrap(),
+            word_bits(1022, 31, 16)
+        );
+        assert_eq!(
+            x_bits_parser("@0xFFC[3..0],1016[31..28]").unwrap(),
+            [word_bits(1016, 31, 28), word_bits(1023, 3, 0)].concat()
+        );
+    }
+
+    /// The default Y bits are the whole word before the last one, in the order of the other forms
+    #[test]
+    fn default_bits() {
+        let cli = <Cli as clap::Parser>::parse_from([
+            "ipl3hasher-new",
+            "verify",
+            "rom.z64",
+            "--y",
+            "0",
+            "--x",
+            "0",
+        ]);
+        let Command::Verify(args) = cli.command else {
+            unreachable!();
+        };
+
+        assert_eq!(args.bits.y_bits, word_bits(1022, 31, 0));
+        assert_eq!(args.bits.y_bits, y_bits_parser("1022[0..31]").unwrap());
+        assert_eq!(args.bits.x_bits, word_bits(1023, 31, 0));
+    }
+
+    #[test]
+    fn invalid_bits() {
+        let error = y_bits_parser("1022[31..0],1022[0..0]").unwrap_err();
+        assert_eq!(error, "overlapping Y bits at index 1022, bit 0");
+        let error = x_bits_parser("1016[31..16],@0xFE0[16..16]").unwrap_err();
+        assert_eq!(error, "overlapping X bits at index 1016, bit 16");
+
+        assert_eq!(
+            y_bits_parser("@0xFF9[31..0]").unwrap_err(),
+            "unaligned Y bits address: 0xFF9"
+        );
+        assert_eq!(
+            x_bits_parser("@0xFFE").unwrap_err(),
+            "unaligned X bits address: 0xFFE"
+        );
+
+        assert!(y_bits_parser("1022[32..0]").is_err());
+        assert!(y_bits_parser("16[31..0]").is_err());
+    }
+
+    /// Exclusions at both ends of the range, of the whole range, outside of it and on presets
+    #[test]
+    fn bits_exclusions() {
+        assert_eq!(
+            y_bits_parser("1022[31..0]!{0}").unwrap(),
+            word_bits(1022, 31, 1)
+        );
+        assert_eq!(
+            y_bits_parser("1022[31..0]!{31}").unwrap(),
+            word_bits(1022, 30, 0)
+        );
+        assert_eq!(
+            y_bits_parser("1022[31..0]!{0,31}").unwrap(),
+            word_bits(1022, 30, 1)
+        );
+        assert_eq!(
+            y_bits_parser("1022[31..0]!{14,15}").unwrap(),
+            [word_bits(1022, 31, 16), word_bits(1022, 13, 0)].concat()
+        );
+        assert!(y_bits_parser("1022[31..0]!{}").is_err());
+
+        assert_eq!(
+            y_bits_parser("1022[31..0]!{0..31}").unwrap_err(),
+            "no Y bits selected"
+        );
+        assert_eq!(
+            y_bits_parser("1022[15..0]!{16}").unwrap_err(),
+            "excluded Y bit 16 isn't in the range for index 1022"
+        );
+        assert_eq!(
+            x_bits_parser("1023[31..1]!{0}").unwrap_err(),
+            "excluded X bit 0 isn't in the range for index 1023"
+        );
+        assert!(y_bits_parser("1022[31..0]!{32}").is_err());
+
+        assert_eq!(
+            y_bits_parser("preset:last-word!{14,15}").unwrap(),
+            y_bits_parser("1022[31..0]!{14,15}").unwrap()
+        );
+        assert_eq!(
+            y_bits_parser("preset:padding-1022-1021!{0}").unwrap(),
+            [word_bits(1021, 15, 1), word_bits(1022, 15, 1)].concat()
+        );
+    }
+
+    /// Global options before the subcommand in every form are skipped, anything else is the subcommand
+    #[test]
+    fn subcommand_after_global_options() {
+        let mut command = Cli::command();
+        command.build();
+        let index = |args: &[&str]| {
+            let args: Vec<std::ffi::OsString> = std::iter::once("ipl3hasher-new")
+                .chain(args.iter().copied())
+                .map(Into::into)
+                .collect();
+            subcommand_index(&command, &args)
+        };
+
+        assert_eq!(index(&["verify"]), 1);
+        assert_eq!(index(&["--json", "-v", "verify"]), 3);
+        assert_eq!(index(&["-vq", "verify"]), 2);
+        assert_eq!(index(&["--log-file", "log.txt", "rom.z64"]), 3);
+        assert_eq!(index(&["--log-file=log.txt", "rom.z64"]), 2);
+        assert_eq!(index(&["--self-test", "--ignore-self-test", "rom.z64"]), 3);
+        assert_eq!(index(&["-v", "--y-init", "0", "rom.z64"]), 2);
+        assert_eq!(index(&["-vx", "rom.z64"]), 1);
+    }
+
+    /// The checkpoint interval needs a file to checkpoint to, from --checkpoint or --resume
+    #[test]
+    fn checkpoint_interval_with_resume() {
+        let parse = |args: &[&str]| {
+            <Cli as clap::Parser>::try_parse_from(
+                ["ipl3hasher-new", "search", "rom.z64"].iter().chain(args),
+            )
+        };
+
+        assert!(parse(&["--resume", "f", "--checkpoint-interval", "10"]).is_ok());
+        assert!(parse(&["--checkpoint", "f", "--checkpoint-interval", "10"]).is_ok());
+        assert!(parse(&["--checkpoint", "f", "--resume", "g"]).is_ok());
+        assert_eq!(
+            parse(&["--checkpoint-interval", "10"])
+                .err()
+                .unwrap()
+                .kind(),
+            clap::error::ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    /// The CPU search doesn't apply the X stride, a stride with the CPU fallback is rejected up front
+    #[test]
+    fn x_stride_conflicts_with_cpu() {
+        let parse = |args: &[&str]| {
+            <Cli as clap::Parser>::try_parse_from(
+                ["ipl3hasher-new", "search", "rom.z64", "--x-stride", "3"]
+                    .iter()
+                    .chain(args),
+            )
+        };
+
+        assert!(parse(&[]).is_ok());
+        for option in ["--cpu-only", "--fallback-cpu"] {
+            assert_eq!(
+                parse(&[option]).err().unwrap().kind(),
+                clap::error::ErrorKind::ArgumentConflict
+            );
+        }
+    }
 }
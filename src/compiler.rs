@@ -0,0 +1,39 @@
+pub fn compile_glsl_to_spirv(
+    name: &str,
+    source: &str,
+    macros: &[(&str, String)],
+    debug: bool,
+) -> Result<Vec<u32>, shaderc::Error> {
+    let compiler = shaderc::Compiler::new()?;
+
+    let entry_point = "main";
+
+    let mut compile_options = shaderc::CompileOptions::new()?;
+
+    compile_options.set_target_env(
+        shaderc::TargetEnv::Vulkan,
+        shaderc::EnvVersion::Vulkan1_1 as u32,
+    );
+
+    // Unoptimized SPIR-V keeps the source lines for the validation layers and GPU debuggers
+    if debug {
+        compile_options.set_optimization_level(shaderc::OptimizationLevel::Zero);
+        compile_options.set_generate_debug_info();
+    } else {
+        compile_options.set_optimization_level(shaderc::OptimizationLevel::Performance);
+    }
+
+    for (name, value) in macros {
+        compile_options.add_macro_definition(name, Some(value));
+    }
+
+    let compilation_artifact = compiler.compile_into_spirv(
+        source,
+        shaderc::ShaderKind::Compute,
+        name,
+        entry_point,
+        Some(&compile_options),
+    )?;
+
+    Ok(compilation_artifact.as_binary().to_vec())
+}
@@ -0,0 +1,73 @@
+pub const DEFAULT_PATH: &str = "ipl3hasher.toml";
+
+// TOML integers are written as hex digits for options parsed as hex
+const HEX_OPTIONS: &[&str] = &["seed", "checksum", "y", "x"];
+
+pub struct Config {
+    pub args: Vec<String>,
+    pub options: Vec<(String, String)>,
+}
+
+fn find_argument<'a>(command: &'a clap::Command, long: &str) -> Option<&'a clap::Arg> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long) && arg.get_id() != "config")
+}
+
+/// Converts the config file values to command line arguments for the subcommand, keyed by the long
+/// option names (options known only to other subcommands are skipped)
+pub fn load(
+    path: &std::path::Path,
+    command: &clap::Command,
+    subcommand: &str,
+) -> Result<Config, String> {
+    let file = std::fs::read_to_string(path)
+        .map_err(|e| format!("can't read config file \"{}\": {e}", path.display()))?;
+
+    let table: toml::Table = file
+        .parse()
+        .map_err(|e| format!("invalid config file \"{}\": {e}", path.display()))?;
+
+    let mut args = vec![];
+    let mut options = vec![];
+
+    for (key, value) in &table {
+        let long = key.replace('_', "-");
+
+        let arg = command
+            .find_subcommand(subcommand)
+            .and_then(|command| find_argument(command, &long));
+
+        let Some(arg) = arg else {
+            if !command
+                .get_subcommands()
+                .any(|command| find_argument(command, &long).is_some())
+            {
+                return Err(format!("unknown option in config file: {key}"));
+            }
+            continue;
+        };
+
+        let flag = format!("--{long}");
+
+        match value {
+            toml::Value::Boolean(true) => args.push(flag),
+            toml::Value::Boolean(false) => {}
+            toml::Value::String(value) => args.extend([flag, value.clone()]),
+            toml::Value::Integer(value) if HEX_OPTIONS.contains(&long.as_str()) => {
+                args.extend([flag, format!("{value:X}")])
+            }
+            toml::Value::Integer(value) => args.extend([flag, value.to_string()]),
+            value => {
+                return Err(format!(
+                    "unsupported {} value for config option: {key}",
+                    value.type_str()
+                ));
+            }
+        }
+
+        options.push((arg.get_id().to_string(), long));
+    }
+
+    Ok(Config { args, options })
+}
@@ -1,10 +1,60 @@
+pub(crate) enum CPUHasherResult {
+    Found(u32),
+    Continue(u32),
+    End,
+}
+
+/// The X values the X word checksum is evaluated for together, one per SIMD lane
+const LANES: usize = 8;
+
+type Lanes = [u32; LANES];
+
+/// The states the CPU calculates for a Y and X value, to compare a shader with word by word
+pub struct CPUHasherDump {
+    /// The state of the Y round as uploaded to the GPU, after `y_round` with the whole X word or
+    /// after the words before the first X word with the X bits
+    pub round_state: [u32; 16],
+    /// The state after the last word, with X in its bits
+    pub final_state: [u32; 16],
+    /// The final mix buffers, the high checksum half comes from the first two and the low half
+    /// from the last two
+    pub finalize_buffers: [u32; 4],
+    /// The 48-bit checksum
+    pub checksum: u64,
+}
+
+/// The IPL3 checksum algorithm on the CPU, the reference the GPU shaders are checked against
 pub struct CPUHasher {
-    ipl3: [u32; 1008],
+    ipl3: Vec<u32>,
     state: [u32; 16],
+    y_prefix: Option<(u32, [u32; 16])>,
+    /// Copies of the IPL3 for `with_bits`, one for every call running at the same time. The words
+    /// written are restored afterwards, so the IPL3 is only copied once for each of them
+    scratch: std::sync::Mutex<Vec<Vec<u32>>>,
+}
+
+impl Clone for CPUHasher {
+    fn clone(&self) -> Self {
+        Self {
+            ipl3: self.ipl3.clone(),
+            state: self.state,
+            y_prefix: self.y_prefix,
+            scratch: std::sync::Mutex::new(vec![]),
+        }
+    }
+}
+
+pub(crate) struct YRoundCache {
+    ipl3: Vec<u32>,
+    states: Vec<(u32, [u32; 16])>,
 }
 
 impl CPUHasher {
-    pub const MAGIC: u32 = 0x6C078965;
+    pub(crate) const MAGIC: u32 = 0x6C078965;
+
+    pub(crate) const X_ROUND_SIZE: u64 = 1 << 24;
+    /// The X values of a round searched by one job of the thread pool at a time
+    const X_CHUNK_SIZE: u64 = 1 << 16;
 
     fn add(a1: u32, a2: u32) -> u32 {
         u32::wrapping_add(a1, a2)
@@ -34,14 +84,35 @@ impl CPUHasher {
         return if diff == 0 { a0 } else { diff };
     }
 
-    fn calculate(ipl3: &[u32; 1008], state: &mut [u32; 16], end: u32) {
-        let end = end.min(1008);
+    #[inline(always)]
+    fn lanes(mut f: impl FnMut(usize) -> u32) -> Lanes {
+        let mut lanes = [0; LANES];
+        for (lane, value) in lanes.iter_mut().enumerate() {
+            *value = f(lane);
+        }
+        lanes
+    }
+
+    /// Picks the lane of a or b, both are computed for every lane up front so that the choice
+    /// compiles to a SIMD select instead of a branch
+    #[inline(always)]
+    fn select(condition: impl Fn(usize) -> bool, a: Lanes, b: Lanes) -> Lanes {
+        Self::lanes(|l| if condition(l) { a[l] } else { b[l] })
+    }
+
+    fn calculate(ipl3: &[u32], state: &mut [u32; 16], end: u32) {
+        Self::calculate_from(ipl3, state, 1, end);
+    }
+
+    fn calculate_from(ipl3: &[u32], state: &mut [u32; 16], start: u32, end: u32) {
+        let ipl3_words = ipl3.len() as u32;
+        let end = end.min(ipl3_words);
 
-        for i in 1..=end as u32 {
+        for i in start..=end as u32 {
             let prev = ipl3[i.saturating_sub(2) as usize];
             let data = ipl3[i.saturating_sub(1) as usize];
 
-            state[0] = Self::add(state[0], Self::sum(Self::sub(1007, i), data, i));
+            state[0] = Self::add(state[0], Self::sum(Self::sub(ipl3_words - 1, i), data, i));
             state[1] = Self::sum(state[1], data, i);
             state[2] = state[2] ^ data;
             state[3] = Self::add(state[3], Self::sum(Self::add(data, 5), Self::MAGIC, i));
@@ -64,30 +135,47 @@ impl CPUHasher {
                 break;
             }
 
-            let next = ipl3[i as usize];
-
-            state[10] = Self::sum(Self::add(state[10], data), next, i);
-            state[11] = Self::sum(state[11] ^ data, next, i);
-            state[12] = Self::add(state[12], state[8] ^ data);
-            state[13] = Self::add(
-                state[13],
-                Self::add(Self::ror(data, data & 0x1F), Self::ror(next, next & 0x1F)),
-            );
-            state[14] = Self::sum(
-                Self::sum(state[14], Self::ror(data, prev & 0x1F), i),
-                Self::ror(next, data & 0x1F),
-                i,
-            );
-            state[15] = Self::sum(
-                Self::sum(state[15], Self::rol(data, prev >> 27), i),
-                Self::rol(next, data >> 27),
-                i,
-            );
+            Self::calculate_next(ipl3, state, i);
         }
     }
 
-    pub fn finalize(state: &[u32; 16]) -> u64 {
-        let mut buffer = vec![state[0]; 4];
+    fn calculate_next(ipl3: &[u32], state: &mut [u32; 16], i: u32) {
+        let prev = ipl3[i.saturating_sub(2) as usize];
+        let data = ipl3[i.saturating_sub(1) as usize];
+        let next = ipl3[i as usize];
+
+        state[10] = Self::sum(Self::add(state[10], data), next, i);
+        state[11] = Self::sum(state[11] ^ data, next, i);
+        state[12] = Self::add(state[12], state[8] ^ data);
+        state[13] = Self::add(
+            state[13],
+            Self::add(Self::ror(data, data & 0x1F), Self::ror(next, next & 0x1F)),
+        );
+        state[14] = Self::sum(
+            Self::sum(state[14], Self::ror(data, prev & 0x1F), i),
+            Self::ror(next, data & 0x1F),
+            i,
+        );
+        state[15] = Self::sum(
+            Self::sum(state[15], Self::rol(data, prev >> 27), i),
+            Self::rol(next, data >> 27),
+            i,
+        );
+    }
+
+    pub(crate) fn finalize(state: &[u32; 16]) -> u64 {
+        let buffer = Self::finalize_buffers(state);
+
+        let final_sum = Self::sum(buffer[0], buffer[1], 16);
+        let final_xor = buffer[3] ^ buffer[2];
+
+        (((final_sum & 0xFFFF) as u64) << 32) | (final_xor as u64)
+    }
+
+    /// The final mix of the state, the high checksum half comes from the first two buffers and the
+    /// low half from the last two
+    fn finalize_buffers(state: &[u32; 16]) -> [u32; 4] {
+        let mut buffer = [state[0]; 4];
 
         for i in 0..16 as u32 {
             let data = state[i as usize];
@@ -110,50 +198,205 @@ impl CPUHasher {
             };
         }
 
-        let final_sum = Self::sum(buffer[0], buffer[1], 16);
-        let final_xor = buffer[3] ^ buffer[2];
-
-        (((final_sum & 0xFFFF) as u64) << 32) | (final_xor as u64)
+        buffer
     }
 
-    pub fn new(ipl3_raw_data: &[u8; 4032], seed: u8) -> Self {
-        let mut ipl3 = [0u32; 1008];
+    /// Hashes an IPL3 of any whole number of words (big endian), 1008 for a real one
+    pub fn new(ipl3_raw_data: &[u8], seed: u8) -> Self {
+        let ipl3: Vec<u32> = ipl3_raw_data
+            .chunks_exact(4)
+            .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+            .collect();
 
-        for (i, bytes) in ipl3_raw_data.chunks(4).enumerate() {
-            ipl3[i] = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+        let state = Self::initial_state(&ipl3, seed);
+
+        Self {
+            ipl3,
+            state,
+            y_prefix: None,
+            scratch: std::sync::Mutex::new(vec![]),
         }
+    }
 
-        let mut state = [0u32; 16];
+    fn initial_state(ipl3: &[u32], seed: u8) -> [u32; 16] {
+        [Self::add(Self::mul(Self::MAGIC, seed as u32), 1) ^ ipl3[0]; 16]
+    }
+
+    /// The IPL3 length hashed, in words
+    pub fn get_ipl3_words(&self) -> u32 {
+        self.ipl3.len() as u32
+    }
 
-        state.fill(Self::add(Self::mul(Self::MAGIC, seed as u32), 1) ^ ipl3[0]);
+    /// Hashes the IPL3 with another CIC seed from now on
+    pub fn set_seed(&mut self, seed: u8) {
+        self.state = Self::initial_state(&self.ipl3, seed);
+        if let Some((word, _)) = self.y_prefix {
+            self.y_prefix = Some(Self::prefix_state(&self.ipl3, self.state, word));
+        }
+    }
 
-        Self { ipl3, state }
+    /// Calculates the state before the first Y word once, `y_round` then only goes through the
+    /// words from there, the ones before it are the same for every Y
+    pub(crate) fn set_y_bits(&mut self, y_bits: &[u32]) {
+        self.y_prefix = y_bits
+            .iter()
+            .map(|offset| offset / 32)
+            .min()
+            // The first IPL3 word is mixed into the initial state and the last one is the X word
+            .filter(|word| (1..(self.get_ipl3_words() - 1)).contains(word))
+            .map(|word| Self::prefix_state(&self.ipl3, self.state, word));
     }
 
-    fn apply_y_bits(&self, y_bits: Vec<u32>, y: u32) -> [u32; 1008] {
-        let mut ipl3 = self.ipl3.clone();
+    fn prefix_state(ipl3: &[u32], mut state: [u32; 16], word: u32) -> (u32, [u32; 16]) {
+        Self::calculate(ipl3, &mut state, word);
+        (word, state)
+    }
 
-        for (i, offset) in y_bits.iter().enumerate() {
+    fn apply_bits(ipl3: &mut [u32], bits: &[u32], value: u32) {
+        for (i, offset) in bits.iter().enumerate() {
             let index = (offset / 32) as usize;
             let bit = 31 - (offset % 32);
-            let shift = y_bits.len() - 1;
-            let value = (y >> (shift - i)) & (1 << 0);
+            let shift = bits.len() - 1;
+            let value = (value >> (shift - i)) & (1 << 0);
 
             ipl3[index] &= !(1 << bit);
             ipl3[index] |= value << bit;
         }
+    }
+
+    /// Calls the closure with the IPL3 with the values stored in their bits, in a scratch copy of
+    /// the hasher instead of a new one for every call. The closure may store other values in the
+    /// same bits, the rest of the IPL3 must be left as it is
+    fn with_bits<R>(&self, bits: &[(&[u32], u32)], f: impl FnOnce(&mut [u32]) -> R) -> R {
+        let mut ipl3 = self
+            .scratch
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| self.ipl3.clone());
+
+        for (bits, value) in bits {
+            Self::apply_bits(&mut ipl3, bits, *value);
+        }
+
+        // A copy left with the bits applied by a panic is dropped
+        let result = f(&mut ipl3);
+
+        for offset in bits.iter().flat_map(|(bits, _)| *bits) {
+            let word = (offset / 32) as usize;
+            ipl3[word] = self.ipl3[word];
+        }
+        self.scratch.lock().unwrap().push(ipl3);
+
+        result
+    }
+
+    /// The X bits are the whole last word
+    pub(crate) fn is_x_word(&self, x_bits: &[u32]) -> bool {
+        let ipl3_words = self.get_ipl3_words();
+        x_bits
+            .iter()
+            .copied()
+            .eq(((ipl3_words - 1) * 32)..(ipl3_words * 32))
+    }
 
-        ipl3
+    pub(crate) fn get_x_word(x_bits: &[u32]) -> u32 {
+        x_bits[0] / 32
     }
 
-    pub fn y_round(&self, y_bits: Vec<u32>, y: u32) -> (u32, [u32; 16]) {
-        let ipl3 = self.apply_y_bits(y_bits, y);
-        let mut state = self.state.clone();
+    /// With the Y bits in a single word, only that word and the ones after it are calculated for
+    /// each Y, `y_round_cached` does the same for Y bits spread over several words
+    pub(crate) fn y_round(&self, y_bits: &[u32], y: u32) -> (u32, [u32; 16]) {
+        let prefix = self
+            .y_prefix
+            .filter(|(word, _)| y_bits.iter().all(|offset| offset / 32 >= *word));
+
+        self.with_bits(&[(y_bits, y)], |ipl3| {
+            let state = match prefix {
+                // Same resume point as in `y_round_cached`, the next rows read the first Y word
+                Some((word, mut state)) => {
+                    Self::calculate_next(ipl3, &mut state, word);
+                    Self::calculate_from(ipl3, &mut state, word + 1, self.get_ipl3_words() - 1);
+                    state
+                }
+                None => {
+                    let mut state = self.state;
+                    Self::calculate(ipl3, &mut state, self.get_ipl3_words() - 1);
+                    state
+                }
+            };
 
-        Self::calculate(&ipl3, &mut state, 1007);
+            Self::y_round_finish(ipl3, state)
+        })
+    }
 
-        let prev = ipl3[1005];
-        let data = ipl3[1006];
+    /// Same result as `y_round`, but the state is only recalculated from the first Y word that
+    /// changed since the cached Y, which is a single word per step in the Gray code order
+    pub(crate) fn y_round_cached(
+        &self,
+        y_bits: &[u32],
+        y: u32,
+        cache: &mut Option<YRoundCache>,
+    ) -> (u32, [u32; 16]) {
+        let mut words: Vec<u32> = y_bits.iter().map(|offset| offset / 32).collect();
+        words.sort();
+        words.dedup();
+
+        // The first IPL3 word is mixed into the initial state, there's nothing to resume from
+        if words.first() == Some(&0) {
+            return self.y_round(y_bits, y);
+        }
+
+        // The state before each Y word only depends on the words preceding it. The cache keeps its
+        // own copy of the IPL3 with the last Y stored in it
+        let (cache, first_changed) = match cache {
+            Some(cache) => {
+                let previous: Vec<u32> = words
+                    .iter()
+                    .map(|word| cache.ipl3[*word as usize])
+                    .collect();
+                Self::apply_bits(&mut cache.ipl3, y_bits, y);
+                let first_changed = words
+                    .iter()
+                    .zip(previous)
+                    .position(|(word, previous)| cache.ipl3[*word as usize] != previous);
+                (cache, first_changed)
+            }
+            None => {
+                let mut state = self.state;
+                Self::calculate(&self.ipl3, &mut state, words[0]);
+                let mut ipl3 = self.ipl3.clone();
+                Self::apply_bits(&mut ipl3, y_bits, y);
+                let cache = cache.insert(YRoundCache {
+                    ipl3,
+                    states: vec![(words[0], state); words.len()],
+                });
+                (cache, Some(0))
+            }
+        };
+
+        let ipl3 = &cache.ipl3;
+
+        if let Some(first_changed) = first_changed {
+            for i in first_changed..(words.len() - 1) {
+                let (word, mut state) = cache.states[i];
+                Self::calculate_next(ipl3, &mut state, word);
+                Self::calculate_from(ipl3, &mut state, word + 1, words[i + 1]);
+                cache.states[i + 1] = (words[i + 1], state);
+            }
+        }
+
+        let (word, mut state) = cache.states[words.len() - 1];
+        Self::calculate_next(ipl3, &mut state, word);
+        Self::calculate_from(ipl3, &mut state, word + 1, self.get_ipl3_words() - 1);
+
+        Self::y_round_finish(ipl3, state)
+    }
+
+    fn y_round_finish(ipl3: &[u32], mut state: [u32; 16]) -> (u32, [u32; 16]) {
+        let ipl3_words = ipl3.len() as u32;
+        let prev = ipl3[ipl3.len() - 3];
+        let data = ipl3[ipl3.len() - 2];
 
         // OPTIMIZATION: Precalculate some values to speed up computation on the GPU side
 
@@ -161,19 +404,553 @@ impl CPUHasher {
         state[11] = state[11] ^ data;
         state[12] = Self::add(state[12], state[8] ^ data);
         state[13] = Self::add(state[13], Self::ror(data, data & 0x1F));
-        state[14] = Self::sum(state[14], Self::ror(data, prev & 0x1F), 1007);
-        state[15] = Self::sum(state[15], Self::rol(data, prev >> 27), 1007);
+        state[14] = Self::sum(state[14], Self::ror(data, prev & 0x1F), ipl3_words - 1);
+        state[15] = Self::sum(state[15], Self::rol(data, prev >> 27), ipl3_words - 1);
 
         (data, state)
     }
 
-    pub fn verify(&self, y_bits: Vec<u32>, y: u32, x: u32) -> u64 {
-        let mut ipl3 = self.apply_y_bits(y_bits, y);
-        let mut state = self.state.clone();
+    /// The state of the round with the X bits and the IPL3 of the round, with Y stored in it and the
+    /// X bits cleared
+    pub(crate) fn y_round_x_bits(
+        &self,
+        y_bits: &[u32],
+        y: u32,
+        x_bits: &[u32],
+    ) -> ([u32; 16], Vec<u32>) {
+        self.with_bits(&[(y_bits, y), (x_bits, 0)], |ipl3| {
+            (self.x_bits_prefix(ipl3, x_bits), ipl3.to_vec())
+        })
+    }
+
+    /// Everything up to the first word containing X bits doesn't depend on X
+    fn x_bits_prefix(&self, ipl3: &[u32], x_bits: &[u32]) -> [u32; 16] {
+        let mut state = self.state;
+        Self::calculate(ipl3, &mut state, Self::get_x_word(x_bits));
+        state
+    }
+
+    /// The checksum with X stored in the IPL3 of the round, which is left with the X bits cleared as
+    /// `y_round_x_bits` returns it
+    fn x_bits_checksum(state: &[u32; 16], ipl3: &mut [u32], x_bits: &[u32], x: u32) -> u64 {
+        let mut state = *state;
+
+        let x_word = Self::get_x_word(x_bits);
 
-        ipl3[1007] = x;
+        Self::apply_bits(ipl3, x_bits, x);
+        Self::calculate_next(ipl3, &mut state, x_word);
+        Self::calculate_from(ipl3, &mut state, x_word + 1, ipl3.len() as u32);
+        Self::apply_bits(ipl3, x_bits, 0);
 
-        Self::calculate(&ipl3, &mut state, 1008);
         Self::finalize(&state)
     }
+
+    fn x_checksum(state: &[u32; 16], y_offset: u32, x: u32, ipl3_words: u32) -> u64 {
+        let mut state = *state;
+
+        let prev = y_offset;
+        let data = x;
+
+        // Same tail as the GPU shader: rows 0..=9 for the word past the end and the remaining rows for
+        // the last word
+
+        state[0] = Self::add(
+            state[0],
+            Self::sum(Self::sub(ipl3_words - 1, ipl3_words), data, ipl3_words),
+        );
+        state[1] = Self::sum(state[1], data, ipl3_words);
+        state[2] ^= data;
+        state[3] = Self::add(
+            state[3],
+            Self::sum(Self::add(data, 5), Self::MAGIC, ipl3_words),
+        );
+        state[4] = Self::add(state[4], Self::ror(data, prev & 0x1F));
+        state[5] = Self::add(state[5], Self::rol(data, prev >> 27));
+        state[6] = if data < state[6] {
+            Self::add(state[3], state[6]) ^ Self::add(data, ipl3_words)
+        } else {
+            Self::add(state[4], data) ^ state[6]
+        };
+        state[7] = Self::sum(state[7], Self::rol(data, prev & 0x1F), ipl3_words);
+        state[8] = Self::sum(state[8], Self::ror(data, prev >> 27), ipl3_words);
+        state[9] = if prev < data {
+            Self::sum(state[9], data, ipl3_words)
+        } else {
+            Self::add(state[9], data)
+        };
+        state[10] = Self::sum(state[10], data, ipl3_words - 1);
+        state[11] = Self::sum(state[11], data, ipl3_words - 1);
+        state[13] = Self::add(state[13], Self::ror(data, data & 0x1F));
+        state[14] = Self::sum(state[14], Self::ror(data, prev & 0x1F), ipl3_words - 1);
+        state[15] = Self::sum(state[15], Self::rol(data, prev >> 27), ipl3_words - 1);
+
+        Self::finalize(&state)
+    }
+
+    /// The X word checksum of LANES consecutive X values, every row is computed for all of them at
+    /// once and the conditional rows are selects, so that it compiles to SIMD instructions
+    #[inline(always)]
+    fn x_checksum_lanes(state: &[u32; 16], y_offset: u32, x: u32, ipl3_words: u32) -> [u64; LANES] {
+        let prev = y_offset;
+        let data = Self::lanes(|lane| Self::add(x, lane as u32));
+        let mut s = state.map(|value| [value; LANES]);
+
+        s[0] = Self::lanes(|l| {
+            Self::add(
+                s[0][l],
+                Self::sum(Self::sub(ipl3_words - 1, ipl3_words), data[l], ipl3_words),
+            )
+        });
+        s[1] = Self::lanes(|l| Self::sum(s[1][l], data[l], ipl3_words));
+        s[2] = Self::lanes(|l| s[2][l] ^ data[l]);
+        s[3] = Self::lanes(|l| {
+            Self::add(
+                s[3][l],
+                Self::sum(Self::add(data[l], 5), Self::MAGIC, ipl3_words),
+            )
+        });
+        s[4] = Self::lanes(|l| Self::add(s[4][l], Self::ror(data[l], prev & 0x1F)));
+        s[5] = Self::lanes(|l| Self::add(s[5][l], Self::rol(data[l], prev >> 27)));
+        s[6] = Self::select(
+            |l| data[l] < s[6][l],
+            Self::lanes(|l| Self::add(s[3][l], s[6][l]) ^ Self::add(data[l], ipl3_words)),
+            Self::lanes(|l| Self::add(s[4][l], data[l]) ^ s[6][l]),
+        );
+        s[7] = Self::lanes(|l| Self::sum(s[7][l], Self::rol(data[l], prev & 0x1F), ipl3_words));
+        s[8] = Self::lanes(|l| Self::sum(s[8][l], Self::ror(data[l], prev >> 27), ipl3_words));
+        s[9] = Self::select(
+            |l| prev < data[l],
+            Self::lanes(|l| Self::sum(s[9][l], data[l], ipl3_words)),
+            Self::lanes(|l| Self::add(s[9][l], data[l])),
+        );
+        s[10] = Self::lanes(|l| Self::sum(s[10][l], data[l], ipl3_words - 1));
+        s[11] = Self::lanes(|l| Self::sum(s[11][l], data[l], ipl3_words - 1));
+        s[13] = Self::lanes(|l| Self::add(s[13][l], Self::ror(data[l], data[l] & 0x1F)));
+        s[14] =
+            Self::lanes(|l| Self::sum(s[14][l], Self::ror(data[l], prev & 0x1F), ipl3_words - 1));
+        s[15] =
+            Self::lanes(|l| Self::sum(s[15][l], Self::rol(data[l], prev >> 27), ipl3_words - 1));
+
+        let mut buffer = [s[0]; 4];
+
+        for i in 0..16u32 {
+            let data = s[i as usize];
+
+            buffer[0] =
+                Self::lanes(|l| Self::add(buffer[0][l], Self::ror(data[l], data[l] & 0x1F)));
+            buffer[1] = Self::select(
+                |l| data[l] < buffer[0][l],
+                Self::lanes(|l| Self::add(buffer[1][l], data[l])),
+                Self::lanes(|l| Self::sum(buffer[1][l], data[l], i)),
+            );
+            buffer[2] = Self::select(
+                |l| ((data[l] & 0x02) >> 1) == (data[l] & 0x01),
+                Self::lanes(|l| Self::add(buffer[2][l], data[l])),
+                Self::lanes(|l| Self::sum(buffer[2][l], data[l], i)),
+            );
+            buffer[3] = Self::select(
+                |l| (data[l] & 0x01) == 0x01,
+                Self::lanes(|l| buffer[3][l] ^ data[l]),
+                Self::lanes(|l| Self::sum(buffer[3][l], data[l], i)),
+            );
+        }
+
+        std::array::from_fn(|l| {
+            let final_sum = Self::sum(buffer[0][l], buffer[1][l], 16);
+            let final_xor = buffer[3][l] ^ buffer[2][l];
+            (((final_sum & 0xFFFF) as u64) << 32) | (final_xor as u64)
+        })
+    }
+
+    /// The lane variant compiled for AVX2, which doubles the lanes of a SIMD instruction over the
+    /// SSE2 baseline
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    fn x_checksum_lanes_avx2(
+        state: &[u32; 16],
+        y_offset: u32,
+        x: u32,
+        ipl3_words: u32,
+    ) -> [u64; LANES] {
+        Self::x_checksum_lanes(state, y_offset, x, ipl3_words)
+    }
+
+    pub(crate) fn x_round(
+        target_checksum: u64,
+        y_offset: u32,
+        x_offset: u32,
+        state: [u32; 16],
+        ipl3_words: u32,
+        pool: &rayon::ThreadPool,
+    ) -> CPUHasherResult {
+        #[cfg(target_arch = "x86_64")]
+        if std::arch::is_x86_feature_detected!("avx2") {
+            return Self::x_search(target_checksum, x_offset, 1 << 32, pool, || {
+                // SAFETY: AVX2 was detected at runtime
+                |x| unsafe { Self::x_checksum_lanes_avx2(&state, y_offset, x, ipl3_words) }
+            });
+        }
+
+        Self::x_search(target_checksum, x_offset, 1 << 32, pool, || {
+            |x| Self::x_checksum_lanes(&state, y_offset, x, ipl3_words)
+        })
+    }
+
+    pub(crate) fn x_bits_round(
+        target_checksum: u64,
+        x_offset: u32,
+        state: [u32; 16],
+        ipl3: &[u32],
+        x_bits: &[u32],
+        pool: &rayon::ThreadPool,
+    ) -> CPUHasherResult {
+        // Every job stores its X values in its own copy of the IPL3 of the round
+        let checksums = || {
+            let mut ipl3 = ipl3.to_vec();
+            move |x| {
+                Self::lanes(|lane| lane as u32).map(|lane| {
+                    Self::x_bits_checksum(&state, &mut ipl3, x_bits, Self::add(x, lane))
+                })
+            }
+        };
+
+        Self::x_search(
+            target_checksum,
+            x_offset,
+            1 << x_bits.len(),
+            pool,
+            checksums,
+        )
+    }
+
+    /// The X range of the round is split into chunks searched on the thread pool, the chunks past a
+    /// match are skipped (the pool checks between chunks) so the result is still the lowest match
+    /// of the round. The checksums closure is created once for every job of the pool, it can keep
+    /// a scratch state between the X values of its chunks
+    fn x_search<F: FnMut(u32) -> [u64; LANES]>(
+        target_checksum: u64,
+        x_offset: u32,
+        x_size: u64,
+        pool: &rayon::ThreadPool,
+        checksums: impl Fn() -> F + Sync,
+    ) -> CPUHasherResult {
+        use rayon::prelude::*;
+
+        let start = x_offset as u64;
+        let end = (start + Self::X_ROUND_SIZE).min(x_size);
+        let chunks = (end - start).div_ceil(Self::X_CHUNK_SIZE);
+
+        // Checksums are computed LANES X values at a time, the ones past the end are ignored
+        let scan = |checksums: &mut F, chunk: u64| {
+            let chunk_start = start + (chunk * Self::X_CHUNK_SIZE);
+            let chunk_end = (chunk_start + Self::X_CHUNK_SIZE).min(end);
+            (chunk_start..chunk_end)
+                .step_by(LANES)
+                .find_map(|x| {
+                    checksums(x as u32)
+                        .into_iter()
+                        .zip(x..chunk_end)
+                        .find(|(checksum, _)| *checksum == target_checksum)
+                })
+                .map(|(_, x)| x as u32)
+        };
+
+        let found = pool.install(|| {
+            (0..chunks)
+                .into_par_iter()
+                .map_init(&checksums, scan)
+                .find_map_first(|found| found)
+        });
+
+        Self::x_search_result(found, start, end, x_size)
+    }
+
+    fn x_search_result(found: Option<u32>, start: u64, end: u64, x_size: u64) -> CPUHasherResult {
+        match found {
+            Some(x) => CPUHasherResult::Found(x),
+            None if end >= x_size => CPUHasherResult::End,
+            None => CPUHasherResult::Continue((end - start) as u32),
+        }
+    }
+
+    pub(crate) fn round_checksum(&self, y_bits: &[u32], y: u32, x_bits: &[u32], x: u32) -> u64 {
+        if self.is_x_word(x_bits) {
+            let (y_offset, state) = self.y_round(y_bits, y);
+            Self::x_checksum(&state, y_offset, x, self.get_ipl3_words())
+        } else {
+            self.with_bits(&[(y_bits, y), (x_bits, 0)], |ipl3| {
+                let state = self.x_bits_prefix(ipl3, x_bits);
+                Self::x_bits_checksum(&state, ipl3, x_bits, x)
+            })
+        }
+    }
+
+    pub(crate) fn count_set_bits(&self, bits: &[u32]) -> usize {
+        bits.iter()
+            .filter(|offset| (self.ipl3[(*offset / 32) as usize] >> (31 - (*offset % 32))) & 1 != 0)
+            .count()
+    }
+
+    /// The state after every word of the IPL3 as it is, before the final mix
+    pub(crate) fn final_state(&self) -> [u32; 16] {
+        let mut state = self.state;
+
+        Self::calculate(&self.ipl3, &mut state, self.get_ipl3_words());
+        state
+    }
+
+    /// The 48-bit checksum of the IPL3 as it is
+    pub fn checksum(&self) -> u64 {
+        Self::finalize(&self.final_state())
+    }
+
+    pub(crate) fn dump_state(
+        &self,
+        y_bits: &[u32],
+        y: u32,
+        x_bits: &[u32],
+        x: u32,
+    ) -> CPUHasherDump {
+        let round_state = if self.is_x_word(x_bits) {
+            self.y_round(y_bits, y).1
+        } else {
+            self.with_bits(&[(y_bits, y), (x_bits, 0)], |ipl3| {
+                self.x_bits_prefix(ipl3, x_bits)
+            })
+        };
+
+        let final_state = self.verify_state(y_bits, y, x_bits, x);
+
+        CPUHasherDump {
+            round_state,
+            final_state,
+            finalize_buffers: Self::finalize_buffers(&final_state),
+            checksum: Self::finalize(&final_state),
+        }
+    }
+
+    /// The 48-bit checksum of the IPL3 with the Y and X values stored in their bits, most significant
+    /// bit first
+    pub fn verify(&self, y_bits: &[u32], y: u32, x_bits: &[u32], x: u32) -> u64 {
+        Self::finalize(&self.verify_state(y_bits, y, x_bits, x))
+    }
+
+    fn verify_state(&self, y_bits: &[u32], y: u32, x_bits: &[u32], x: u32) -> [u32; 16] {
+        self.with_bits(&[(y_bits, y), (x_bits, x)], |ipl3| {
+            let mut state = self.state;
+
+            Self::calculate(ipl3, &mut state, self.get_ipl3_words());
+            state
+        })
+    }
+
+    /// The checksums of the Y and X candidates like `verify`, in the candidate order. The state up
+    /// to the first X word is calculated once for the candidates of a Y, which are kept together on
+    /// one of the threads
+    pub fn verify_batch(
+        &self,
+        y_bits: &[u32],
+        x_bits: &[u32],
+        candidates: &[(u32, u32)],
+        threads: usize,
+    ) -> Vec<u64> {
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        order.sort_by_key(|&index| candidates[index].0);
+
+        let verify = |order: &[usize]| {
+            order
+                .chunk_by(|&a, &b| candidates[a].0 == candidates[b].0)
+                .flat_map(|order| {
+                    let y = candidates[order[0]].0;
+                    self.with_bits(&[(y_bits, y), (x_bits, 0)], |ipl3| {
+                        let state = self.x_bits_prefix(ipl3, x_bits);
+                        order
+                            .iter()
+                            .map(|&index| {
+                                let x = candidates[index].1;
+                                (index, Self::x_bits_checksum(&state, ipl3, x_bits, x))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<(usize, u64)>>()
+        };
+
+        // Chunks split at Y boundaries, so that no Y prefix is calculated twice
+        let chunk_size = candidates.len().div_ceil(threads.max(1)).max(1);
+        let mut chunks = vec![];
+        let mut rest = &order[..];
+        while !rest.is_empty() {
+            let mut end = chunk_size.min(rest.len());
+            while end < rest.len() && candidates[rest[end]].0 == candidates[rest[end - 1]].0 {
+                end += 1;
+            }
+            let (chunk, next) = rest.split_at(end);
+            chunks.push(chunk);
+            rest = next;
+        }
+
+        let results: Vec<(usize, u64)> = if chunks.len() <= 1 {
+            verify(&order)
+        } else {
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = chunks
+                    .iter()
+                    .map(|chunk| scope.spawn(|| verify(chunk)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().unwrap())
+                    .collect()
+            })
+        };
+
+        let mut checksums = vec![0; candidates.len()];
+        for (index, checksum) in results {
+            checksums[index] = checksum;
+        }
+        checksums
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u8 = 0x3F;
+
+    /// Xorshift words, the same sequence for every run
+    fn random_words(state: &mut u32, count: usize) -> Vec<u32> {
+        (0..count)
+            .map(|_| {
+                *state ^= *state << 13;
+                *state ^= *state >> 17;
+                *state ^= *state << 5;
+                *state
+            })
+            .collect()
+    }
+
+    /// A hasher of a pseudo-random IPL3 of the given length, short ones keep the full
+    /// recalculations fast
+    fn hasher(ipl3_words: usize) -> CPUHasher {
+        let ipl3: Vec<u8> = random_words(&mut 0x6C078965, ipl3_words)
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect();
+        CPUHasher::new(&ipl3, SEED)
+    }
+
+    /// Bit offsets of the low bits of an IPL3 word, most significant bit first
+    fn low_bits(word: u32, bits: u32) -> Vec<u32> {
+        ((word * 32 + 32 - bits)..(word * 32 + 32)).collect()
+    }
+
+    /// Every Y of 16 bits in a single word, resumed from the state before the Y word, matches the
+    /// round calculated from the first word and the cached round
+    #[test]
+    fn y_round_prefix_matches_every_16_bit_y() {
+        let y_bits = low_bits(40, 16);
+        let full = hasher(64);
+        let mut prefixed = full.clone();
+        prefixed.set_y_bits(&y_bits);
+        assert!(prefixed.y_prefix.is_some());
+
+        let mut cache = None;
+        for y in 0..(1 << 16) {
+            let round = prefixed.y_round(&y_bits, y);
+            assert_eq!(round, full.y_round(&y_bits, y), "Y={y:04X}");
+            assert_eq!(
+                round,
+                full.y_round_cached(&y_bits, y, &mut cache),
+                "Y={y:04X}"
+            );
+        }
+    }
+
+    /// Every lane of the X word checksum matches the scalar checksum of its X, over random states,
+    /// Y offsets and X values, including the lanes wrapping past u32::MAX
+    #[test]
+    fn x_checksum_lanes_match_x_checksum() {
+        let mut random = 0x5D588B65;
+
+        for i in 0..4096 {
+            let words = random_words(&mut random, 19);
+            let state: [u32; 16] = words[..16].try_into().unwrap();
+            let (y_offset, ipl3_words) = (words[16], 1008 - (words[17] % 4));
+            let x = match i % 4 {
+                0 => u32::MAX - (words[18] % (2 * LANES as u32)),
+                _ => words[18],
+            };
+
+            let expected: Vec<u64> = (0..LANES as u32)
+                .map(|lane| {
+                    CPUHasher::x_checksum(&state, y_offset, x.wrapping_add(lane), ipl3_words)
+                })
+                .collect();
+
+            assert_eq!(
+                CPUHasher::x_checksum_lanes(&state, y_offset, x, ipl3_words),
+                expected[..],
+                "X={x:08X}"
+            );
+
+            #[cfg(target_arch = "x86_64")]
+            if std::arch::is_x86_feature_detected!("avx2") {
+                // SAFETY: AVX2 was detected at runtime
+                let lanes =
+                    unsafe { CPUHasher::x_checksum_lanes_avx2(&state, y_offset, x, ipl3_words) };
+                assert_eq!(lanes, expected[..], "X={x:08X}");
+            }
+        }
+    }
+
+    /// The X bits search over several chunks of the thread pool reports the X the checksum was
+    /// verified for, from any number of threads, and the end of the range past it
+    #[test]
+    fn x_bits_round_finds_verified_x() {
+        let (y_bits, x_bits) = (low_bits(40, 4), low_bits(60, 18));
+        let hasher = hasher(64);
+        let (y, x) = (0x9, 0x2F0A3);
+        let target_checksum = hasher.verify(&y_bits, y, &x_bits, x);
+        let (state, ipl3) = hasher.y_round_x_bits(&y_bits, y, &x_bits);
+
+        for threads in [1, 4] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let search = |x_offset| {
+                CPUHasher::x_bits_round(target_checksum, x_offset, state, &ipl3, &x_bits, &pool)
+            };
+
+            assert!(
+                matches!(search(0), CPUHasherResult::Found(found) if found == x),
+                "{threads} threads"
+            );
+            assert!(
+                matches!(search(x + 1), CPUHasherResult::End),
+                "{threads} threads"
+            );
+        }
+    }
+
+    /// Walking the Gray code order with the Y bits spread over several words, the cached round
+    /// recalculated from the changed word matches the full round at every step
+    #[test]
+    fn y_round_cached_matches_y_round_in_gray_order() {
+        let y_bits = [low_bits(10, 4), low_bits(30, 4), low_bits(50, 4)].concat();
+        let hasher = hasher(64);
+        let y_count = 1u64 << y_bits.len();
+
+        let mut cache = None;
+        for position in 0..y_count as u32 {
+            let y = crate::hasher::YOrder::Gray.get_y_offset(position, y_count);
+            assert_eq!(
+                hasher.y_round_cached(&y_bits, y, &mut cache),
+                hasher.y_round(&y_bits, y),
+                "position {position}, Y={y:03X}"
+            );
+        }
+    }
 }
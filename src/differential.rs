@@ -0,0 +1,123 @@
+use crate::{hasher::Hasher, known_answer, rom};
+
+/// The IPL3 length of every case, the GPU pipelines are built for a single length
+pub const IPL3_WORDS: usize = rom::IPL3_WORDS;
+
+/// A random IPL3 image, seed, bits layout and Y/X pair for the GPUs to find the X value of. The
+/// case follows from its case seed alone, so a divergence is rerun from the seed, the seeds of a
+/// run are consecutive
+pub struct DifferentialCase {
+    /// The seed the case is generated from
+    pub case_seed: u64,
+    /// The xorshift state the image is generated from, see `known_answer::generate_image`
+    pub generator: u32,
+    /// The CIC seed
+    pub seed: u8,
+    /// The Y bits, in the order of the bits arguments
+    pub y_bits: Vec<u32>,
+    /// The X bits, the whole last word in a quarter of the cases for the X word path
+    pub x_bits: Vec<u32>,
+    /// The Y value
+    pub y: u32,
+    /// The X value the GPUs must find
+    pub x: u32,
+    /// Picks how far before X the dispatch starts, so that X isn't always hashed by the first thread
+    pub x_lead: u64,
+}
+
+impl DifferentialCase {
+    /// Generates the case of the seed
+    pub fn new(case_seed: u64) -> Self {
+        let mut random = Random(case_seed);
+
+        let generator = random.next() as u32;
+        let seed = random.next() as u8;
+
+        let x_bits = if random.below(4) == 0 {
+            (((IPL3_WORDS as u32 - 1) * 32)..(IPL3_WORDS as u32 * 32)).collect()
+        } else {
+            random.bits(IPL3_WORDS as u32, &[])
+        };
+        let y_bits = random.bits(IPL3_WORDS as u32 - 1, &x_bits);
+
+        let y = random.next() as u32 & Hasher::get_y_max(&y_bits);
+        let x = random.next() as u32 & Hasher::get_y_max(&x_bits);
+        let x_lead = random.next();
+
+        Self {
+            case_seed,
+            generator,
+            seed,
+            y_bits,
+            x_bits,
+            y,
+            x,
+            x_lead,
+        }
+    }
+
+    /// The IPL3 image before the Y and X values are stored in their bits
+    pub fn get_image(&self) -> Vec<u8> {
+        known_answer::generate_image(self.generator, IPL3_WORDS)
+    }
+}
+
+impl std::fmt::Display for DifferentialCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "case {:016X} (image {:08X} with seed {:02X}, Y bits {}, X bits {}, Y={:08X} X={:08X})",
+            self.case_seed,
+            self.generator,
+            self.seed,
+            rom::format_bits(&self.y_bits),
+            rom::format_bits(&self.x_bits),
+            self.y,
+            self.x
+        ))
+    }
+}
+
+/// SplitMix64, small and seeded so that the cases (and the self-test samples) are the same on every
+/// platform and run
+pub(crate) struct Random(pub(crate) u64);
+
+impl Random {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    /// 1 to 32 sorted bit offsets in a window of up to 3 consecutive words before the word end,
+    /// like the bits arguments give them. The first word is skipped as it can't be selected there
+    fn bits(&mut self, words_end: u32, exclude: &[u32]) -> Vec<u32> {
+        loop {
+            let window = 1 + self.below(3) as u32;
+            let start = 1 + self.below((words_end - 1) as u64) as u32;
+            let end = (start + window).min(words_end);
+
+            let mut offsets: Vec<u32> = ((start * 32)..(end * 32))
+                .filter(|offset| !exclude.contains(offset))
+                .collect();
+            if offsets.is_empty() {
+                continue;
+            }
+
+            let count = (1 + self.below(32) as usize).min(offsets.len());
+            for i in 0..count {
+                let j = i + self.below((offsets.len() - i) as u64) as usize;
+                offsets.swap(i, j);
+            }
+            offsets.truncate(count);
+            offsets.sort();
+
+            return offsets;
+        }
+    }
+}
@@ -1,9 +1,78 @@
+/// Everything that can fail in the library and the binary, with a message for the user
 #[derive(Debug)]
 pub enum HasherError {
+    /// A GPU match, Y and X, didn't verify on the CPU, with the checksum the CPU calculated
     ChecksumVerifyError(u32, u32, u64),
+    /// The CPU round checksum of Y and X differs from the checksum over all the words
+    SelfCheckError(u32, u32, u64, u64),
+    /// The shader, by name, disagrees with the CPU on the checksum of Y and the X offset
+    ShaderCheckError(&'static str, u32, u32),
+    /// The GPU, by index, missed the planted Y and X with the backend and shader named, and the seed
+    /// of the samples
+    GPUSelfTestError(usize, String, &'static str, u32, u32, u64),
+    /// The GPU, by index, disagrees with the CPU on a differential test case, with the backend and
+    /// shader named, the case seed, the case and what the GPU reported
+    GPUDifferentialTestError(usize, String, &'static str, u64, String, String),
+    /// The requested backends aren't among the ones available on the platform
+    GPUBackendUnavailable(wgpu::Backends, wgpu::Backends),
+    /// No GPU adapter was listed
+    GPUAdapterNoneFound,
+    /// The selected adapter index isn't in the adapter list
     GPUAdapterOutOfBounds,
+    /// The adapter, by index, is selected for more than one GPU
+    GPUAdapterDuplicate(usize),
+    /// The local size is above the adapter limit
+    GPULocalSizeTooLarge(u32, u32),
+    /// The workgroups in a dimension need more dispatches than allowed with the adapter limit
+    GPUWorkgroupsTooLarge(&'static str, u32, u32),
+    /// The GLSL shader didn't compile to SPIR-V
+    #[cfg(feature = "glsl")]
+    ShaderCompileError(shaderc::Error),
+    /// wgpu rejected the shader or its pipeline
+    ShaderValidationError(String),
+    /// No adapter name contains the string
+    GPUAdapterNotFound(String),
+    /// Several adapter names contain the string, with the names
+    GPUAdapterAmbiguous(String, Vec<String>),
+    /// The output file exists and isn't to be overwritten
+    OutputFileExists(std::path::PathBuf),
+    /// The backup of the ROM signed in place exists and isn't to be overwritten
+    BackupFileExists(std::path::PathBuf),
+    /// The IPL3 read back from the signed ROM file hashes to the first checksum instead of the target
+    SignedRomMismatch(std::path::PathBuf, u64, u64),
+    /// The ROM header word isn't a known byte order
+    UnknownRomFormat(u32),
+    /// The IPL3 at the offset doesn't fit in the ROM file of the size, with the IPL3 size
+    IPL3OutOfBounds(u64, u64, u64),
+    /// The ROM stream ended after the bytes received, before the end of the IPL3 at the offset
+    RomTruncated(u64, u64, u64),
+    /// The ROM of the size is shorter than the header CRCs cover, with the minimum size
+    HeaderCrcRomTooSmall(u64, u64),
+    /// The Y or X bits index (ROM word) is past the last index for the IPL3 length in words
+    BitsOutOfBounds(&'static str, u32, u32, usize),
+    /// The built-in known-answer vector (generator, words, seed) expected the first checksum and the
+    /// CPU calculated the second
+    KnownAnswerMismatch(u32, usize, u8, u64, u64),
+    /// The checkpoint file is malformed
+    CheckpointParseError(String),
+    /// The checkpoint is for a different search, with the parameter that differs
+    CheckpointMismatch(&'static str),
+    /// The results file or the entry picked from it is malformed
+    ResultsParseError(String),
+    /// The GPU device couldn't be created
     WgpuRequestDeviceError(wgpu::RequestDeviceError),
+    /// Waiting on the GPU device failed
     WgpuPollError(wgpu::PollError),
+    /// The GPU device was lost or reported an error, with the reason
+    GPUDeviceLost(String),
+    /// The GPU ran out of memory allocating the buffers of the size (or running a dispatch) with the
+    /// workgroups, with the device buffer size limits
+    GPUOutOfMemory(Option<u64>, (u32, u32, u32), u64, u32),
+    /// The thread pool of the CPU search couldn't be created
+    CPUThreadPoolError(rayon::ThreadPoolBuildError),
+    /// The Ctrl+C handler couldn't be installed
+    InterruptHandlerError(ctrlc::Error),
+    /// Reading or writing a file failed
     IoError(std::io::Error),
 }
 
@@ -13,26 +82,165 @@ impl std::fmt::Display for HasherError {
             Self::ChecksumVerifyError(y, x, verify_checksum) => f.write_fmt(format_args!(
                 "GPU Hasher result is wrong: Y={y:08X} X={x:08X} | 0x{verify_checksum:012X}"
             )),
-            Self::GPUAdapterOutOfBounds => f.write_str("Selected GPU adapter doesn't exist"),
+            Self::SelfCheckError(y, x, round_checksum, checksum) => f.write_fmt(format_args!(
+                "CPU self check failed: Y={y:08X} X={x:08X} | 0x{round_checksum:012X} != 0x{checksum:012X}"
+            )),
+            Self::ShaderCheckError(shader, y, x) => f.write_fmt(format_args!(
+                "GPU shader {shader} doesn't match the CPU: Y={y:08X} X offset={x:08X}"
+            )),
+            Self::GPUSelfTestError(gpu, backend, shader, y, x, seed) => f.write_fmt(format_args!(
+                "GPU {gpu} self-test failed: the {shader} shader on the {backend} backend missed Y={y:08X} X={x:08X} (rerun with --self-test-seed 0x{seed:016X}), this driver and backend combination produces wrong results, try another --backend or --shader (or --skip-self-test to search anyway)"
+            )),
+            Self::GPUDifferentialTestError(gpu, backend, shader, case_seed, case, reported) => {
+                f.write_fmt(format_args!(
+                    "GPU {gpu} differential test failed: the {shader} shader on the {backend} backend {reported} on {case}, rerun it with --random-seed 0x{case_seed:016X} --iterations 1"
+                ))
+            }
+            Self::GPUBackendUnavailable(requested, available) => {
+                let names = |backends: &wgpu::Backends| {
+                    backends
+                        .iter_names()
+                        .map(|(name, _)| name.to_lowercase())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                };
+                f.write_fmt(format_args!(
+                    "GPU backend {} isn't available on this platform, available backends: {}",
+                    names(requested),
+                    names(available)
+                ))
+            }
+            Self::GPUAdapterNoneFound => f.write_str(
+                "No GPU adapter found (software adapters need --allow-software-adapter), search on the CPU with --fallback-cpu or --cpu-only",
+            ),
+            Self::GPUAdapterOutOfBounds => f.write_str(
+                "Selected GPU adapter doesn't exist (software adapters need --allow-software-adapter)",
+            ),
+            Self::GPUAdapterDuplicate(index) => f.write_fmt(format_args!(
+                "GPU adapter {index} is selected more than once"
+            )),
+            Self::GPULocalSizeTooLarge(local_size, max) => f.write_fmt(format_args!(
+                "Local size {local_size} exceeds the GPU adapter limit of {max} threads per workgroup"
+            )),
+            Self::GPUWorkgroupsTooLarge(dimension, size, max) => f.write_fmt(format_args!(
+                "Workgroups {dimension} size {size} needs more than 16 dispatches with the GPU adapter limit of {max} per dimension"
+            )),
+            #[cfg(feature = "glsl")]
+            Self::ShaderCompileError(e) => f.write_fmt(format_args!("Shader compile error: {}", e)),
+            Self::ShaderValidationError(error) => {
+                f.write_fmt(format_args!("GPU shader pipeline creation failed: {error}"))
+            }
+            Self::GPUAdapterNotFound(name) => {
+                f.write_fmt(format_args!("No GPU adapter matches \"{name}\""))
+            }
+            Self::GPUAdapterAmbiguous(name, candidates) => f.write_fmt(format_args!(
+                "More than one GPU adapter matches \"{name}\": {}",
+                candidates.join(", ")
+            )),
+            Self::OutputFileExists(path) => f.write_fmt(format_args!(
+                "Output file \"{}\" already exists, use --force to overwrite it",
+                path.display()
+            )),
+            Self::SignedRomMismatch(path, checksum, target_checksum) => f.write_fmt(format_args!(
+                "Signed ROM \"{}\" doesn't match when read back: its IPL3 hashes to 0x{checksum:012X} instead of 0x{target_checksum:012X}, check --format and --ipl3-offset",
+                path.display()
+            )),
+            Self::BackupFileExists(path) => f.write_fmt(format_args!(
+                "Backup file \"{}\" already exists, use --force-backup to overwrite it (or --no-backup to sign without one)",
+                path.display()
+            )),
+            Self::UnknownRomFormat(header) => f.write_fmt(format_args!(
+                "Unknown ROM format (header: 0x{header:08X}), use --format to select it"
+            )),
+            Self::IPL3OutOfBounds(offset, size, ipl3_size) => f.write_fmt(format_args!(
+                "IPL3 at offset {offset} doesn't fit in the {size} byte ROM file (IPL3 size: {ipl3_size})"
+            )),
+            Self::RomTruncated(received, offset, ipl3_size) => f.write_fmt(format_args!(
+                "ROM ended after {received} bytes, the IPL3 at offset {offset} needs {} bytes",
+                offset + ipl3_size
+            )),
+            Self::BitsOutOfBounds(name, index, last_index, ipl3_words) => f.write_fmt(format_args!(
+                "{name} bits index {index} is past the {ipl3_words} word IPL3 (last {name} index: {last_index})"
+            )),
+            Self::KnownAnswerMismatch(generator, ipl3_words, seed, expected, checksum) => {
+                f.write_fmt(format_args!(
+                    "Known-answer self-test failed: the {ipl3_words} word image {generator:08X} with seed {seed:02X} hashes to 0x{checksum:012X} instead of 0x{expected:012X}, this build calculates wrong checksums (--ignore-self-test to continue anyway)"
+                ))
+            }
+            Self::CheckpointParseError(error) => {
+                f.write_fmt(format_args!("Checkpoint file is invalid: {error}"))
+            }
+            Self::CheckpointMismatch(parameter) => f.write_fmt(format_args!(
+                "Checkpoint doesn't match current search: different {parameter}"
+            )),
+            Self::HeaderCrcRomTooSmall(size, minimum) => f.write_fmt(format_args!(
+                "ROM of {size} bytes is too small for the header CRCs, which cover the first {minimum} bytes"
+            )),
+            Self::ResultsParseError(error) => {
+                f.write_fmt(format_args!("Results file is invalid: {error}"))
+            }
             Self::WgpuRequestDeviceError(error) => f.write_str(error.to_string().as_str()),
             Self::WgpuPollError(error) => f.write_str(error.to_string().as_str()),
+            Self::GPUOutOfMemory(allocation_size, (wx, wy, wz), max_buffer_size, max_binding_size) => {
+                match allocation_size {
+                    Some(size) => f.write_fmt(format_args!(
+                        "GPU out of memory allocating {size} bytes of buffers with workgroups {wx},{wy},{wz}"
+                    ))?,
+                    None => f.write_fmt(format_args!(
+                        "GPU out of memory running a dispatch with workgroups {wx},{wy},{wz}"
+                    ))?,
+                }
+                f.write_fmt(format_args!(
+                    " (device limits: max buffer size {max_buffer_size}, max storage buffer binding size {max_binding_size})"
+                ))
+            }
+            Self::GPUDeviceLost(reason) => {
+                f.write_fmt(format_args!("GPU device was lost: {reason}"))
+            }
+            Self::CPUThreadPoolError(error) => f.write_fmt(format_args!(
+                "CPU thread pool couldn't be created: {error}"
+            )),
+            Self::InterruptHandlerError(error) => f.write_fmt(format_args!(
+                "Could not install the Ctrl+C handler: {error}"
+            )),
             Self::IoError(error) => f.write_str(error.to_string().as_str()),
         }
     }
 }
 
+impl std::error::Error for HasherError {}
+
 impl From<wgpu::RequestDeviceError> for HasherError {
     fn from(value: wgpu::RequestDeviceError) -> Self {
         Self::WgpuRequestDeviceError(value)
     }
 }
 
+#[cfg(feature = "glsl")]
+impl From<shaderc::Error> for HasherError {
+    fn from(value: shaderc::Error) -> Self {
+        Self::ShaderCompileError(value)
+    }
+}
+
 impl From<wgpu::PollError> for HasherError {
     fn from(value: wgpu::PollError) -> Self {
         Self::WgpuPollError(value)
     }
 }
 
+impl From<rayon::ThreadPoolBuildError> for HasherError {
+    fn from(value: rayon::ThreadPoolBuildError) -> Self {
+        Self::CPUThreadPoolError(value)
+    }
+}
+
+impl From<ctrlc::Error> for HasherError {
+    fn from(value: ctrlc::Error) -> Self {
+        Self::InterruptHandlerError(value)
+    }
+}
+
 impl From<std::io::Error> for HasherError {
     fn from(value: std::io::Error) -> Self {
         Self::IoError(value)
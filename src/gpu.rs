@@ -1,8 +1,100 @@
 use crate::error::HasherError;
 
+/// Picks an adapter from the list
+#[derive(Clone)]
+pub enum GPUAdapterSelector {
+    /// By its index in the list
+    Index(usize),
+    /// By a case-insensitive part of its name, matching a single adapter
+    Name(String),
+}
+
+/// The workgroups of every dispatch
+#[derive(Clone, Copy)]
+pub enum GPUWorkgroupsSelector {
+    /// Sized from a calibration dispatch to take about a quarter of a second
+    Auto,
+    /// The X, Y and Z workgroups, split into several dispatches above the adapter limit
+    Size(u32, u32, u32),
+}
+
+/// The shader the GPU runs
+#[derive(Clone)]
 pub enum GPUHasherShader {
+    /// GLSL, or WGSL on the GL and WebGPU backends where SPIR-V input isn't reliable (and without the
+    /// glsl feature)
+    Auto,
+    /// The WGSL shader, built into the binary
     Wgsl,
+    /// The GLSL shader, built into the binary and compiled to SPIR-V at runtime, needs the glsl
+    /// feature (WGSL is used without it)
     Glsl,
+    /// A GLSL shader file read and compiled at runtime in place of the built-in one, needs the glsl
+    /// feature like `Glsl`
+    GlslFile(std::path::PathBuf),
+}
+
+impl GPUHasherShader {
+    /// The name used on the command line
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Wgsl => "wgsl",
+            Self::Glsl | Self::GlslFile(_) => "glsl",
+        }
+    }
+}
+
+/// Whether the compiled pipelines were loaded from the on-disk pipeline cache
+#[derive(Clone, Copy, Debug)]
+pub enum GPUPipelineCache {
+    /// The backend has no pipeline cache (only Vulkan has one)
+    Unsupported,
+    /// The pipelines were compiled anew and the cache written
+    Miss,
+    /// The pipelines were loaded from the cache
+    Hit,
+}
+
+impl GPUPipelineCache {
+    /// The name reported in the output
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Unsupported => "unsupported",
+            Self::Miss => "miss",
+            Self::Hit => "hit",
+        }
+    }
+}
+
+/// The adapter capabilities relevant to the hasher and the configuration derived from them
+pub struct GPUDeviceInfo {
+    /// The backend of the adapter
+    pub backend: wgpu::Backend,
+    /// The name of the shader used
+    pub shader: &'static str,
+    /// The SPIR-V is passed to the driver as it is, without wgpu translating it
+    pub passthrough: bool,
+    /// The input is in push constants instead of a uniform buffer
+    pub push_constants: bool,
+    /// The device times the dispatches
+    pub timestamp_query: bool,
+    /// The shaders multiply with 64-bit integers
+    pub shader_int64: bool,
+    /// The compiled pipelines are cached on disk
+    pub pipeline_cache: bool,
+    /// The minimum and maximum subgroup size, if the adapter supports subgroups
+    pub subgroup_size: Option<(u32, u32)>,
+    /// The largest buffer the device allows
+    pub max_buffer_size: u64,
+    /// The largest storage buffer binding the device allows
+    pub max_storage_buffer_binding_size: u32,
+    /// The workgroups of every dispatch
+    pub workgroups: (u32, u32, u32),
+    /// The threads in a workgroup
+    pub local_size: u32,
+    /// The dispatches the workgroups are split into
+    pub dispatches: usize,
 }
 
 #[repr(C)]
@@ -12,114 +104,649 @@ struct GPUHasherInput {
     target_lo: u32,
     y_offset: u32,
     x_offset: u32,
-    state: [u32; 16],
+    y_top_shift: u32,
+    y_top_complement: u32,
+    y_bottom_shift: u32,
+    y_bottom_complement: u32,
+    x_stride: u32,
 }
 
+// Both shaders declare the input as the same u32 fields in this order, without any padding
+const _: () = assert!(std::mem::size_of::<GPUHasherInput>() == 9 * std::mem::size_of::<u32>());
+const _: () = assert!(std::mem::align_of::<GPUHasherInput>() == std::mem::align_of::<u32>());
+
 impl GPUHasherInput {
-    fn new(target_checksum: u64, y_offset: u32, x_offset: u32, state: [u32; 16]) -> Self {
+    fn new(target_checksum: u64, y_offset: u32, x_offset: u32, x_stride: u32) -> Self {
+        // OPTIMIZATION: The rotations of the X word by the Y word only depend on the Y round
+        let y_top_shift = y_offset >> 27;
+        let y_bottom_shift = y_offset & 0x1F;
+
         Self {
-            target_hi: ((target_checksum >> 32) & 0xFFFF) as u32,
+            target_hi: (target_checksum >> 32) as u32,
             target_lo: (target_checksum & 0xFFFFFFFF) as u32,
             y_offset,
             x_offset,
-            state,
+            y_top_shift,
+            y_top_complement: 32 - y_top_shift,
+            y_bottom_shift,
+            y_bottom_complement: 32 - y_bottom_shift,
+            x_stride,
+        }
+    }
+}
+
+/// The decimal value following the first occurrence of the prefix in a shader source, so that the
+/// constants shared with the shaders are read from them at build time
+const fn shader_constant(source: &str, prefix: &str) -> usize {
+    let (source, prefix) = (source.as_bytes(), prefix.as_bytes());
+    let mut i = 0;
+
+    while i + prefix.len() <= source.len() {
+        let mut j = 0;
+        while j < prefix.len() && source[i + j] == prefix[j] {
+            j += 1;
         }
+
+        if j == prefix.len() {
+            let mut value = 0;
+            let mut k = i + j;
+            while k < source.len() && source[k].is_ascii_digit() {
+                value = value * 10 + (source[k] - b'0') as usize;
+                k += 1;
+            }
+            return value;
+        }
+
+        i += 1;
     }
+
+    panic!("shader constant not found");
 }
 
+const MAX_RESULTS: usize =
+    shader_constant(include_str!("shaders/hasher.glsl"), "#define MAX_RESULTS ");
+
+const _: () = assert!(
+    shader_constant(
+        include_str!("shaders/hasher.wgsl"),
+        "const MAX_RESULTS: u32 = "
+    ) == MAX_RESULTS
+);
+
+/// The match count followed by the first MAX_RESULTS matches, the whole record is copied to the
+/// download buffer after every dispatch
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct GPUHasherOutput {
-    found: i32,
-    x_result: u32,
+    count: u32,
+    x_results: [u32; MAX_RESULTS],
 }
 
+// The shaders declare the output as a u32 counter and a u32 array, without any padding
+const _: () = assert!(
+    std::mem::size_of::<GPUHasherOutput>() == (1 + MAX_RESULTS) * std::mem::size_of::<u32>()
+);
+const _: () = assert!(std::mem::align_of::<GPUHasherOutput>() == std::mem::align_of::<u32>());
+
+// A state dump takes the place of the results
+const _: () = assert!(MAX_RESULTS >= 16);
+
 impl GPUHasherOutput {
-    fn get_result(&self) -> Option<u32> {
-        if self.found != 0 {
-            Some(self.x_result)
-        } else {
-            None
+    const SIZE: wgpu::BufferAddress = std::mem::size_of::<Self>() as wgpu::BufferAddress;
+
+    const EMPTY: Self = Self {
+        count: 0,
+        x_results: [0; MAX_RESULTS],
+    };
+
+    /// The stored matches in ascending order and the number of matches that didn't fit
+    fn get_results(&self) -> Option<(Vec<u32>, u32)> {
+        if self.count == 0 {
+            return None;
         }
+
+        let stored = (self.count as usize).min(MAX_RESULTS);
+        let mut x_results = self.x_results[..stored].to_vec();
+        x_results.sort_unstable();
+
+        Some((x_results, self.count - stored as u32))
     }
 }
 
-pub enum GPUHasherResult {
-    Found(u32),
-    Continue(u32),
+pub(crate) enum GPUHasherResult {
+    /// The matches in ascending order and the number of matches dropped from a full result buffer
+    Found(Vec<u32>, u32),
+    Continue,
     End,
+    /// The state before the final mix of the X value at the offset, from a state dump dispatch
+    State([u32; 16]),
+}
+
+struct GPUHasherSlot {
+    output_buffer: wgpu::Buffer,
+    download_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    timestamps: Option<GPUHasherTimestamps>,
 }
 
+/// Timestamps written at the beginning and the end of the compute pass
+struct GPUHasherTimestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    download_buffer: wgpu::Buffer,
+}
+
+impl GPUHasherTimestamps {
+    const SIZE: wgpu::BufferAddress = 2 * std::mem::size_of::<u64>() as wgpu::BufferAddress;
+
+    fn new(device: &wgpu::Device) -> Self {
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::wgt::BufferDescriptor {
+                label: Some("timestamp resolve buffer"),
+                size: Self::SIZE,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            download_buffer: device.create_buffer(&wgpu::wgt::BufferDescriptor {
+                label: Some("timestamp download buffer"),
+                size: Self::SIZE,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+        }
+    }
+}
+
+/// What the pipelines were compiled with besides the adapter and shader configuration
+#[derive(Clone, Copy, Default, PartialEq)]
+struct GPUHasherPipelineOptions {
+    /// The target checksum halves baked in, None for the input comparison
+    target: Option<(u32, u32)>,
+    early_exit_period: u32,
+    /// 64-bit multiplications, the shaders multiply 32-bit halves without them
+    shader_int64: bool,
+    /// The GLSL SPIR-V goes to the driver as it is and splits the products with unpackUint2x32
+    passthrough: bool,
+    ipl3_words: u32,
+    /// The invocation at the X offset writes its state into the results instead of searching
+    dump_state: bool,
+}
+
+/// The initial state and IPL3 words of a Y round of the X bits search
+pub(crate) type GPUXBitsRound = ([u32; 16], Vec<u32>);
+
+/// A dispatch submitted ahead of time for the X offset expected in the next call
+struct GPUHasherPending {
+    slot: usize,
+    x_bits: bool,
+    input: GPUHasherInput,
+    state: Vec<u32>,
+    submission: wgpu::SubmissionIndex,
+    time: std::time::Instant,
+}
+
+/// A wgpu device with the hasher pipelines and buffers, `Hasher` searches on one per GPU
 pub struct GPUHasher {
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    output_buffer: wgpu::Buffer,
-    download_buffer: wgpu::Buffer,
-    bind_group: wgpu::BindGroup,
+    x_bits_buffer: wgpu::Buffer,
+    state_buffer: wgpu::Buffer,
+    /// The number of Y rounds the X bits and state buffers have room for
+    round_capacity: usize,
+    state: Option<Vec<u32>>,
+    /// The X bits and IPL3 words (of every round) last uploaded, the buffer is written again only
+    /// when they change
+    x_bits_input: Option<(Vec<u32>, Vec<u32>)>,
+    /// Holds the input of every split dispatch when the adapter lacks push constants
+    input_buffer: Option<wgpu::Buffer>,
+    input_stride: wgpu::BufferAddress,
+    slots: [GPUHasherSlot; 2],
+    pending: Option<GPUHasherPending>,
+    kernel_time: Option<std::time::Duration>,
+    max_dispatch_time: Option<std::time::Duration>,
+    target_dispatch_time: Option<std::time::Duration>,
+    /// The average GPU time of a workgroup in seconds, with a target dispatch time
+    workgroup_time: Option<f64>,
+    submit_split: u32,
+    last_completion: Option<std::time::Instant>,
+    /// Set from the device callbacks once the device is lost or reports an uncaptured error
+    lost: std::sync::Arc<std::sync::Mutex<Option<String>>>,
+    out_of_memory: bool,
+    /// The requested workgroups when they were halved to fit in the device memory
+    downsized_from: Option<(u32, u32, u32)>,
+    pipeline_cache_status: GPUPipelineCache,
+    pipeline_cache: Option<wgpu::PipelineCache>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
     compute_pipeline: wgpu::ComputePipeline,
+    x_bits_pipeline: wgpu::ComputePipeline,
+    specialize: bool,
+    early_exit_period: u32,
+    shader_int64: bool,
+    passthrough: bool,
+    x_stride: u32,
+    ipl3_words: u32,
+    dump_state: bool,
+    pipeline_options: GPUHasherPipelineOptions,
+    debug: bool,
+    shader: GPUHasherShader,
     workgroups: (u32, u32, u32),
+    dispatches: Vec<(u32, u32, u32)>,
+    local_size: u32,
 }
 
 impl GPUHasher {
-    const LOCAL_WORKGROUP_SIZE: u32 = 256;
     const ENTRY_POINT: &str = "main";
+    const X_BITS_ENTRY_POINT: &str = "main_x_bits";
+    /// The X bits count, IPL3 word index, Y round count and bit offsets before the IPL3 words
+    const X_BITS_HEADER_WORDS: usize = 3 + 32;
+    const WGSL_PUSH_CONSTANT_INPUT: &str = "var<push_constant> input: Input;";
+    const WGSL_UNIFORM_INPUT: &str = "@group(0) @binding(3) var<uniform> input: Input;";
+    const WGSL_INT32_PRODUCT: &str = "let prod: vec2<u32> = mul_wide(a0, v1);";
+    const WGSL_INT64_PRODUCT: &str =
+        "let prod: vec2<u32> = vec2(u32(u64(a0) * u64(v1)), u32((u64(a0) * u64(v1)) >> 32));";
+    const LOCAL_SIZE_CONSTANT: &str = "LOCAL_SIZE_X";
+    const EARLY_EXIT_PERIOD_CONSTANT: &str = "EARLY_EXIT_PERIOD";
+    const IPL3_WORDS_CONSTANT: &str = "IPL3_WORDS";
+    const DUMP_STATE_CONSTANT: &str = "DUMP_STATE";
+    const AUTO_CALIBRATION_WORKGROUPS: u32 = 4096;
+    const AUTO_DISPATCH_DURATION: std::time::Duration = std::time::Duration::from_millis(250);
+    // Only the lower 16 bits of the checksum high word are compared, so this never matches
+    const AUTO_CALIBRATION_CHECKSUM: u64 = u64::MAX;
+    const MAX_DISPATCH_SPLIT: u32 = 16;
+    const MAX_SUBMIT_SPLIT: u32 = 256;
+    /// The relative deviation from the target dispatch time the workgroups are left as they are at
+    const TARGET_DISPATCH_TOLERANCE: f64 = 0.1;
+    /// The weight of the last dispatch in the average GPU time of a workgroup
+    const WORKGROUP_TIME_SMOOTHING: f64 = 0.25;
+    const PIPELINE_CACHE_DIR: &str = "ipl3hasher-new";
+
+    /// The adapters of the backends, ordered by their type for a power preference (adapters of the
+    /// same type keep the enumeration order), software adapters are left out unless allowed. Debug
+    /// enables the validation layers and debug info of the backends
+    pub fn list_gpu_adapters(
+        backends: wgpu::Backends,
+        power_preference: wgpu::PowerPreference,
+        allow_software_adapter: bool,
+        debug: bool,
+    ) -> Result<Vec<wgpu::Adapter>, HasherError> {
+        let available_backends = wgpu::Instance::enabled_backend_features();
+
+        if !available_backends.intersects(backends) {
+            return Err(HasherError::GPUBackendUnavailable(
+                backends,
+                available_backends,
+            ));
+        }
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends,
+            flags: if debug {
+                wgpu::InstanceFlags::debugging().with_env()
+            } else {
+                wgpu::InstanceFlags::from_build_config().with_env()
+            },
+            ..Default::default()
+        });
+
+        let mut adapters: Vec<wgpu::Adapter> = instance
+            .enumerate_adapters(backends)
+            .into_iter()
+            .filter(|adapter| {
+                allow_software_adapter || adapter.get_info().device_type != wgpu::DeviceType::Cpu
+            })
+            .collect();
+
+        // The fallback adapter (lavapipe, WARP) isn't always enumerated, it has to be asked for
+        if allow_software_adapter
+            && let Ok(adapter) =
+                pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference,
+                    force_fallback_adapter: true,
+                    compatible_surface: None,
+                }))
+            && !adapters
+                .iter()
+                .any(|listed| listed.get_info() == adapter.get_info())
+        {
+            adapters.push(adapter);
+        }
+
+        let device_types: &[wgpu::DeviceType] = match power_preference {
+            wgpu::PowerPreference::HighPerformance => &[
+                wgpu::DeviceType::DiscreteGpu,
+                wgpu::DeviceType::IntegratedGpu,
+                wgpu::DeviceType::VirtualGpu,
+            ],
+            wgpu::PowerPreference::LowPower => &[
+                wgpu::DeviceType::IntegratedGpu,
+                wgpu::DeviceType::DiscreteGpu,
+                wgpu::DeviceType::VirtualGpu,
+            ],
+            wgpu::PowerPreference::None => &[],
+        };
+
+        adapters.sort_by_key(|adapter| {
+            let device_type = adapter.get_info().device_type;
+            device_types
+                .iter()
+                .position(|preferred| *preferred == device_type)
+                .unwrap_or(device_types.len())
+        });
+
+        Ok(adapters)
+    }
+
+    /// The index of the adapter the selector picks from the list
+    pub fn select_gpu_adapter(
+        adapters: &[wgpu::Adapter],
+        selector: &GPUAdapterSelector,
+    ) -> Result<usize, HasherError> {
+        match selector {
+            GPUAdapterSelector::Index(index) => {
+                if *index < adapters.len() {
+                    Ok(*index)
+                } else {
+                    Err(HasherError::GPUAdapterOutOfBounds)
+                }
+            }
+            GPUAdapterSelector::Name(name) => {
+                let pattern = name.to_lowercase();
+                let candidates: Vec<(usize, String)> = adapters
+                    .iter()
+                    .map(|adapter| adapter.get_info().name)
+                    .enumerate()
+                    .filter(|(_, adapter_name)| adapter_name.to_lowercase().contains(&pattern))
+                    .collect();
 
-    pub fn list_gpu_adapters() -> Vec<wgpu::Adapter> {
-        wgpu::Instance::new(&wgpu::InstanceDescriptor::default())
-            .enumerate_adapters(wgpu::Backends::all())
+                match candidates.as_slice() {
+                    [] => Err(HasherError::GPUAdapterNotFound(name.clone())),
+                    [(index, _)] => Ok(*index),
+                    _ => Err(HasherError::GPUAdapterAmbiguous(
+                        name.clone(),
+                        candidates
+                            .iter()
+                            .map(|(index, adapter_name)| format!("{index}: {adapter_name}"))
+                            .collect(),
+                    )),
+                }
+            }
+        }
     }
 
-    pub fn get_gpu_info(&self) -> wgpu::AdapterInfo {
+    pub(crate) fn get_gpu_info(&self) -> wgpu::AdapterInfo {
         self.adapter.get_info()
     }
 
-    pub fn new(
+    pub(crate) fn get_gpu_limits(&self) -> wgpu::Limits {
+        self.adapter.limits()
+    }
+
+    pub(crate) fn get_workgroups(&self) -> (u32, u32, u32) {
+        self.workgroups
+    }
+
+    pub(crate) fn get_device_info(&self) -> GPUDeviceInfo {
+        let features = self.adapter.features();
+        let limits = self.adapter.limits();
+
+        GPUDeviceInfo {
+            backend: self.adapter.get_info().backend,
+            shader: self.shader.get_name(),
+            passthrough: self.passthrough,
+            push_constants: self.input_buffer.is_none(),
+            timestamp_query: features.contains(wgpu::Features::TIMESTAMP_QUERY),
+            shader_int64: self.shader_int64,
+            pipeline_cache: features.contains(wgpu::Features::PIPELINE_CACHE),
+            subgroup_size: features
+                .contains(wgpu::Features::SUBGROUP)
+                .then_some((limits.min_subgroup_size, limits.max_subgroup_size)),
+            max_buffer_size: limits.max_buffer_size,
+            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+            workgroups: self.workgroups,
+            local_size: self.local_size,
+            dispatches: self.dispatches.len(),
+        }
+    }
+
+    pub(crate) fn get_pipeline_cache(&self) -> GPUPipelineCache {
+        self.pipeline_cache_status
+    }
+
+    pub(crate) fn get_local_size(&self) -> u32 {
+        self.local_size
+    }
+
+    pub(crate) fn get_dispatch_size(&self) -> u64 {
+        let (wx, wy, wz) = self.workgroups;
+        wx as u64 * wy as u64 * wz as u64 * self.local_size as u64
+    }
+
+    /// Creates the hasher, halving the workgroups for as long as the device runs out of memory.
+    /// Debug prints every uncaptured device error, compiles the GLSL shader without optimizations
+    /// and keeps the shader runtime checks
+    pub(crate) fn new(
         adapter: wgpu::Adapter,
         shader: GPUHasherShader,
-        workgroups: (u32, u32, u32),
+        workgroups: GPUWorkgroupsSelector,
+        local_size: u32,
+        debug: bool,
     ) -> Result<Self, HasherError> {
+        let mut selector = workgroups;
+
+        loop {
+            let error =
+                match Self::create(adapter.clone(), shader.clone(), selector, local_size, debug) {
+                    Ok(mut hasher) => {
+                        if let GPUWorkgroupsSelector::Size(wx, wy, wz) = workgroups
+                            && hasher.workgroups != (wx, wy, wz)
+                        {
+                            hasher.downsized_from = Some((wx, wy, wz));
+                        }
+                        return Ok(hasher);
+                    }
+                    Err(error) => error,
+                };
+
+            let halved = match (&error, selector) {
+                (HasherError::GPUOutOfMemory(..), GPUWorkgroupsSelector::Size(wx, wy, wz)) => {
+                    Self::halve_workgroups((wx, wy, wz))
+                }
+                _ => None,
+            };
+
+            let Some((wx, wy, wz)) = halved else {
+                return Err(error);
+            };
+
+            selector = GPUWorkgroupsSelector::Size(wx, wy, wz);
+        }
+    }
+
+    /// Halves the outermost workgroups dimension larger than one, None at a single workgroup
+    fn halve_workgroups(workgroups: (u32, u32, u32)) -> Option<(u32, u32, u32)> {
+        match workgroups {
+            (wx, wy, wz) if wz > 1 => Some((wx, wy, wz / 2)),
+            (wx, wy, _) if wy > 1 => Some((wx, wy / 2, 1)),
+            (wx, _, _) if wx > 1 => Some((wx / 2, 1, 1)),
+            _ => None,
+        }
+    }
+
+    fn create(
+        adapter: wgpu::Adapter,
+        shader: GPUHasherShader,
+        workgroups: GPUWorkgroupsSelector,
+        local_size: u32,
+        debug: bool,
+    ) -> Result<Self, HasherError> {
+        let adapter_limits = adapter.limits();
+        let max_local_size = adapter_limits
+            .max_compute_invocations_per_workgroup
+            .min(adapter_limits.max_compute_workgroup_size_x);
+
+        if local_size > max_local_size {
+            return Err(HasherError::GPULocalSizeTooLarge(
+                local_size,
+                max_local_size,
+            ));
+        }
+
+        let max_workgroups = adapter_limits.max_compute_workgroups_per_dimension;
+
+        // The GL backend can't take SPIR-V input reliably, the WGSL port works everywhere. Without
+        // the glsl feature there is no GLSL compiler and every shader is the WGSL one
+        let shader = match shader {
+            _ if !cfg!(feature = "glsl") => GPUHasherShader::Wgsl,
+            GPUHasherShader::Auto => match adapter.get_info().backend {
+                wgpu::Backend::Gl | wgpu::Backend::BrowserWebGpu => GPUHasherShader::Wgsl,
+                _ => GPUHasherShader::Glsl,
+            },
+            shader => shader,
+        };
+
+        if let GPUWorkgroupsSelector::Size(wx, wy, wz) = workgroups
+            && let Some((dimension, size)) = [("x", wx), ("y", wy), ("z", wz)]
+                .into_iter()
+                .find(|(_, size)| size.div_ceil(max_workgroups) > Self::MAX_DISPATCH_SPLIT)
+        {
+            return Err(HasherError::GPUWorkgroupsTooLarge(
+                dimension,
+                size,
+                max_workgroups,
+            ));
+        }
+
+        let downlevel_limits = wgpu::Limits::downlevel_defaults();
+
+        // naga rejects the 64 to 2x32 bit cast of unpackUint2x32 (bitcasts must keep the width), so
+        // the GLSL unpack path is used only where the SPIR-V can skip naga and go to the driver as-is
+        let passthrough = matches!(shader, GPUHasherShader::Glsl | GPUHasherShader::GlslFile(_))
+            && adapter
+                .features()
+                .contains(wgpu::Features::SPIRV_SHADER_PASSTHROUGH);
+        let passthrough_feature = if passthrough {
+            wgpu::Features::SPIRV_SHADER_PASSTHROUGH
+        } else {
+            wgpu::Features::empty()
+        };
+
+        // Without push constants the dispatch input goes through a uniform buffer written before
+        // every submission, as on WebGPU
+        let push_constants = adapter.features().contains(wgpu::Features::PUSH_CONSTANTS);
+        let (push_constants_feature, push_constant_size) = if push_constants {
+            (
+                wgpu::Features::PUSH_CONSTANTS,
+                std::mem::size_of::<GPUHasherInput>() as u32,
+            )
+        } else {
+            (wgpu::Features::empty(), 0)
+        };
+
+        // Requested whenever available, the shaders fall back to 32-bit multiplications without it
+        let shader_int64_feature = adapter.features() & wgpu::Features::SHADER_INT64;
+        let pipeline_options = GPUHasherPipelineOptions {
+            shader_int64: !shader_int64_feature.is_empty(),
+            passthrough,
+            ipl3_words: crate::rom::IPL3_WORDS as u32,
+            ..Default::default()
+        };
+
+        // Requested whenever available, the queries are only created once timings are enabled
+        let timestamp_feature = adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
+
+        // Compiled pipelines can be serialized on Vulkan only, other backends always compile anew
+        let pipeline_cache_path = if adapter.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            Self::pipeline_cache_path(&adapter, &shader, (local_size, passthrough, push_constants))
+        } else {
+            None
+        };
+        let pipeline_cache_feature = if pipeline_cache_path.is_some() {
+            wgpu::Features::PIPELINE_CACHE
+        } else {
+            wgpu::Features::empty()
+        };
+
         let (device, queue) =
             pollster::block_on(adapter.request_device(&wgpu::wgt::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::PUSH_CONSTANTS | wgpu::Features::SHADER_INT64,
+                label: Some("ipl3hasher"),
+                required_features: push_constants_feature
+                    | shader_int64_feature
+                    | passthrough_feature
+                    | timestamp_feature
+                    | pipeline_cache_feature,
                 required_limits: wgpu::Limits {
-                    max_push_constant_size: 128,
-                    ..wgpu::Limits::downlevel_defaults()
+                    max_push_constant_size: push_constant_size,
+                    max_compute_invocations_per_workgroup:
+                        local_size.max(downlevel_limits.max_compute_invocations_per_workgroup),
+                    max_compute_workgroup_size_x:
+                        local_size.max(downlevel_limits.max_compute_workgroup_size_x),
+                    ..downlevel_limits
                 },
                 memory_hints: wgpu::MemoryHints::Performance,
                 trace: wgpu::Trace::Off,
             }))?;
 
-        let shader_module_descriptor = match shader {
-            GPUHasherShader::Wgsl => wgpu::include_wgsl!("shaders/hasher.wgsl"),
-            GPUHasherShader::Glsl => wgpu::include_spirv!("shaders/hasher.spv"),
-        };
+        let lost = std::sync::Arc::new(std::sync::Mutex::new(None));
 
-        let shader_module = unsafe {
-            device.create_shader_module_trusted(
-                shader_module_descriptor,
-                wgpu::ShaderRuntimeChecks::unchecked(),
-            )
+        device.set_device_lost_callback({
+            let lost = lost.clone();
+            move |reason, message| {
+                if reason != wgpu::DeviceLostReason::Destroyed {
+                    *lost.lock().unwrap() = Some(format!("{reason:?}: {message}"));
+                }
+            }
+        });
+
+        // Only the first error is reported with the dispatch that failed, debug prints all of them
+        // with the labels of the objects involved
+        device.on_uncaptured_error(Box::new({
+            let lost = lost.clone();
+            move |error| {
+                if debug {
+                    eprintln!("GPU error: {error}");
+                }
+                lost.lock().unwrap().get_or_insert(error.to_string());
+            }
+        }));
+
+        // Allocation failures are captured instead of panicking, new retries with fewer workgroups
+        device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+
+        // The precomputed state changes only once per Y round, it's uploaded when it differs
+        let (x_bits_buffer, state_buffer) =
+            Self::create_round_buffers(&device, 1, pipeline_options.ipl3_words);
+
+        let workgroups_size = match workgroups {
+            GPUWorkgroupsSelector::Auto => None,
+            GPUWorkgroupsSelector::Size(wx, wy, wz) => Some((wx, wy, wz)),
         };
 
-        let output_buffer = device.create_buffer(&wgpu::wgt::BufferDescriptor {
-            label: None,
-            size: std::mem::size_of::<GPUHasherOutput>() as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
-            mapped_at_creation: false,
-        });
+        // Auto workgroups never exceed the dimension limit, so they always take a single dispatch
+        let dispatch_count = workgroups_size
+            .map(|workgroups| Self::split_workgroups(workgroups, max_workgroups).len())
+            .unwrap_or(1);
 
-        let download_buffer = device.create_buffer(&wgpu::wgt::BufferDescriptor {
-            label: None,
-            size: std::mem::size_of::<GPUHasherOutput>() as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
+        let input_stride = (std::mem::size_of::<GPUHasherInput>() as wgpu::BufferAddress)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+
+        let input_buffer = (!push_constants).then(|| {
+            device.create_buffer(&wgpu::wgt::BufferDescriptor {
+                label: Some("input buffer"),
+                size: input_stride * dispatch_count as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
         });
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[wgpu::BindGroupLayoutEntry {
+        let mut bind_group_layout_entries = vec![
+            wgpu::BindGroupLayoutEntry {
                 binding: 0,
                 visibility: wgpu::ShaderStages::COMPUTE,
                 ty: wgpu::BindingType::Buffer {
@@ -128,113 +755,1386 @@ impl GPUHasher {
                     min_binding_size: None,
                 },
                 count: None,
-            }],
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+
+        if input_buffer.is_some() {
+            bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<GPUHasherInput>() as u64
+                    ),
+                },
+                count: None,
+            });
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bind group layout"),
+            entries: &bind_group_layout_entries,
         });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: output_buffer.as_entire_binding(),
-            }],
+        // Two sets of result buffers, so the next dispatch runs while the previous result is read
+        let slots = [0, 1].map(|slot| {
+            let output_buffer = device.create_buffer(&wgpu::wgt::BufferDescriptor {
+                label: Some(&format!("output buffer {slot}")),
+                size: GPUHasherOutput::SIZE,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            let download_buffer = device.create_buffer(&wgpu::wgt::BufferDescriptor {
+                label: Some(&format!("download buffer {slot}")),
+                size: GPUHasherOutput::SIZE,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            let bind_group = Self::create_bind_group(
+                &device,
+                &bind_group_layout,
+                slot,
+                &output_buffer,
+                &x_bits_buffer,
+                &state_buffer,
+                input_buffer.as_ref(),
+            );
+
+            GPUHasherSlot {
+                output_buffer,
+                download_buffer,
+                bind_group,
+                timestamps: None,
+            }
         });
 
+        let push_constant_ranges = if push_constants {
+            vec![wgpu::PushConstantRange {
+                range: (0..push_constant_size),
+                stages: wgpu::ShaderStages::COMPUTE,
+            }]
+        } else {
+            vec![]
+        };
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
+            label: Some("pipeline layout"),
             bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[wgpu::PushConstantRange {
-                range: (0..std::mem::size_of::<GPUHasherInput>() as u32),
-                stages: wgpu::ShaderStages::COMPUTE,
-            }],
+            push_constant_ranges: &push_constant_ranges,
         });
 
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            module: &shader_module,
-            entry_point: Some(Self::ENTRY_POINT),
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            cache: None,
+        // A missing or unreadable file starts an empty cache, fallback makes the driver discard data
+        // that doesn't match this device or is corrupt instead of failing
+        let pipeline_cache_data = pipeline_cache_path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok());
+        let pipeline_cache = pipeline_cache_path.as_ref().map(|_| unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("pipeline cache"),
+                data: pipeline_cache_data.as_deref(),
+                fallback: true,
+            })
         });
 
-        Ok(Self {
+        let compute_pipeline = Self::create_compute_pipeline(
+            &device,
+            &pipeline_layout,
+            pipeline_cache.as_ref(),
+            &shader,
+            local_size,
+            false,
+            push_constants,
+            debug,
+            pipeline_options,
+        )?;
+
+        let x_bits_pipeline = Self::create_compute_pipeline(
+            &device,
+            &pipeline_layout,
+            pipeline_cache.as_ref(),
+            &shader,
+            local_size,
+            true,
+            push_constants,
+            debug,
+            pipeline_options,
+        )?;
+
+        let pipeline_cache_status = match (&pipeline_cache, pipeline_cache_path) {
+            (Some(_), Some(_)) if pipeline_cache_data.is_some() => GPUPipelineCache::Hit,
+            (Some(cache), Some(path)) => {
+                if let Some(data) = cache.get_data() {
+                    Self::save_pipeline_cache(&path, &data);
+                }
+                GPUPipelineCache::Miss
+            }
+            _ => GPUPipelineCache::Unsupported,
+        };
+
+        let workgroups = workgroups_size.unwrap_or((
+            Self::AUTO_CALIBRATION_WORKGROUPS
+                .min(adapter_limits.max_compute_workgroups_per_dimension),
+            1,
+            1,
+        ));
+
+        if pollster::block_on(device.pop_error_scope()).is_some() {
+            let allocation_size = [&x_bits_buffer, &state_buffer]
+                .into_iter()
+                .chain(&input_buffer)
+                .chain(
+                    slots
+                        .iter()
+                        .flat_map(|slot| [&slot.output_buffer, &slot.download_buffer]),
+                )
+                .map(|buffer| buffer.size())
+                .sum();
+            return Err(Self::out_of_memory_error(
+                &device,
+                Some(allocation_size),
+                workgroups,
+            ));
+        }
+
+        let auto_workgroups = workgroups_size.is_none();
+
+        let mut hasher = Self {
             adapter,
             device,
             queue,
-            output_buffer,
-            download_buffer,
-            bind_group,
+            x_bits_buffer,
+            state_buffer,
+            round_capacity: 1,
+            state: None,
+            x_bits_input: None,
+            input_buffer,
+            input_stride,
+            slots,
+            pending: None,
+            kernel_time: None,
+            max_dispatch_time: None,
+            target_dispatch_time: None,
+            workgroup_time: None,
+            submit_split: 1,
+            last_completion: None,
+            lost,
+            out_of_memory: false,
+            downsized_from: None,
+            pipeline_cache_status,
+            pipeline_cache,
+            bind_group_layout,
+            pipeline_layout,
             compute_pipeline,
+            x_bits_pipeline,
+            specialize: false,
+            early_exit_period: 0,
+            shader_int64: pipeline_options.shader_int64,
+            passthrough: pipeline_options.passthrough,
+            x_stride: 1,
+            ipl3_words: pipeline_options.ipl3_words,
+            dump_state: false,
+            pipeline_options,
+            debug,
+            shader,
             workgroups,
+            dispatches: Self::split_workgroups(workgroups, max_workgroups),
+            local_size,
+        };
+
+        if auto_workgroups {
+            hasher.workgroups = hasher.get_auto_workgroups()?;
+            hasher.dispatches = Self::split_workgroups(hasher.workgroups, max_workgroups);
+        }
+
+        Ok(hasher)
+    }
+
+    /// The X bits and state buffers sized for the given number of Y rounds searched together
+    fn create_round_buffers(
+        device: &wgpu::Device,
+        y_count: usize,
+        ipl3_words: u32,
+    ) -> (wgpu::Buffer, wgpu::Buffer) {
+        let x_bits_buffer = device.create_buffer(&wgpu::wgt::BufferDescriptor {
+            label: Some("x bits buffer"),
+            size: ((Self::X_BITS_HEADER_WORDS + ipl3_words as usize * y_count)
+                * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let state_buffer = device.create_buffer(&wgpu::wgt::BufferDescriptor {
+            label: Some("state buffer"),
+            size: (16 * y_count * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        (x_bits_buffer, state_buffer)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        slot: usize,
+        output_buffer: &wgpu::Buffer,
+        x_bits_buffer: &wgpu::Buffer,
+        state_buffer: &wgpu::Buffer,
+        input_buffer: Option<&wgpu::Buffer>,
+    ) -> wgpu::BindGroup {
+        let mut bind_group_entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: output_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: x_bits_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: state_buffer.as_entire_binding(),
+            },
+        ];
+
+        if let Some(input_buffer) = input_buffer {
+            bind_group_entries.push(wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: input_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<GPUHasherInput>() as u64),
+                }),
+            });
+        }
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("bind group {slot}")),
+            layout,
+            entries: &bind_group_entries,
         })
     }
 
-    pub fn x_round(
+    /// Grows the X bits and state buffers to fit the Y rounds, dropping any pending dispatch
+    fn reserve_rounds(&mut self, y_count: usize) {
+        if y_count <= self.round_capacity {
+            return;
+        }
+
+        let (x_bits_buffer, state_buffer) =
+            Self::create_round_buffers(&self.device, y_count, self.ipl3_words);
+
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            slot.bind_group = Self::create_bind_group(
+                &self.device,
+                &self.bind_group_layout,
+                i,
+                &slot.output_buffer,
+                &x_bits_buffer,
+                &state_buffer,
+                self.input_buffer.as_ref(),
+            );
+        }
+
+        self.x_bits_buffer = x_bits_buffer;
+        self.state_buffer = state_buffer;
+        self.round_capacity = y_count;
+        self.state = None;
+        self.x_bits_input = None;
+        self.pending = None;
+    }
+
+    /// Splits the workgroups into dispatches that fit in the per dimension limit, each dispatch
+    /// covers the X range right after the previous one
+    fn split_workgroups(workgroups: (u32, u32, u32), max: u32) -> Vec<(u32, u32, u32)> {
+        let split = |size: u32| -> Vec<u32> {
+            (0..size.div_ceil(max))
+                .map(|i| (size - i * max).min(max))
+                .collect()
+        };
+
+        let (wx, wy, wz) = workgroups;
+        let mut dispatches = vec![];
+
+        for z in split(wz) {
+            for y in split(wy) {
+                for x in split(wx) {
+                    dispatches.push((x, y, z));
+                }
+            }
+        }
+
+        dispatches
+    }
+
+    /// Splits a dispatch along its outermost dimension larger than one into up to the given number
+    /// of parts, each part covers the X range right after the previous one
+    fn split_dispatch(dispatch: (u32, u32, u32), parts: u32) -> Vec<(u32, u32, u32)> {
+        let (wx, wy, wz) = dispatch;
+        let size = if wz > 1 {
+            wz
+        } else if wy > 1 {
+            wy
+        } else {
+            wx
+        };
+        let parts = parts.clamp(1, size) as u64;
+
+        (0..parts)
+            .map(|i| {
+                let part = (size as u64 * (i + 1) / parts - size as u64 * i / parts) as u32;
+                if wz > 1 {
+                    (wx, wy, part)
+                } else if wy > 1 {
+                    (wx, part, 1)
+                } else {
+                    (part, 1, 1)
+                }
+            })
+            .collect()
+    }
+
+    /// Times a calibration dispatch and picks the largest power of two workgroup count that fits
+    /// in the dimension limits and the target dispatch duration, so the X space is split evenly
+    fn get_auto_workgroups(&mut self) -> Result<(u32, u32, u32), HasherError> {
+        let mut elapsed = std::time::Duration::ZERO;
+
+        // The first dispatch only warms up the device, the second one is timed
+        for _ in 0..2 {
+            let time = std::time::Instant::now();
+            self.x_round(Self::AUTO_CALIBRATION_CHECKSUM, 0, 0, None, [0; 16])?;
+            elapsed = time.elapsed();
+        }
+
+        let hashes_per_second = self.get_dispatch_size() as f64 / elapsed.as_secs_f64();
+        let target_size = hashes_per_second * Self::AUTO_DISPATCH_DURATION.as_secs_f64();
+
+        let floor_power_of_two = |value: u64| 1u64 << (63 - value.max(1).leading_zeros());
+
+        let max_dimension =
+            floor_power_of_two(self.get_gpu_limits().max_compute_workgroups_per_dimension as u64);
+        let total = floor_power_of_two(
+            ((target_size as u64) / self.local_size as u64).min((1 << 32) / self.local_size as u64),
+        );
+
+        let wx = total.min(max_dimension);
+        let wy = (total / wx).min(max_dimension);
+        let wz = (total / (wx * wy)).min(max_dimension);
+
+        Ok((wx as u32, wy as u32, wz as u32))
+    }
+
+    /// The cache file in the user cache directory, named after the adapter and a hash of everything
+    /// the compiled pipelines depend on
+    fn pipeline_cache_path(
+        adapter: &wgpu::Adapter,
+        shader: &GPUHasherShader,
+        options: impl std::hash::Hash,
+    ) -> Option<std::path::PathBuf> {
+        use std::hash::{Hash, Hasher};
+
+        let key = wgpu::util::pipeline_cache_key(&adapter.get_info())?;
+
+        let source = match shader {
+            GPUHasherShader::Wgsl => {
+                std::borrow::Cow::Borrowed(include_str!("shaders/hasher.wgsl"))
+            }
+            GPUHasherShader::Glsl => {
+                std::borrow::Cow::Borrowed(include_str!("shaders/hasher.glsl"))
+            }
+            GPUHasherShader::GlslFile(path) => {
+                std::borrow::Cow::Owned(std::fs::read_to_string(path).ok()?)
+            }
+            GPUHasherShader::Auto => unreachable!("auto shader is resolved in new"),
+        };
+
+        let mut hasher = std::hash::DefaultHasher::new();
+        shader.get_name().hash(&mut hasher);
+        source.hash(&mut hasher);
+        options.hash(&mut hasher);
+
+        let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+            .or_else(|| std::env::var_os("LOCALAPPDATA"))
+            .map(std::path::PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".cache"))
+            })?;
+
+        Some(
+            cache_dir
+                .join(Self::PIPELINE_CACHE_DIR)
+                .join(format!("{key}-{:016x}", hasher.finish())),
+        )
+    }
+
+    /// Failures are ignored, the pipelines are just compiled again on the next run
+    fn save_pipeline_cache(path: &std::path::Path, data: &[u8]) {
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        // Written aside and renamed so that a concurrent run never reads a partial file
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        if std::fs::create_dir_all(dir).is_err() || std::fs::write(&temp_path, data).is_err() {
+            return;
+        }
+        if std::fs::rename(&temp_path, path).is_err() {
+            std::fs::remove_file(&temp_path).ok();
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_compute_pipeline(
+        device: &wgpu::Device,
+        pipeline_layout: &wgpu::PipelineLayout,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        shader: &GPUHasherShader,
+        local_size: u32,
+        x_bits: bool,
+        push_constants: bool,
+        debug: bool,
+        options: GPUHasherPipelineOptions,
+    ) -> Result<wgpu::ComputePipeline, HasherError> {
+        let wgsl_constants = [
+            (Self::LOCAL_SIZE_CONSTANT, local_size as f64),
+            (
+                Self::EARLY_EXIT_PERIOD_CONSTANT,
+                options.early_exit_period as f64,
+            ),
+            (Self::IPL3_WORDS_CONSTANT, options.ipl3_words as f64),
+            (Self::DUMP_STATE_CONSTANT, options.dump_state as u32 as f64),
+        ];
+
+        let (shader_module_descriptor, entry_point, constants): (_, _, &[(&str, f64)]) =
+            match shader {
+                GPUHasherShader::Wgsl => (
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("hasher.wgsl"),
+                        source: wgpu::ShaderSource::Wgsl({
+                            let mut source =
+                                std::borrow::Cow::Borrowed(include_str!("shaders/hasher.wgsl"));
+                            if !push_constants {
+                                source = std::borrow::Cow::Owned(source.replace(
+                                    Self::WGSL_PUSH_CONSTANT_INPUT,
+                                    Self::WGSL_UNIFORM_INPUT,
+                                ));
+                            }
+                            if options.shader_int64 {
+                                source =
+                                    std::borrow::Cow::Owned(source.replace(
+                                        Self::WGSL_INT32_PRODUCT,
+                                        Self::WGSL_INT64_PRODUCT,
+                                    ));
+                            }
+                            source
+                        }),
+                    },
+                    if x_bits {
+                        Self::X_BITS_ENTRY_POINT
+                    } else {
+                        Self::ENTRY_POINT
+                    },
+                    &wgsl_constants,
+                ),
+                GPUHasherShader::Auto => unreachable!("auto shader is resolved in new"),
+                #[cfg(not(feature = "glsl"))]
+                GPUHasherShader::Glsl | GPUHasherShader::GlslFile(_) => {
+                    unreachable!("GLSL shader is resolved to WGSL in new without the glsl feature")
+                }
+                #[cfg(feature = "glsl")]
+                GPUHasherShader::Glsl | GPUHasherShader::GlslFile(_) => {
+                    let mut macros = vec![(Self::LOCAL_SIZE_CONSTANT, local_size.to_string())];
+                    if x_bits {
+                        macros.push(("X_BITS", String::from("1")));
+                    }
+                    if options.shader_int64 {
+                        macros.push(("SHADER_INT64", String::from("1")));
+                    }
+                    if options.passthrough {
+                        macros.push(("USE_UNPACK_UINT_2X32", String::from("1")));
+                    }
+                    if !push_constants {
+                        macros.push(("INPUT_UNIFORM_BUFFER", String::from("1")));
+                    }
+                    if options.early_exit_period > 0 {
+                        macros.push((
+                            Self::EARLY_EXIT_PERIOD_CONSTANT,
+                            options.early_exit_period.to_string(),
+                        ));
+                    }
+                    macros.push((Self::IPL3_WORDS_CONSTANT, options.ipl3_words.to_string()));
+                    if options.dump_state {
+                        macros.push((Self::DUMP_STATE_CONSTANT, String::from("1")));
+                    }
+                    if let Some((target_hi, target_lo)) = options.target {
+                        macros.push(("TARGET_HI", format!("{target_hi:#X}u")));
+                        macros.push(("TARGET_LO", format!("{target_lo:#X}u")));
+                    }
+                    // A shader file given at runtime replaces the embedded source, with the same macros
+                    let (name, source) = match shader {
+                        GPUHasherShader::GlslFile(path) => (
+                            path.display().to_string(),
+                            std::borrow::Cow::Owned(std::fs::read_to_string(path)?),
+                        ),
+                        _ => (
+                            String::from("hasher.glsl"),
+                            std::borrow::Cow::Borrowed(include_str!("shaders/hasher.glsl")),
+                        ),
+                    };
+                    (
+                        wgpu::ShaderModuleDescriptor {
+                            label: Some("hasher.glsl"),
+                            source: wgpu::ShaderSource::SpirV(std::borrow::Cow::Owned(
+                                crate::compiler::compile_glsl_to_spirv(
+                                    &name, &source, &macros, debug,
+                                )?,
+                            )),
+                        },
+                        Self::ENTRY_POINT,
+                        &[],
+                    )
+                }
+            };
+
+        // Validation errors are captured instead of panicking, a shader file may well be broken
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let label = shader_module_descriptor.label;
+
+        let shader_module = match shader_module_descriptor.source {
+            wgpu::ShaderSource::SpirV(source) if options.passthrough => unsafe {
+                device.create_shader_module_passthrough(
+                    wgpu::ShaderModuleDescriptorPassthrough::SpirV(
+                        wgpu::ShaderModuleDescriptorSpirV { label, source },
+                    ),
+                )
+            },
+            source => unsafe {
+                device.create_shader_module_trusted(
+                    wgpu::ShaderModuleDescriptor { label, source },
+                    if debug {
+                        wgpu::ShaderRuntimeChecks::checked()
+                    } else {
+                        wgpu::ShaderRuntimeChecks::unchecked()
+                    },
+                )
+            },
+        };
+
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(if x_bits {
+                "x bits pipeline"
+            } else {
+                "hasher pipeline"
+            }),
+            layout: Some(pipeline_layout),
+            module: &shader_module,
+            entry_point: Some(entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants,
+                ..Default::default()
+            },
+            cache: pipeline_cache,
+        });
+
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(HasherError::ShaderValidationError(error.to_string()));
+        }
+
+        Ok(compute_pipeline)
+    }
+
+    /// The same adapter, shader and dispatch time budget with other workgroups
+    pub(crate) fn with_workgroups(
+        &self,
+        workgroups: (u32, u32, u32),
+        local_size: u32,
+    ) -> Result<Self, HasherError> {
+        let (wx, wy, wz) = workgroups;
+        let mut hasher = Self::new(
+            self.adapter.clone(),
+            self.shader.clone(),
+            GPUWorkgroupsSelector::Size(wx, wy, wz),
+            local_size,
+            self.debug,
+        )?;
+        hasher.max_dispatch_time = self.max_dispatch_time;
+        hasher.target_dispatch_time = self.target_dispatch_time;
+        hasher.specialize = self.specialize;
+        hasher.early_exit_period = self.early_exit_period;
+        hasher.set_shader_int64(self.shader_int64);
+        hasher.set_passthrough(self.passthrough);
+        hasher.x_stride = self.x_stride;
+        hasher.set_ipl3_words(self.ipl3_words);
+        Ok(hasher)
+    }
+
+    /// Compiles the target checksum into the shader instead of reading it from the input, the
+    /// pipelines are rebuilt on the first dispatch for every new target. Returns false when the
+    /// shader isn't compiled at runtime (WGSL), which keeps comparing against the input
+    pub(crate) fn set_specialized(&mut self, specialize: bool) -> bool {
+        let supported = matches!(
+            self.shader,
+            GPUHasherShader::Glsl | GPUHasherShader::GlslFile(_)
+        );
+        self.specialize = specialize && supported;
+        supported
+    }
+
+    pub(crate) fn is_specialized(&self) -> bool {
+        self.specialize
+    }
+
+    /// Lets every invocation return early once a match was found in its dispatch, the X bits
+    /// checksum checks again every period words of its loop, 0 disables it. The pipelines are
+    /// rebuilt on the next dispatch
+    pub(crate) fn set_early_exit_period(&mut self, early_exit_period: u32) {
+        self.early_exit_period = early_exit_period;
+    }
+
+    pub(crate) fn get_early_exit_period(&self) -> u32 {
+        self.early_exit_period
+    }
+
+    /// The IPL3 length in words the shaders hash, the pipelines are rebuilt on the next dispatch
+    /// and the X bits buffer on the next X bits round
+    pub(crate) fn set_ipl3_words(&mut self, ipl3_words: u32) {
+        if ipl3_words != self.ipl3_words {
+            self.ipl3_words = ipl3_words;
+            self.round_capacity = 0;
+        }
+    }
+
+    /// Compiles the shaders with 64-bit multiplications when the device supports them, otherwise
+    /// they multiply 32-bit halves. The pipelines are rebuilt on the next dispatch
+    pub(crate) fn set_shader_int64(&mut self, shader_int64: bool) {
+        self.shader_int64 = shader_int64
+            && self
+                .device
+                .features()
+                .contains(wgpu::Features::SHADER_INT64);
+    }
+
+    /// Passes the GLSL SPIR-V to the driver as it is when the device supports it, which splits the
+    /// 64-bit products with unpackUint2x32, otherwise wgpu translates it and the products are
+    /// shifted. The pipelines are rebuilt on the next dispatch
+    pub(crate) fn set_passthrough(&mut self, passthrough: bool) {
+        self.passthrough = passthrough
+            && self
+                .device
+                .features()
+                .contains(wgpu::Features::SPIRV_SHADER_PASSTHROUGH);
+    }
+
+    /// Invocation i of a dispatch at X offset x hashes the X value (x + i) * stride, wrapping in the
+    /// X space. The stride must be odd, which makes the dispatches still visit every X value once,
+    /// and the results are reported as X offsets
+    pub(crate) fn set_x_stride(&mut self, x_stride: u32) {
+        self.x_stride = x_stride;
+    }
+
+    /// Makes the dispatches return the state before the final mix of the X value at their offset,
+    /// hashed without the X stride, instead of searching. The pipelines are rebuilt on the next
+    /// dispatch
+    pub(crate) fn set_dump_state(&mut self, dump_state: bool) {
+        self.dump_state = dump_state;
+    }
+
+    pub(crate) fn get_shader_name(&self) -> &'static str {
+        self.shader.get_name()
+    }
+
+    fn rebuild_pipelines(&mut self, options: GPUHasherPipelineOptions) -> Result<(), HasherError> {
+        let push_constants = self.input_buffer.is_none();
+
+        let [compute_pipeline, x_bits_pipeline] = [false, true].map(|x_bits| {
+            Self::create_compute_pipeline(
+                &self.device,
+                &self.pipeline_layout,
+                self.pipeline_cache.as_ref(),
+                &self.shader,
+                self.local_size,
+                x_bits,
+                push_constants,
+                self.debug,
+                options,
+            )
+        });
+
+        self.compute_pipeline = compute_pipeline?;
+        self.x_bits_pipeline = x_bits_pipeline?;
+        self.pipeline_options = options;
+        self.pending = None;
+
+        Ok(())
+    }
+
+    /// Splits every dispatch into as many submissions as needed to keep each of them under the
+    /// time budget (the OS GPU watchdog resets the device on long submissions), None disables it
+    pub(crate) fn set_max_dispatch_time(&mut self, max_dispatch_time: Option<std::time::Duration>) {
+        self.max_dispatch_time = max_dispatch_time;
+        self.submit_split = 1;
+    }
+
+    /// Scales the workgroups after every dispatch so that the dispatches take about the target GPU
+    /// time, None keeps them as they are
+    pub(crate) fn set_target_dispatch_time(
+        &mut self,
+        target_dispatch_time: Option<std::time::Duration>,
+    ) {
+        self.target_dispatch_time = target_dispatch_time;
+    }
+
+    /// The same adapter and local size with another shader, a single workgroup per dispatch
+    pub(crate) fn with_shader(&self, shader: GPUHasherShader) -> Result<Self, HasherError> {
+        let mut hasher = Self::new(
+            self.adapter.clone(),
+            shader,
+            GPUWorkgroupsSelector::Size(1, 1, 1),
+            self.local_size,
+            self.debug,
+        )?;
+        hasher.set_shader_int64(self.shader_int64);
+        // Passthrough turned off on a device that supports it stays off with the other shader
+        if self
+            .device
+            .features()
+            .contains(wgpu::Features::SPIRV_SHADER_PASSTHROUGH)
+        {
+            hasher.set_passthrough(self.passthrough);
+        }
+        hasher.x_stride = self.x_stride;
+        hasher.set_ipl3_words(self.ipl3_words);
+        Ok(hasher)
+    }
+
+    /// Times the compute pass of every following dispatch with timestamp queries, returns false
+    /// when the adapter doesn't support them
+    pub(crate) fn enable_timings(&mut self) -> bool {
+        if !self
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+        {
+            return false;
+        }
+
+        for slot in &mut self.slots {
+            slot.timestamps = Some(GPUHasherTimestamps::new(&self.device));
+        }
+
+        true
+    }
+
+    /// Waits for the pending dispatch to finish, if there is one, its result is dropped unread
+    pub(crate) fn drain(&mut self) -> Result<(), HasherError> {
+        if let Some(pending) = self.pending.take() {
+            let poll = self
+                .device
+                .poll(wgpu::PollType::WaitForSubmissionIndex(pending.submission));
+
+            self.check_lost()?;
+            poll?;
+        }
+
+        Ok(())
+    }
+
+    /// Recreates the device with the same adapter and configuration after it was lost, dropping
+    /// any pending dispatch
+    pub(crate) fn reset(&mut self) -> Result<(), HasherError> {
+        let timings = self.slots[0].timestamps.is_some();
+
+        *self = self.with_workgroups(self.workgroups, self.local_size)?;
+
+        if timings {
+            self.enable_timings();
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn is_out_of_memory(&self) -> bool {
+        self.out_of_memory
+    }
+
+    /// Replaces the hasher with one using half the workgroups after a dispatch ran out of memory,
+    /// returns false when it's already down to a single workgroup
+    pub(crate) fn downsize(&mut self) -> Result<bool, HasherError> {
+        let Some(workgroups) = Self::halve_workgroups(self.workgroups) else {
+            return Ok(false);
+        };
+
+        let downsized_from = self.downsized_from.unwrap_or(self.workgroups);
+        let timings = self.slots[0].timestamps.is_some();
+
+        *self = self.with_workgroups(workgroups, self.local_size)?;
+        self.downsized_from = Some(downsized_from);
+
+        if timings {
+            self.enable_timings();
+        }
+
+        Ok(true)
+    }
+
+    /// The requested workgroups, once after the hasher was downsized
+    pub(crate) fn take_downsized(&mut self) -> Option<(u32, u32, u32)> {
+        self.downsized_from.take()
+    }
+
+    fn out_of_memory_error(
+        device: &wgpu::Device,
+        allocation_size: Option<u64>,
+        workgroups: (u32, u32, u32),
+    ) -> HasherError {
+        let limits = device.limits();
+        HasherError::GPUOutOfMemory(
+            allocation_size,
+            workgroups,
+            limits.max_buffer_size,
+            limits.max_storage_buffer_binding_size,
+        )
+    }
+
+    fn check_lost(&self) -> Result<(), HasherError> {
+        match self.lost.lock().unwrap().clone() {
+            Some(reason) => Err(HasherError::GPUDeviceLost(reason)),
+            None => Ok(()),
+        }
+    }
+
+    /// The GPU time of the compute pass in the last dispatch read back, if it was timed
+    pub(crate) fn take_kernel_time(&mut self) -> Option<std::time::Duration> {
+        self.kernel_time.take()
+    }
+
+    pub(crate) fn x_round(
         &mut self,
         target_checksum: u64,
         y_offset: u32,
         x_offset: u32,
+        x_next: Option<u64>,
         initial_state: [u32; 16],
     ) -> Result<GPUHasherResult, HasherError> {
-        let (wx, wy, wz) = self.workgroups;
+        let input = GPUHasherInput::new(target_checksum, y_offset, x_offset, self.x_stride);
 
-        let mut command_encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.dispatch(false, input, initial_state.to_vec(), x_next, 1 << 32)
+    }
 
-        {
-            let mut compute_pass =
-                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: None,
-                    timestamp_writes: None,
-                });
-            compute_pass.set_pipeline(&self.compute_pipeline);
-            compute_pass.set_bind_group(0, &self.bind_group, &[]);
-            compute_pass.set_push_constants(
-                0,
-                bytemuck::bytes_of(&GPUHasherInput::new(
-                    target_checksum,
-                    y_offset,
-                    x_offset,
-                    initial_state,
-                )),
-            );
-            compute_pass.dispatch_workgroups(wx, wy, wz);
+    /// Searches the X bits of one or more Y rounds (their initial state and IPL3 words) in a single
+    /// X range, round i takes the X values from i << x_bits.len(), the results keep that offset
+    pub(crate) fn x_bits_round(
+        &mut self,
+        target_checksum: u64,
+        x_offset: u32,
+        x_next: Option<u64>,
+        rounds: &[GPUXBitsRound],
+        x_bits: &[u32],
+    ) -> Result<GPUHasherResult, HasherError> {
+        self.reserve_rounds(rounds.len());
+
+        let ipl3_words = self.ipl3_words as usize;
+
+        let ipl3_changed = !matches!(&self.x_bits_input, Some((bits, words))
+            if bits == x_bits
+                && words.len() == rounds.len() * ipl3_words
+                && words.chunks_exact(ipl3_words).zip(rounds).all(|(words, (_, ipl3))| words == &ipl3[..]));
+
+        if ipl3_changed {
+            let mut x_bits_data = vec![0u32; Self::X_BITS_HEADER_WORDS + ipl3_words * rounds.len()];
+
+            x_bits_data[0] = x_bits.len() as u32;
+            x_bits_data[1] = x_bits[0] / 32;
+            x_bits_data[2] = rounds.len() as u32;
+            x_bits_data[3..(3 + x_bits.len())].copy_from_slice(x_bits);
+            for (words, (_, ipl3)) in x_bits_data[Self::X_BITS_HEADER_WORDS..]
+                .chunks_exact_mut(ipl3_words)
+                .zip(rounds)
+            {
+                words.copy_from_slice(&ipl3[..]);
+            }
+
+            self.queue
+                .write_buffer(&self.x_bits_buffer, 0, bytemuck::cast_slice(&x_bits_data));
+            self.x_bits_input = Some((
+                x_bits.to_vec(),
+                x_bits_data[Self::X_BITS_HEADER_WORDS..].to_vec(),
+            ));
         }
 
-        command_encoder.copy_buffer_to_buffer(
-            &self.output_buffer,
-            0,
-            &self.download_buffer,
-            0,
-            self.output_buffer.size(),
-        );
+        let input = GPUHasherInput::new(target_checksum, 0, x_offset, self.x_stride);
+        let state = rounds.iter().flat_map(|(state, _)| *state).collect();
+
+        self.dispatch(
+            true,
+            input,
+            state,
+            x_next,
+            (rounds.len() as u64) << x_bits.len(),
+        )
+    }
+
+    /// Runs the dispatch for the input and returns its result, the dispatch for x_next (the X offset
+    /// the caller asks for next) is submitted before waiting, so the GPU doesn't idle during readback
+    fn dispatch(
+        &mut self,
+        x_bits: bool,
+        input: GPUHasherInput,
+        state: Vec<u32>,
+        x_next: Option<u64>,
+        x_size: u64,
+    ) -> Result<GPUHasherResult, HasherError> {
+        let x_offset = input.x_offset;
+
+        let input = if self.dump_state {
+            GPUHasherInput {
+                x_stride: 1,
+                ..input
+            }
+        } else {
+            input
+        };
+
+        let options = GPUHasherPipelineOptions {
+            target: self
+                .specialize
+                .then_some((input.target_hi, input.target_lo)),
+            early_exit_period: self.early_exit_period,
+            shader_int64: self.shader_int64,
+            passthrough: self.passthrough,
+            ipl3_words: self.ipl3_words,
+            dump_state: self.dump_state,
+        };
+        if self.pipeline_options != options {
+            self.rebuild_pipelines(options)?;
+        }
+
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+
+        // A pending dispatch for anything else (a new Y round or X offset) is dropped unread
+        let (slot, submission, submit_time) = match self.pending.take() {
+            Some(pending)
+                if pending.x_bits == x_bits
+                    && pending.state == state
+                    && bytemuck::bytes_of(&pending.input) == bytemuck::bytes_of(&input) =>
+            {
+                (pending.slot, pending.submission, pending.time)
+            }
+            _ => {
+                // The state buffer lives as long as the hasher, the write lands before the next
+                // submission and after the dispatch ahead (dropped here) that read the previous state
+                if self.state.as_ref() != Some(&state) {
+                    self.queue
+                        .write_buffer(&self.state_buffer, 0, bytemuck::cast_slice(&state));
+                    self.state = Some(state.clone());
+                }
+                let time = std::time::Instant::now();
+                (0, self.submit(0, x_bits, input), time)
+            }
+        };
 
-        let command_buffer = command_encoder.finish();
+        if let Some(x_next) = x_next
+            && x_next < x_size
+        {
+            let input = GPUHasherInput {
+                x_offset: x_next as u32,
+                ..input
+            };
+            let slot = slot ^ 1;
+            let time = std::time::Instant::now();
+            let submission = self.submit(slot, x_bits, input);
+            self.pending = Some(GPUHasherPending {
+                slot,
+                x_bits,
+                input,
+                state,
+                submission,
+                time,
+            });
+        }
 
-        self.queue.submit([command_buffer]);
+        if pollster::block_on(self.device.pop_error_scope()).is_some() {
+            self.out_of_memory = true;
+            self.pending = None;
+            return Err(Self::out_of_memory_error(
+                &self.device,
+                None,
+                self.workgroups,
+            ));
+        }
 
-        let buffer_slice = self.download_buffer.slice(..);
+        let download_buffer = &self.slots[slot].download_buffer;
+        let buffer_slice = download_buffer.slice(..GPUHasherOutput::SIZE);
 
         buffer_slice.map_async(wgpu::MapMode::Read, |_| {});
 
-        self.device.poll(wgpu::PollType::Wait)?;
+        let timestamps_slice = self.slots[slot]
+            .timestamps
+            .as_ref()
+            .map(|timestamps| timestamps.download_buffer.slice(..));
+
+        if let Some(timestamps_slice) = &timestamps_slice {
+            timestamps_slice.map_async(wgpu::MapMode::Read, |_| {});
+        }
+
+        // Blocks on the submission fence in the driver, the thread sleeps until the GPU is done
+        // instead of spinning on poll
+        let poll = self
+            .device
+            .poll(wgpu::PollType::WaitForSubmissionIndex(submission));
+
+        // The mapped buffers are invalid on a lost device, its result must not be read
+        self.check_lost()?;
+        poll?;
+
+        let completion = std::time::Instant::now();
 
         let result = *bytemuck::from_bytes::<GPUHasherOutput>(&buffer_slice.get_mapped_range());
 
-        self.download_buffer.unmap();
+        download_buffer.unmap();
+
+        if let Some(timestamps_slice) = timestamps_slice {
+            let [start, end] =
+                *bytemuck::from_bytes::<[u64; 2]>(&timestamps_slice.get_mapped_range());
+            let nanoseconds =
+                end.saturating_sub(start) as f64 * self.queue.get_timestamp_period() as f64;
+            self.kernel_time = Some(std::time::Duration::from_nanos(nanoseconds as u64));
+            timestamps_slice.buffer().unmap();
+        }
+
+        if self.dump_state {
+            let mut state = [0u32; 16];
+            state.copy_from_slice(&result.x_results[..16]);
+            return Ok(GPUHasherResult::State(state));
+        }
+
+        // The GPU time of the dispatch, it can't start before the previous one is done
+        let start = self.last_completion.map_or(submit_time, |last_completion| {
+            last_completion.max(submit_time)
+        });
+        self.last_completion = Some(completion);
+        let elapsed = completion - start;
+
+        if let Some(max_dispatch_time) = self.max_dispatch_time {
+            self.update_submit_split(max_dispatch_time, elapsed);
+        }
+
+        let x_step = self.get_dispatch_size();
 
-        Ok(match result.get_result() {
+        if let Some(target_dispatch_time) = self.target_dispatch_time {
+            // The last dispatch of the X range partly runs past it, its time isn't representative
+            if x_offset as u64 + x_step <= x_size {
+                self.update_workgroups(target_dispatch_time, elapsed, x_size);
+            }
+        }
+
+        Ok(match result.get_results() {
             None => {
-                let x_step = wx as u64 * wy as u64 * wz as u64 * Self::LOCAL_WORKGROUP_SIZE as u64;
-                if x_offset as u64 + x_step > u32::MAX as u64 {
+                if x_offset as u64 + x_step >= x_size {
                     GPUHasherResult::End
                 } else {
-                    GPUHasherResult::Continue(x_step as u32)
+                    GPUHasherResult::Continue
                 }
             }
-            Some(x) => GPUHasherResult::Found(x),
+            Some((x_results, dropped)) => GPUHasherResult::Found(x_results, dropped),
         })
     }
+
+    /// Picks the number of submissions that fits the GPU time of the completed dispatch in the
+    /// budget, the split is raised as soon as a submission runs over and lowered only when well
+    /// under the budget
+    fn update_submit_split(
+        &mut self,
+        max_dispatch_time: std::time::Duration,
+        elapsed: std::time::Duration,
+    ) {
+        let needed = ((elapsed.as_secs_f64() / max_dispatch_time.as_secs_f64()).ceil() as u32)
+            .clamp(1, Self::MAX_SUBMIT_SPLIT);
+
+        if needed > self.submit_split || needed * 2 <= self.submit_split {
+            self.submit_split = needed;
+        }
+    }
+
+    /// Averages the GPU time of a workgroup over the completed dispatches and scales the workgroups
+    /// of the following ones to take the target time, at most doubling or halving them at once and
+    /// leaving them as they are within the tolerance so that the size settles. The workgroups are
+    /// laid out along x, never more than the X range needs. The pending dispatch covers the
+    /// previous size, it's dropped
+    fn update_workgroups(
+        &mut self,
+        target_dispatch_time: std::time::Duration,
+        elapsed: std::time::Duration,
+        x_size: u64,
+    ) {
+        let (wx, wy, wz) = self.workgroups;
+        let total = wx as u64 * wy as u64 * wz as u64;
+
+        let sample = elapsed.as_secs_f64() / total as f64;
+        let workgroup_time = match self.workgroup_time {
+            Some(average) => average + (sample - average) * Self::WORKGROUP_TIME_SMOOTHING,
+            None => sample,
+        };
+        self.workgroup_time = Some(workgroup_time);
+
+        let ratio = (target_dispatch_time.as_secs_f64()
+            / (workgroup_time * total as f64).max(f64::EPSILON))
+        .clamp(0.5, 2.0);
+
+        if (ratio - 1.0).abs() < Self::TARGET_DISPATCH_TOLERANCE {
+            return;
+        }
+
+        let max_workgroups = self.get_gpu_limits().max_compute_workgroups_per_dimension;
+        let max_total = (max_workgroups as u64 * Self::MAX_DISPATCH_SPLIT as u64)
+            .min(x_size.div_ceil(self.local_size as u64));
+
+        let scaled = ((total as f64 * ratio) as u64).clamp(1, max_total.max(1));
+
+        if scaled == total {
+            return;
+        }
+
+        let dispatches = Self::split_workgroups((scaled as u32, 1, 1), max_workgroups);
+        self.reserve_dispatches(dispatches.len());
+        self.workgroups = (scaled as u32, 1, 1);
+        self.dispatches = dispatches;
+        self.pending = None;
+    }
+
+    /// Grows the input buffer (without push constants) to hold the input of every dispatch
+    fn reserve_dispatches(&mut self, dispatch_count: usize) {
+        let Some(input_buffer) = &self.input_buffer else {
+            return;
+        };
+
+        let size = self.input_stride * dispatch_count as wgpu::BufferAddress;
+        if size <= input_buffer.size() {
+            return;
+        }
+
+        let input_buffer = self.device.create_buffer(&wgpu::wgt::BufferDescriptor {
+            label: Some("input buffer"),
+            size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            slot.bind_group = Self::create_bind_group(
+                &self.device,
+                &self.bind_group_layout,
+                i,
+                &slot.output_buffer,
+                &self.x_bits_buffer,
+                &self.state_buffer,
+                Some(&input_buffer),
+            );
+        }
+
+        self.input_buffer = Some(input_buffer);
+        self.pending = None;
+    }
+
+    fn submit(&self, slot: usize, x_bits: bool, input: GPUHasherInput) -> wgpu::SubmissionIndex {
+        let slot = &self.slots[slot];
+
+        self.queue.write_buffer(
+            &slot.output_buffer,
+            0,
+            bytemuck::bytes_of(&GPUHasherOutput::EMPTY),
+        );
+
+        // Over the dispatch time budget the work goes out in several submissions, the X ranges
+        // stay contiguous and the results accumulate in the same output buffer
+        // A state dump only needs the invocation at the X offset, the first of the first workgroup
+        let dispatches: &[(u32, u32, u32)] = if self.dump_state {
+            &[(1, 1, 1)]
+        } else {
+            &self.dispatches
+        };
+        let parts: Vec<(u32, u32, u32)> = dispatches
+            .iter()
+            .flat_map(|dispatch| Self::split_dispatch(*dispatch, self.submit_split))
+            .collect();
+        let chunks: Vec<&[(u32, u32, u32)]> = parts
+            .chunks(parts.len().div_ceil(self.submit_split as usize))
+            .collect();
+
+        let mut input = input;
+        let mut submission = None;
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let (first, last) = (i == 0, i == chunks.len() - 1);
+
+            let mut command_encoder =
+                self.device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("hasher dispatch"),
+                    });
+
+            {
+                let mut compute_pass =
+                    command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                        label: Some("hasher pass"),
+                        timestamp_writes: slot.timestamps.as_ref().filter(|_| first || last).map(
+                            |timestamps| wgpu::ComputePassTimestampWrites {
+                                query_set: &timestamps.query_set,
+                                beginning_of_pass_write_index: first.then_some(0),
+                                end_of_pass_write_index: last.then_some(1),
+                            },
+                        ),
+                    });
+                compute_pass.set_pipeline(if x_bits {
+                    &self.x_bits_pipeline
+                } else {
+                    &self.compute_pipeline
+                });
+                if self.input_buffer.is_none() {
+                    compute_pass.set_bind_group(0, &slot.bind_group, &[]);
+                }
+
+                for (j, (wx, wy, wz)) in chunk.iter().copied().enumerate() {
+                    match &self.input_buffer {
+                        None => compute_pass.set_push_constants(0, bytemuck::bytes_of(&input)),
+                        Some(input_buffer) => {
+                            let offset = j as wgpu::BufferAddress * self.input_stride;
+                            self.queue.write_buffer(
+                                input_buffer,
+                                offset,
+                                bytemuck::bytes_of(&input),
+                            );
+                            compute_pass.set_bind_group(0, &slot.bind_group, &[offset as u32]);
+                        }
+                    }
+                    compute_pass.dispatch_workgroups(wx, wy, wz);
+
+                    let size = wx as u64 * wy as u64 * wz as u64 * self.local_size as u64;
+                    input.x_offset = input.x_offset.wrapping_add(size as u32);
+                }
+            }
+
+            if last {
+                command_encoder.copy_buffer_to_buffer(
+                    &slot.output_buffer,
+                    0,
+                    &slot.download_buffer,
+                    0,
+                    GPUHasherOutput::SIZE,
+                );
+
+                if let Some(timestamps) = &slot.timestamps {
+                    command_encoder.resolve_query_set(
+                        &timestamps.query_set,
+                        0..2,
+                        &timestamps.resolve_buffer,
+                        0,
+                    );
+                    command_encoder.copy_buffer_to_buffer(
+                        &timestamps.resolve_buffer,
+                        0,
+                        &timestamps.download_buffer,
+                        0,
+                        GPUHasherTimestamps::SIZE,
+                    );
+                }
+            }
+
+            submission = Some(self.queue.submit([command_encoder.finish()]));
+        }
+
+        submission.expect("a dispatch always has workgroups")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKGROUPS: [(u32, u32, u32); 7] = [
+        (1, 1, 1),
+        (65535, 1, 1),
+        (65536, 1, 1),
+        (200000, 1, 1),
+        (1000, 70000, 1),
+        (70000, 3, 65537),
+        (7, 131071, 2),
+    ];
+
+    fn threads((wx, wy, wz): (u32, u32, u32)) -> u64 {
+        wx as u64 * wy as u64 * wz as u64
+    }
+
+    /// The dispatches fit in the limit of every dimension and together cover all the threads
+    #[test]
+    fn split_workgroups_covers_every_thread() {
+        for max in [255, 256, 65535] {
+            for workgroups in WORKGROUPS {
+                let dispatches = GPUHasher::split_workgroups(workgroups, max);
+
+                for (wx, wy, wz) in dispatches.iter().copied() {
+                    assert!(wx <= max && wy <= max && wz <= max, "{workgroups:?} {max}");
+                    assert!(wx > 0 && wy > 0 && wz > 0, "{workgroups:?} {max}");
+                }
+                let total: u64 = dispatches.iter().copied().map(threads).sum();
+                assert_eq!(total, threads(workgroups), "{workgroups:?} {max}");
+            }
+        }
+    }
+
+    /// Every dispatch splits into at most the parts asked for, the X offset of each part starts
+    /// where the previous one ended and the last one ends at the end of the dispatches
+    #[test]
+    fn split_dispatch_keeps_the_x_offsets_contiguous() {
+        let local_size = 64;
+
+        for parts in [1, 2, 3, 7, 100_000] {
+            for workgroups in WORKGROUPS {
+                let dispatches = GPUHasher::split_workgroups(workgroups, 65535);
+
+                let mut x_offset = 0u64;
+                for dispatch in dispatches {
+                    let (wx, wy, wz) = dispatch;
+                    let split = GPUHasher::split_dispatch(dispatch, parts);
+                    assert!(split.len() as u32 <= parts, "{dispatch:?} {parts}");
+
+                    let start = x_offset;
+                    for part in split {
+                        let (px, py, pz) = part;
+                        assert!(threads(part) > 0, "{dispatch:?} {parts}");
+                        assert!(px <= wx && py <= wy && pz <= wz, "{dispatch:?} {parts}");
+                        x_offset += threads(part) * local_size;
+                    }
+                    assert_eq!(x_offset - start, threads(dispatch) * local_size);
+                }
+                assert_eq!(x_offset, threads(workgroups) * local_size, "{workgroups:?}");
+            }
+        }
+    }
 }
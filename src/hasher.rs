@@ -1,147 +1,2115 @@
-use crate::{cpu, error::HasherError, gpu};
-use std::io::{Read, Seek, Write};
+use crate::{checkpoint, cpu, differential, error::HasherError, gpu, results, rom};
+use std::io::Write;
 
+/// How a call to `Hasher::compute_round` ended
 pub enum HasherResult {
-    Found(u32, u32),
+    /// Y, the verified X matches in ascending order and the number of dropped matches
+    Found(u32, Vec<u32>, u32),
+    /// The dispatches finished without a match, the round continues at the next X offset
+    Progress,
+    /// The round finished without a match, the next call searches the next Y
     Continue,
+    /// The GPU devices were lost and recreated, with the reason, the dispatch is retried next
+    DeviceReset(String),
+    /// The GPUs ran out of memory and their workgroups were halved, see `take_gpu_downsized`
+    Downsized,
+    /// The deadline passed before the dispatch
+    TimeLimit,
+    /// The interrupt flag was set before the dispatch
+    Interrupted,
+    /// Every Y was searched
     End,
 }
 
+/// The order the Y values of the range are searched in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum YOrder {
+    /// From the first Y value up
+    Sequential,
+    /// A permutation of the range from the seed, the same for every run with that seed
+    Random(u64),
+    /// Gray code order, consecutive Y values differ in a single bit
+    Gray,
+}
+
+impl YOrder {
+    /// Maps the position in the Y range to the offset of the Y value from the range start
+    pub(crate) fn get_y_offset(&self, position: u32, y_count: u64) -> u32 {
+        match self {
+            Self::Sequential => position,
+            Self::Random(seed) => Self::permute(position, y_count, *seed),
+            Self::Gray => Self::gray(position, y_count),
+        }
+    }
+
+    /// Gray code of the position, consecutive values differ by a single bit when the Y range is a
+    /// power of two (other sizes walk the cycle like the random order)
+    fn gray(position: u32, y_count: u64) -> u32 {
+        let mut value = position as u64;
+
+        loop {
+            value ^= value >> 1;
+            if value < y_count {
+                return value as u32;
+            }
+        }
+    }
+
+    /// Reversible mix of the position over the next power of two, walking the cycle until the
+    /// value lands in the range, so no table of visited values is needed
+    fn permute(position: u32, y_count: u64, seed: u64) -> u32 {
+        const ROUNDS: u64 = 4;
+
+        let bits = u64::BITS - (y_count - 1).leading_zeros();
+        let mask = (1u64 << bits) - 1;
+        let mut value = position as u64;
+
+        loop {
+            for round in 0..ROUNDS {
+                let key = seed.wrapping_add(round).wrapping_mul(0x9E3779B97F4A7C15);
+                value = (value ^ (key >> 32)) & mask;
+                value = value.wrapping_mul(0xD6E8FEB86659FD93) & mask;
+                value ^= value >> (bits / 2 + 1);
+            }
+            if value < y_count {
+                return value as u32;
+            }
+        }
+    }
+}
+
+/// The Y and X values searched for the target checksum
+pub struct HasherSearch {
+    /// The CIC seed the IPL3 is hashed with
+    pub seed: u8,
+    /// The 48-bit checksum to find
+    pub target_checksum: u64,
+    /// Bit offsets into the IPL3 (word index * 32 + 31 - bit) the Y values are stored in, most
+    /// significant bit first
+    pub y_bits: Vec<u32>,
+    /// Bit offsets into the IPL3 the X values are stored in, most significant bit first
+    pub x_bits: Vec<u32>,
+    /// The first Y value searched
+    pub y_init: u32,
+    /// The last Y value searched, the highest value of the Y bits when None
+    pub y_end: Option<u32>,
+    /// The order the Y range is searched in
+    pub y_order: YOrder,
+    /// The X offset the first Y round starts at
+    pub x_init: u32,
+}
+
+/// The devices the search runs on
+pub enum HasherDevices {
+    /// The CPU hasher alone
+    Cpu {
+        /// The threads a round is split over
+        threads: usize,
+    },
+    /// One GPU for every adapter selector, in that order
+    Gpu {
+        /// The adapters to select from, see `GPUHasher::list_gpu_adapters`
+        adapters: Vec<wgpu::Adapter>,
+        /// Picks the adapter of every GPU, each adapter can be used once
+        selectors: Vec<gpu::GPUAdapterSelector>,
+        /// The workgroups of every GPU, a single value applies to all of them
+        workgroups: Vec<gpu::GPUWorkgroupsSelector>,
+        /// The local size of every GPU, a single value applies to all of them
+        local_size: Vec<u32>,
+        /// The shader all the GPUs run
+        shader: gpu::GPUHasherShader,
+        /// Validation layers and device error messages
+        debug: bool,
+    },
+}
+
+impl HasherDevices {
+    /// Falls back to the CPU hasher with the threads when there's no GPU adapter to select from,
+    /// otherwise `Hasher::new` fails with `HasherError::GPUAdapterNoneFound`
+    pub fn with_cpu_fallback(self, threads: usize) -> Self {
+        match self {
+            Self::Gpu { adapters, .. } if adapters.is_empty() => Self::Cpu { threads },
+            devices => devices,
+        }
+    }
+}
+
+/// What a search covers, for a dry run
+pub struct HasherPlan {
+    /// The X values hashed per call of every GPU together (or the CPU round size)
+    pub dispatch_size: u64,
+    /// The first Y value
+    pub y_init: u32,
+    /// The last Y value
+    pub y_end: u32,
+    /// The X values of every Y
+    pub x_size: u64,
+    /// The hashes left to the end of the Y range
+    pub total_hashes: u128,
+    /// The Y bit offsets into the IPL3
+    pub y_bits: Vec<u32>,
+    /// The X bit offsets into the IPL3
+    pub x_bits: Vec<u32>,
+    /// The Y bits set in the IPL3 as read, they're overwritten by the search
+    pub y_bits_set: usize,
+    /// The X bits set in the IPL3 as read, they're overwritten by the search
+    pub x_bits_set: usize,
+    /// The IPL3 words holding both Y and X bits
+    pub shared_words: Vec<u32>,
+}
+
+/// The states of a Y and X value on the CPU and on every GPU, for `Hasher::dump_state`
+pub struct HasherStateDump {
+    /// The CPU states, final mix buffers and checksum
+    pub cpu: cpu::CPUHasherDump,
+    /// The state before the final mix on every GPU, equal to the CPU final state when the shader
+    /// matches the CPU
+    pub gpus: Vec<[u32; 16]>,
+}
+
+/// How far the search is
+pub struct HasherStats {
+    /// The Y values searched since the start position
+    pub y_done: u64,
+    /// The Y values from the start position to the end of the range
+    pub y_total: u64,
+    /// The smoothed hash rate
+    pub hashes_per_second: f64,
+    /// The hashes left to the end of the Y range
+    pub remaining_hashes: u64,
+    /// The time the search waited for the Y round states on the CPU between the rounds, most of
+    /// it is hidden behind the GPU dispatches of the previous round
+    pub y_round_time: std::time::Duration,
+}
+
+impl HasherStats {
+    /// The time to the end of the Y range at the current rate, None before the first dispatch
+    pub fn get_eta(&self) -> Option<std::time::Duration> {
+        if self.hashes_per_second > 0.0 {
+            Some(std::time::Duration::from_secs_f64(
+                self.remaining_hashes as f64 / self.hashes_per_second,
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// The result of a benchmark run
+pub struct HasherBenchmark {
+    /// The workgroups and local size of every GPU
+    pub gpu_workgroups: Vec<((u32, u32, u32), u32)>,
+    /// The X values hashed
+    pub hashes: u64,
+    /// The dispatches timed, of all the GPUs
+    pub dispatches: u32,
+    /// The wall time of the timed dispatches
+    pub elapsed: std::time::Duration,
+    /// The target checksum was compiled into the shaders
+    pub specialized: bool,
+    /// The early exit period the shaders were compiled with
+    pub early_exit_period: u32,
+    /// Every dispatch found a match at its first X value
+    pub early_match: bool,
+}
+
+impl HasherBenchmark {
+    /// The hashes per second, 0 when no time was measured
+    pub fn get_hashes_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.hashes as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// The average wall time of a single timed dispatch, submission and readback included
+    pub fn get_dispatch_latency(&self) -> std::time::Duration {
+        self.elapsed / self.dispatches.max(1)
+    }
+}
+
+/// Searches the X values of a Y round from an offset, Hasher drives the Y and X orchestration,
+/// the verification and the checkpoints the same way for every backend
+trait XSearchBackend {
+    fn x_round(
+        &mut self,
+        target_checksum: u64,
+        x_offset: u32,
+        round_state: &std::sync::Arc<RoundState>,
+        x_bits: &[u32],
+    ) -> Result<XRoundResult, HasherError>;
+}
+
+/// The X search on a thread pool of the CPU threads
+struct CPUBackend {
+    pool: rayon::ThreadPool,
+    ipl3_words: u32,
+}
+
+impl XSearchBackend for CPUBackend {
+    fn x_round(
+        &mut self,
+        target_checksum: u64,
+        x_offset: u32,
+        round_state: &std::sync::Arc<RoundState>,
+        x_bits: &[u32],
+    ) -> Result<XRoundResult, HasherError> {
+        let result = match round_state.as_ref() {
+            RoundState::XWord(y_offset, state) => cpu::CPUHasher::x_round(
+                target_checksum,
+                *y_offset,
+                x_offset,
+                *state,
+                self.ipl3_words,
+                &self.pool,
+            ),
+            // The CPU searches a single Y per round
+            RoundState::XBits(rounds) => cpu::CPUHasher::x_bits_round(
+                target_checksum,
+                x_offset,
+                rounds[0].0,
+                &rounds[0].1,
+                x_bits,
+                &self.pool,
+            ),
+        };
+        Ok(match result {
+            cpu::CPUHasherResult::Found(x) => XRoundResult::Found(vec![x], 0),
+            cpu::CPUHasherResult::Continue(x_step) => XRoundResult::Continue(x_step),
+            cpu::CPUHasherResult::End => XRoundResult::End,
+        })
+    }
+}
+
+/// A Y round shared with the GPU workers, which take its X sub-ranges from the counter
+struct GPURound {
+    target_checksum: u64,
+    round_state: std::sync::Arc<RoundState>,
+    x_bits: Vec<u32>,
+    x_size: u64,
+    /// The start of the next X sub-range to take
+    x_next: std::sync::atomic::AtomicU64,
+    /// Set on a match or an error (and by `GPUWorkers::stop`), no more sub-ranges are taken after
+    stop: std::sync::atomic::AtomicBool,
+}
+
+impl GPURound {
+    /// The next X sub-range of the dispatch size, None once the round is taken or stopped
+    fn take(&self, dispatch_size: u64) -> Option<std::ops::Range<u64>> {
+        if self.stop.load(std::sync::atomic::Ordering::SeqCst) {
+            return None;
+        }
+
+        let x_offset = self
+            .x_next
+            .fetch_add(dispatch_size, std::sync::atomic::Ordering::SeqCst);
+        (x_offset < self.x_size).then(|| x_offset..(x_offset + dispatch_size).min(self.x_size))
+    }
+}
+
+enum GPUReport {
+    /// The result of a dispatch over the X sub-range, with its kernel time (and size) and the size
+    /// of the next dispatch of the GPU
+    Searched {
+        index: usize,
+        x_range: std::ops::Range<u64>,
+        result: Result<gpu::GPUHasherResult, HasherError>,
+        kernel_time: Option<(std::time::Duration, u64)>,
+        dispatch_size: u64,
+    },
+    /// The worker is done with the round
+    Idle,
+}
+
+/// A long-lived worker thread per GPU, each takes the next X sub-range of the round for its own
+/// dispatch size as soon as it's done with the last one, so GPUs of different speeds never wait
+/// for each other. The workers exit once this is dropped
+struct GPUWorkers {
+    gpus: Vec<std::sync::Arc<std::sync::Mutex<gpu::GPUHasher>>>,
+    rounds: Vec<std::sync::mpsc::Sender<std::sync::Arc<GPURound>>>,
+    reports: std::sync::mpsc::Receiver<GPUReport>,
+    /// The round being searched, until every worker is done with it
+    round: Option<std::sync::Arc<GPURound>>,
+    /// The workers still searching the round
+    active: usize,
+    /// Every X offset of the round below it is searched
+    x_done: u64,
+    /// The sub-ranges searched past x_done, their end by their start
+    searched: std::collections::BTreeMap<u64, u64>,
+    /// The dispatch size of every GPU, from the reports while a round is searched
+    dispatch_sizes: Vec<u64>,
+    kernel_times: Vec<Option<(std::time::Duration, u64)>>,
+}
+
+impl GPUWorkers {
+    fn spawn(gpus: Vec<gpu::GPUHasher>) -> Self {
+        let (report_sender, reports) = std::sync::mpsc::channel();
+
+        let gpus: Vec<_> = gpus
+            .into_iter()
+            .map(|gpu| std::sync::Arc::new(std::sync::Mutex::new(gpu)))
+            .collect();
+
+        let rounds = gpus
+            .iter()
+            .enumerate()
+            .map(|(index, gpu)| {
+                let (round_sender, round_receiver) =
+                    std::sync::mpsc::channel::<std::sync::Arc<GPURound>>();
+                let gpu = gpu.clone();
+                let reports = report_sender.clone();
+
+                std::thread::spawn(move || {
+                    for round in round_receiver {
+                        Self::search(index, &gpu, &round, &reports);
+                        if reports.send(GPUReport::Idle).is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                round_sender
+            })
+            .collect();
+
+        Self {
+            dispatch_sizes: vec![0; gpus.len()],
+            kernel_times: vec![None; gpus.len()],
+            gpus,
+            rounds,
+            reports,
+            round: None,
+            active: 0,
+            x_done: 0,
+            searched: std::collections::BTreeMap::new(),
+        }
+    }
+
+    /// Searches the sub-ranges of the round on the GPU, taking the next one before submitting the
+    /// current one, so that it's submitted ahead. A sub-range taken is always searched in full, a
+    /// match or an error stops the round from being taken further. The worker itself stays and waits
+    /// for the next round, so that it searches on the GPU reset in place after a device loss
+    fn search(
+        index: usize,
+        gpu: &std::sync::Mutex<gpu::GPUHasher>,
+        round: &GPURound,
+        reports: &std::sync::mpsc::Sender<GPUReport>,
+    ) {
+        let dispatch_size = gpu.lock().unwrap().get_dispatch_size();
+        let mut x_range = round.take(dispatch_size);
+
+        while let Some(current) = x_range {
+            let mut gpu = gpu.lock().unwrap();
+            let dispatch_size = gpu.get_dispatch_size();
+
+            // The dispatch size follows --target-dispatch-ms, the rest of a sub-range taken at a
+            // larger size is searched next
+            let x_end = (current.start + dispatch_size).min(current.end);
+            let next = if x_end < current.end {
+                Some(x_end..current.end)
+            } else {
+                round.take(dispatch_size)
+            };
+            let x_next = next.as_ref().map(|next| next.start);
+
+            let result = match round.round_state.as_ref() {
+                RoundState::XWord(y_offset, state) => gpu.x_round(
+                    round.target_checksum,
+                    *y_offset,
+                    current.start as u32,
+                    x_next,
+                    *state,
+                ),
+                RoundState::XBits(rounds) => gpu.x_bits_round(
+                    round.target_checksum,
+                    current.start as u32,
+                    x_next,
+                    rounds,
+                    &round.x_bits,
+                ),
+            };
+            let kernel_time = gpu
+                .take_kernel_time()
+                .map(|kernel_time| (kernel_time, dispatch_size));
+            let next_dispatch_size = gpu.get_dispatch_size();
+            drop(gpu);
+
+            x_range = match &result {
+                Ok(gpu::GPUHasherResult::Found(..)) => {
+                    round.stop.store(true, std::sync::atomic::Ordering::SeqCst);
+                    next
+                }
+                Ok(_) => next,
+                Err(_) => {
+                    round.stop.store(true, std::sync::atomic::Ordering::SeqCst);
+                    None
+                }
+            };
+
+            let report = GPUReport::Searched {
+                index,
+                x_range: current.start..x_end,
+                result,
+                kernel_time,
+                dispatch_size: next_dispatch_size,
+            };
+            if reports.send(report).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// The GPUs, each once its worker is done with the current dispatch
+    fn lock(&self) -> Vec<std::sync::MutexGuard<'_, gpu::GPUHasher>> {
+        self.gpus.iter().map(|gpu| gpu.lock().unwrap()).collect()
+    }
+
+    /// The X values hashed per dispatch of every GPU together
+    fn get_dispatch_size(&self) -> u64 {
+        match self.round {
+            Some(_) => self.dispatch_sizes.iter().sum(),
+            None => self.lock().iter().map(|gpu| gpu.get_dispatch_size()).sum(),
+        }
+    }
+
+    /// The kernel time and the dispatch size of the last dispatch of each GPU, None for a GPU that
+    /// wasn't timed or didn't dispatch since the last call
+    fn take_kernel_times(&mut self) -> Vec<Option<(std::time::Duration, u64)>> {
+        self.kernel_times.iter_mut().map(Option::take).collect()
+    }
+
+    fn start(
+        &mut self,
+        target_checksum: u64,
+        x_offset: u32,
+        round_state: &std::sync::Arc<RoundState>,
+        x_bits: &[u32],
+    ) {
+        let x_size: u64 = match round_state.as_ref() {
+            RoundState::XWord(..) => 1 << 32,
+            RoundState::XBits(rounds) => (rounds.len() as u64) << x_bits.len(),
+        };
+
+        let dispatch_sizes = self
+            .lock()
+            .iter()
+            .map(|gpu| gpu.get_dispatch_size())
+            .collect();
+        self.dispatch_sizes = dispatch_sizes;
+
+        let round = std::sync::Arc::new(GPURound {
+            target_checksum,
+            round_state: round_state.clone(),
+            x_bits: x_bits.to_vec(),
+            x_size,
+            x_next: std::sync::atomic::AtomicU64::new(x_offset as u64),
+            stop: std::sync::atomic::AtomicBool::new(false),
+        });
+
+        self.active = self
+            .rounds
+            .iter()
+            .filter(|rounds| rounds.send(round.clone()).is_ok())
+            .count();
+        self.x_done = x_offset as u64;
+        self.searched.clear();
+        self.round = Some(round);
+    }
+
+    /// The next sub-range searched in the round and its result, None once every worker is done
+    /// with the round
+    fn next_report(
+        &mut self,
+    ) -> Option<(
+        std::ops::Range<u64>,
+        Result<gpu::GPUHasherResult, HasherError>,
+    )> {
+        while self.round.is_some() && self.active > 0 {
+            match self.reports.recv() {
+                Ok(GPUReport::Searched {
+                    index,
+                    x_range,
+                    result,
+                    kernel_time,
+                    dispatch_size,
+                }) => {
+                    self.kernel_times[index] = kernel_time;
+                    self.dispatch_sizes[index] = dispatch_size;
+                    return Some((x_range, result));
+                }
+                Ok(GPUReport::Idle) => self.active -= 1,
+                Err(_) => break,
+            }
+        }
+
+        self.round = None;
+        None
+    }
+
+    /// No more sub-ranges of the round are taken, the workers finish the ones they have (including
+    /// the dispatches submitted ahead) and their results are dropped
+    fn stop(&mut self) {
+        if let Some(round) = &self.round {
+            round.stop.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+        while self.next_report().is_some() {}
+    }
+}
+
+impl Drop for GPUWorkers {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl XSearchBackend for GPUWorkers {
+    /// The workers keep searching the round between the calls, a call continuing from where the
+    /// last one left off waits for the searched X values to reach past its offset, anything else
+    /// stops the round and starts a new one from the offset. A match stops the round, the call
+    /// returns once every sub-range taken is searched with the matches of all of them. The matches
+    /// are the X values themselves, so they never depend on which GPU or submission found them
+    fn x_round(
+        &mut self,
+        target_checksum: u64,
+        x_offset: u32,
+        round_state: &std::sync::Arc<RoundState>,
+        x_bits: &[u32],
+    ) -> Result<XRoundResult, HasherError> {
+        let continued = self.round.as_ref().is_some_and(|round| {
+            std::sync::Arc::ptr_eq(&round.round_state, round_state)
+                && round.target_checksum == target_checksum
+                && round.x_bits == x_bits
+                && self.x_done == x_offset as u64
+        });
+
+        if !continued {
+            self.stop();
+            self.start(target_checksum, x_offset, round_state, x_bits);
+        }
+
+        let mut found: Option<(Vec<u32>, u32)> = None;
+        let mut error = None;
+
+        while let Some((x_range, result)) = self.next_report() {
+            match result {
+                Ok(gpu::GPUHasherResult::Found(x_results, dropped)) => {
+                    let (found_x_results, found_dropped) = found.get_or_insert_default();
+                    found_x_results.extend(x_results);
+                    *found_dropped += dropped;
+                }
+                Ok(_) if found.is_none() && error.is_none() => {
+                    self.searched.insert(x_range.start, x_range.end);
+                    while let Some(x_end) = self.searched.remove(&self.x_done) {
+                        self.x_done = x_end;
+                    }
+
+                    // The round ends once every worker is done with it
+                    let x_size = self.round.as_ref().map_or(0, |round| round.x_size);
+                    if self.x_done > x_offset as u64 && self.x_done < x_size {
+                        return Ok(XRoundResult::Continue(
+                            (self.x_done - x_offset as u64) as u32,
+                        ));
+                    }
+                }
+                Ok(_) => {}
+                Err(round_error) => {
+                    error.get_or_insert(round_error);
+                }
+            }
+        }
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        Ok(match found {
+            Some((mut x_results, dropped)) => {
+                x_results.sort_unstable();
+                XRoundResult::Found(x_results, dropped)
+            }
+            None => XRoundResult::End,
+        })
+    }
+}
+
+enum Backend {
+    Gpu(GPUWorkers),
+    Cpu(CPUBackend),
+}
+
+impl Backend {
+    fn get_search(&mut self) -> &mut dyn XSearchBackend {
+        match self {
+            Self::Gpu(gpus) => gpus,
+            Self::Cpu(cpu) => cpu,
+        }
+    }
+
+    /// Stops the round of the GPU workers, see `GPUWorkers::stop`
+    fn stop(&mut self) {
+        if let Self::Gpu(gpus) = self {
+            gpus.stop();
+        }
+    }
+}
+
+enum RoundState {
+    XWord(u32, [u32; 16]),
+    /// The initial state and IPL3 words of every Y searched in the round
+    XBits(Vec<gpu::GPUXBitsRound>),
+}
+
+/// Computes the round states of the next rounds on a worker thread while the GPUs search the
+/// current one, the worker exits once this is dropped
+struct RoundPrefetch {
+    requests: std::sync::mpsc::Sender<Vec<u32>>,
+    round_states: std::sync::mpsc::Receiver<RoundState>,
+    /// The Y values of the round state being computed, if any
+    pending: Option<Vec<u32>>,
+}
+
+impl RoundPrefetch {
+    /// Starts the worker with its own copy of the CPU hasher, which must be replaced after the CPU
+    /// hasher changes (the seed for one)
+    fn spawn(cpu: cpu::CPUHasher, y_bits: Vec<u32>, x_bits: Vec<u32>, y_order: YOrder) -> Self {
+        let (requests, request_receiver) = std::sync::mpsc::channel::<Vec<u32>>();
+        let (round_state_sender, round_states) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            let mut y_round_cache = None;
+            for ys in request_receiver {
+                let round_state =
+                    Self::compute(&cpu, &y_bits, &x_bits, y_order, &ys, &mut y_round_cache);
+                if round_state_sender.send(round_state).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests,
+            round_states,
+            pending: None,
+        }
+    }
+
+    /// The round state of the Y values, a single one with the whole X word
+    fn compute(
+        cpu: &cpu::CPUHasher,
+        y_bits: &[u32],
+        x_bits: &[u32],
+        y_order: YOrder,
+        ys: &[u32],
+        y_round_cache: &mut Option<cpu::YRoundCache>,
+    ) -> RoundState {
+        if cpu.is_x_word(x_bits) {
+            let (y_offset, state) = match y_order {
+                YOrder::Gray => cpu.y_round_cached(y_bits, ys[0], y_round_cache),
+                _ => cpu.y_round(y_bits, ys[0]),
+            };
+            RoundState::XWord(y_offset, state)
+        } else {
+            let rounds = ys
+                .iter()
+                .map(|&y| cpu.y_round_x_bits(y_bits, y, x_bits))
+                .collect();
+            RoundState::XBits(rounds)
+        }
+    }
+
+    /// Starts computing the round state of the Y values, unless it already is
+    fn request(&mut self, ys: Vec<u32>) {
+        if self.pending.as_ref() == Some(&ys) {
+            return;
+        }
+
+        self.discard();
+        if self.requests.send(ys.clone()).is_ok() {
+            self.pending = Some(ys);
+        }
+    }
+
+    /// The prefetched round state of the Y values, waiting for it to finish. None when another one
+    /// was prefetched (the search was moved elsewhere), which is discarded
+    fn take(&mut self, ys: &[u32]) -> Option<RoundState> {
+        let pending = self.pending.take()?;
+        let round_state = self.round_states.recv().ok()?;
+
+        (pending == ys).then_some(round_state)
+    }
+
+    fn discard(&mut self) {
+        if self.pending.take().is_some() {
+            self.round_states.recv().ok();
+        }
+    }
+}
+
+enum XRoundResult {
+    Found(Vec<u32>, u32),
+    Continue(u32),
+    End,
+}
+
+/// The search for the Y and X values that make the IPL3 hash to the target checksum. Every
+/// round searches the X values of a Y (or of a batch of Y values on the GPUs), `compute_round`
+/// runs the dispatches of a round a call at a time
 pub struct Hasher {
     cpu: cpu::CPUHasher,
-    gpu: gpu::GPUHasher,
+    ipl3_sha256: String,
+    backend: Backend,
+    gpu_adapter_list: Vec<wgpu::AdapterInfo>,
+    seed: u8,
     target_checksum: u64,
     y_bits: Vec<u32>,
+    x_bits: Vec<u32>,
     y: u32,
+    y_first: u32,
+    y_end: u32,
+    y_order: YOrder,
+    y_position: u32,
+    y_start_position: u32,
+    y_finished: bool,
+    y_batch: u32,
+    x_stride: u32,
+    /// The X offset in the consecutive X ranges of every Y of the round
+    x_offset: u32,
+    round_hashes: u64,
+    total_hashes: u64,
+    round_state: Option<std::sync::Arc<RoundState>>,
+    y_round_cache: Option<cpu::YRoundCache>,
+    round_prefetch: Option<RoundPrefetch>,
+    /// The time `compute_round` waited for the round states of new rounds
+    y_round_time: std::time::Duration,
+    /// When the GPUs started on the X values the next call reports, they keep searching between
+    /// the calls
+    x_round_start: Option<std::time::Instant>,
+    hashes_per_second: f64,
+    deadline: Option<std::time::Instant>,
+    interrupt: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+    verify: bool,
+    verify_threads: usize,
+    max_device_resets: u32,
+    device_resets: u32,
 }
 
 impl Hasher {
+    const BENCHMARK_CHECKSUM: u64 = u64::MAX;
+
+    /// Prepares the search of the IPL3 (the raw big endian words, see `read_ipl3`) on the devices,
+    /// the GPUs are created and their pipelines compiled here
     pub fn new(
-        path: std::path::PathBuf,
-        gpu_adapter_id: usize,
-        workgroups: (u32, u32, u32),
-        shader: gpu::GPUHasherShader,
-        seed: u8,
-        target_checksum: u64,
-        y_bits: Vec<u32>,
-        y_init: u32,
+        ipl3: &[u8],
+        search: HasherSearch,
+        devices: HasherDevices,
     ) -> Result<Self, HasherError> {
-        let ipl3 = Self::load_ipl3(path)?;
+        let HasherSearch {
+            seed,
+            target_checksum,
+            y_bits,
+            x_bits,
+            y_init,
+            y_end,
+            y_order,
+            x_init,
+        } = search;
+
+        rom::check_bits(ipl3.len() / 4, &y_bits, &x_bits)?;
+
+        let mut cpu = cpu::CPUHasher::new(ipl3, seed);
+        cpu.set_y_bits(&y_bits);
+        let ipl3_sha256 = results::ResultEntry::get_ipl3_sha256(ipl3);
+
+        let (backend, gpu_adapter_list) = match devices {
+            HasherDevices::Cpu { threads } => (
+                Backend::Cpu(CPUBackend {
+                    pool: rayon::ThreadPoolBuilder::new()
+                        .num_threads(threads)
+                        .build()?,
+                    ipl3_words: cpu.get_ipl3_words(),
+                }),
+                vec![],
+            ),
+            HasherDevices::Gpu {
+                adapters,
+                selectors,
+                workgroups,
+                local_size,
+                shader,
+                debug,
+            } => {
+                if adapters.is_empty() {
+                    return Err(HasherError::GPUAdapterNoneFound);
+                }
+
+                let mut adapter_ids = vec![];
+                let mut gpus = vec![];
+
+                for (i, selector) in selectors.iter().enumerate() {
+                    let adapter_id = gpu::GPUHasher::select_gpu_adapter(&adapters, selector)?;
+                    if adapter_ids.contains(&adapter_id) {
+                        return Err(HasherError::GPUAdapterDuplicate(adapter_id));
+                    }
+                    adapter_ids.push(adapter_id);
+
+                    // A single workgroups or local size value applies to all GPUs
+                    let mut gpu = gpu::GPUHasher::new(
+                        adapters[adapter_id].clone(),
+                        shader.clone(),
+                        workgroups[i.min(workgroups.len() - 1)],
+                        local_size[i.min(local_size.len() - 1)],
+                        debug,
+                    )?;
+                    gpu.set_ipl3_words(cpu.get_ipl3_words());
+                    gpus.push(gpu);
+                }
 
-        let cpu = cpu::CPUHasher::new(&ipl3, seed);
+                (
+                    Backend::Gpu(GPUWorkers::spawn(gpus)),
+                    adapters.iter().map(|adapter| adapter.get_info()).collect(),
+                )
+            }
+        };
 
-        let adapters = gpu::GPUHasher::list_gpu_adapters();
-        let adapter = adapters
-            .get(gpu_adapter_id)
-            .ok_or(HasherError::GPUAdapterOutOfBounds)?;
-        let gpu = gpu::GPUHasher::new(adapter.clone(), shader, workgroups)?;
+        let y_end = y_end.unwrap_or(Self::get_y_max(&y_bits));
 
-        Ok(Self {
+        let mut hasher = Self {
             cpu,
-            gpu,
+            ipl3_sha256,
+            backend,
+            gpu_adapter_list,
+            seed,
             target_checksum,
             y_bits,
+            x_bits,
             y: y_init,
-        })
+            y_first: y_init,
+            y_end,
+            y_order,
+            y_position: 0,
+            y_start_position: 0,
+            y_finished: y_init > y_end,
+            y_batch: 1,
+            x_stride: 1,
+            x_offset: 0,
+            round_hashes: 0,
+            total_hashes: 0,
+            round_state: None,
+            y_round_cache: None,
+            round_prefetch: None,
+            y_round_time: std::time::Duration::ZERO,
+            x_round_start: None,
+            hashes_per_second: 0.0,
+            deadline: None,
+            interrupt: None,
+            verify: true,
+            verify_threads: 1,
+            max_device_resets: 0,
+            device_resets: 0,
+        };
+
+        hasher.set_y_position(0);
+        hasher.set_x_offset(x_init);
+
+        Ok(hasher)
+    }
+
+    /// The checksum of the IPL3 with the Y and X values stored in their bits
+    pub fn verify_ipl3(
+        ipl3: &[u8],
+        seed: u8,
+        y_bits: &[u32],
+        x_bits: &[u32],
+        y: u32,
+        x: u32,
+    ) -> Result<u64, HasherError> {
+        rom::check_bits(ipl3.len() / 4, y_bits, x_bits)?;
+
+        Ok(cpu::CPUHasher::new(ipl3, seed).verify(y_bits, y, x_bits, x))
+    }
+
+    /// The checksum of the IPL3 as it is
+    pub fn compute_ipl3(ipl3: &[u8], seed: u8) -> u64 {
+        cpu::CPUHasher::new(ipl3, seed).checksum()
     }
 
-    fn load_ipl3(path: std::path::PathBuf) -> Result<[u8; 4032], HasherError> {
-        let mut f = std::fs::File::open(path)?;
+    /// The seeds the IPL3 as it is hashes to the target checksum with
+    pub fn seed_scan_ipl3(ipl3: &[u8], target_checksum: u64) -> Vec<u8> {
+        let mut cpu = cpu::CPUHasher::new(ipl3, 0);
 
-        let mut ipl3 = [0u8; 4032];
+        (0..=u8::MAX)
+            .filter(|seed| {
+                cpu.set_seed(*seed);
+                cpu.checksum() == target_checksum
+            })
+            .collect()
+    }
 
-        f.seek(std::io::SeekFrom::Start(64))?;
-        f.read_exact(&mut ipl3)?;
+    /// Reads the IPL3 from the ROM file (or stdin for `rom::STDIN_PATH`) with the layout
+    pub fn read_ipl3(
+        path: &std::path::Path,
+        layout: rom::RomLayout,
+    ) -> Result<Vec<u8>, HasherError> {
+        if rom::is_stdin(path) {
+            Self::load_ipl3(std::io::stdin().lock(), layout)
+        } else {
+            Self::load_ipl3(std::fs::File::open(path)?, layout)
+        }
+    }
 
-        Ok(ipl3)
+    /// Reads the IPL3 from a ROM stream with the layout, without seeking
+    pub fn load_ipl3(
+        f: impl std::io::Read,
+        layout: rom::RomLayout,
+    ) -> Result<Vec<u8>, HasherError> {
+        rom::RomFormat::read_ipl3_stream(f, layout)
     }
 
+    /// Stores the Y and X values in the bits of the IPL3 in the ROM file, in place
     pub fn sign_rom(
-        path: std::path::PathBuf,
-        y_bits: Vec<u32>,
+        path: &std::path::Path,
+        layout: rom::RomLayout,
+        y_bits: &[u32],
+        x_bits: &[u32],
         y: u32,
         x: u32,
     ) -> Result<(), HasherError> {
+        rom::check_bits(layout.ipl3_words, y_bits, x_bits)?;
         let mut f = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
             .open(path)?;
 
-        for (i, offset) in y_bits.iter().enumerate() {
-            let index = offset / 8;
-            let bit = 7 - (offset % 8);
-            let shift = y_bits.len() - 1;
-            let value = ((y >> (shift - i)) & (1 << 0)) as u8;
+        layout.write_bits(&mut f, &[(y_bits, y), (x_bits, x)])?;
 
-            let mut byte = [0u8];
+        f.flush()?;
 
-            f.seek(std::io::SeekFrom::Start(index as u64 + 64))?;
-            f.read_exact(&mut byte)?;
+        Ok(())
+    }
 
-            byte[0] &= !(1 << bit);
-            byte[0] |= value << bit;
+    /// Reads the IPL3 back from the signed ROM file with the same layout and checks that it hashes
+    /// to the target checksum as it is, returns the checksum
+    pub fn verify_rom(
+        path: &std::path::Path,
+        layout: rom::RomLayout,
+        seed: u8,
+        target_checksum: u64,
+    ) -> Result<u64, HasherError> {
+        let ipl3 = Self::read_ipl3(path, layout)?;
+        let checksum = Self::compute_ipl3(&ipl3, seed);
 
-            f.seek(std::io::SeekFrom::Current(-1))?;
-            f.write_all(&mut byte)?;
+        if checksum != target_checksum {
+            return Err(HasherError::SignedRomMismatch(
+                path.to_path_buf(),
+                checksum,
+                target_checksum,
+            ));
         }
 
-        f.seek(std::io::SeekFrom::Start(4092))?;
-        f.write_all(&mut x.to_be_bytes().to_vec())?;
+        Ok(checksum)
+    }
 
-        f.flush()?;
+    /// The IPL3 as read already hashes to the target checksum
+    pub fn is_rom_matching(&self) -> bool {
+        self.cpu.checksum() == self.target_checksum
+    }
 
-        Ok(())
+    /// Searches for the target checksum with another CIC seed, from the current round
+    pub fn set_seed(&mut self, seed: u8) {
+        self.seed = seed;
+        self.cpu.set_seed(seed);
+        self.round_state = None;
+        self.y_round_cache = None;
+        self.round_prefetch = None;
     }
 
+    /// The Y the search is at
     pub fn get_y(&self) -> u32 {
-        self.y
+        self.get_round_position().0
     }
 
-    fn is_y_finished(&self) -> bool {
-        (self.y as u64) > ((1u64 << self.y_bits.len()) - 1)
+    /// The Y the search started from, the first Y value of the range in the random and Gray orders
+    pub fn get_y_start(&self) -> u32 {
+        match self.y_order {
+            YOrder::Sequential => self.y_first + self.y_start_position,
+            YOrder::Random(_) | YOrder::Gray => self.y_first,
+        }
     }
 
-    pub fn get_gpu_info(&self) -> wgpu::AdapterInfo {
-        self.gpu.get_gpu_info()
+    fn get_y_count(&self) -> u64 {
+        (self.y_end as u64 + 1).saturating_sub(self.y_first as u64)
     }
 
-    pub fn compute_round(&mut self) -> Result<HasherResult, HasherError> {
-        if self.is_y_finished() {
-            return Ok(HasherResult::End);
+    /// Moves the search to the position in the Y order, at the start of its round
+    pub fn set_y_position(&mut self, position: u32) {
+        let y_count = self.get_y_count();
+        self.y_position = position;
+        self.y_start_position = position;
+        self.y_finished = position as u64 >= y_count;
+        if !self.y_finished {
+            self.y = self.y_first + self.y_order.get_y_offset(position, y_count);
         }
+        self.round_state = None;
+        self.x_offset = 0;
+    }
 
-        let (y_offset, state) = self.cpu.y_round(self.y_bits.clone(), self.y);
+    /// The X offset the search is at in the round of the current Y
+    pub fn get_x_offset(&self) -> u32 {
+        self.get_round_position().2
+    }
 
-        let mut x_offset = 0;
+    /// Moves the search to the X offset in the current round
+    pub fn set_x_offset(&mut self, x_offset: u32) {
+        self.x_offset = x_offset;
+    }
 
-        loop {
-            let result = self
-                .gpu
-                .x_round(self.target_checksum, y_offset, x_offset, state)?;
+    /// Searches the X bits of this many consecutive Y values (in the Y order) in every GPU round,
+    /// so that small X spaces still fill the dispatches. Limited to what fits in 32 bits of X
+    pub fn set_y_batch(&mut self, y_batch: u32) {
+        self.y_batch = y_batch.max(1);
+        self.round_state = None;
+    }
 
-            match result {
-                gpu::GPUHasherResult::Found(x) => {
-                    let verify_checksum = self.cpu.verify(self.y_bits.clone(), self.y, x);
-                    if verify_checksum != self.target_checksum {
-                        return Err(HasherError::ChecksumVerifyError(self.y, x, verify_checksum));
-                    }
-                    return Ok(HasherResult::Found(self.y, x));
-                }
-                gpu::GPUHasherResult::Continue(x_step) => {
-                    x_offset += x_step;
-                }
-                gpu::GPUHasherResult::End => {
-                    break;
-                }
+    /// Makes the GPUs hash the X offsets multiplied by the odd stride (in the X space), so that the
+    /// X offsets reported, saved and resumed from count in that order. The CPU backend searches X
+    /// in order and ignores it
+    pub fn set_x_stride(&mut self, x_stride: u32) {
+        if let Backend::Gpu(gpus) = &mut self.backend {
+            self.x_stride = x_stride;
+            for gpu in gpus.lock().iter_mut() {
+                gpu.set_x_stride(x_stride);
             }
         }
+    }
 
-        if self.is_y_finished() {
-            return Ok(HasherResult::End);
+    /// The X value hashed at the X offset of a round
+    fn get_x_at(&self, x_offset: u32) -> u32 {
+        (x_offset.wrapping_mul(self.x_stride) as u64 & (self.get_x_size() - 1)) as u32
+    }
+
+    /// The X offset of a round the X value is hashed at, multiplying by the inverse of the stride
+    fn get_x_offset_of(&self, x: u32) -> u32 {
+        // Newton's iteration doubles the correct low bits of the inverse, an odd value is its own
+        // inverse in the low 3 bits
+        let mut inverse = self.x_stride;
+        for _ in 0..4 {
+            inverse = inverse.wrapping_mul(2u32.wrapping_sub(self.x_stride.wrapping_mul(inverse)));
+        }
+        (x.wrapping_mul(inverse) as u64 & (self.get_x_size() - 1)) as u32
+    }
+
+    /// The Y values searched in the current round, more than one only for batched X bits rounds
+    fn get_round_ys(&self) -> Vec<u32> {
+        let y_count = self.get_y_count();
+        let batch = match self.backend {
+            Backend::Gpu(_) if !self.cpu.is_x_word(&self.x_bits) => {
+                (self.y_batch as u64).min((1 << 32) / self.get_x_size())
+            }
+            _ => 1,
+        };
+        let y_position_end = (self.y_position as u64 + batch).min(y_count);
+
+        (self.y_position as u64..y_position_end)
+            .map(|position| self.y_first + self.y_order.get_y_offset(position as u32, y_count))
+            .collect()
+    }
+
+    fn get_round_x_size(&self) -> u64 {
+        self.get_round_ys().len() as u64 * self.get_x_size()
+    }
+
+    /// The Y, its position and the X offset of the Y the round is currently at
+    fn get_round_position(&self) -> (u32, u32, u32) {
+        let index = (self.x_offset as u64 / self.get_x_size()) as u32;
+        if index == 0 {
+            return (self.y, self.y_position, self.x_offset);
         }
 
-        self.y += 1;
+        let y = self.get_round_ys()[index as usize];
+        let x_offset = (self.x_offset as u64 % self.get_x_size()) as u32;
+        (y, self.y_position + index, x_offset)
+    }
+
+    /// The position and parameters of the search, to resume it from later
+    pub fn get_checkpoint(&self) -> checkpoint::Checkpoint {
+        let (y, y_position, x_offset) = self.get_round_position();
 
-        Ok(HasherResult::Continue)
+        checkpoint::Checkpoint {
+            y,
+            x_offset,
+            y_order: self.y_order,
+            y_position,
+            y_first: self.y_first,
+            y_end: self.y_end,
+            x_stride: self.x_stride,
+            y_bits: self.y_bits.clone(),
+            x_bits: self.x_bits.clone(),
+            seed: self.seed,
+            target_checksum: self.target_checksum,
+            ipl3_sha256: self.ipl3_sha256.clone(),
+        }
+    }
+
+    /// Resumes the search from the checkpoint, which must be for the same search
+    pub fn restore_checkpoint(
+        &mut self,
+        checkpoint: &checkpoint::Checkpoint,
+    ) -> Result<(), HasherError> {
+        checkpoint.check_parameters(&self.get_checkpoint())?;
+        self.set_y_position(checkpoint.y_position);
+        self.set_x_offset(checkpoint.x_offset);
+        Ok(())
+    }
+
+    /// Stops the search before the next dispatch once the deadline passes, None runs until the end
+    pub fn set_deadline(&mut self, deadline: Option<std::time::Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Stops the search before the next dispatch once the flag is set: `compute_round` returns
+    /// `HasherResult::Interrupted` for as long as it stays set, with the position (`get_y` and
+    /// `get_x_offset`) where the search continues from, after the in-flight GPU work finished
+    pub fn set_interrupt(
+        &mut self,
+        interrupt: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+    ) {
+        self.interrupt = interrupt;
+    }
+
+    /// Verifies every match on the CPU before returning it, on by default
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// The number of CPU threads the matches of a dispatch are verified on, 1 by default
+    pub fn set_verify_threads(&mut self, threads: usize) {
+        self.verify_threads = threads;
+    }
+
+    /// The number of times in a row the GPU devices are recreated after a device loss before the
+    /// error is returned
+    pub fn set_max_device_resets(&mut self, max_device_resets: u32) {
+        self.max_device_resets = max_device_resets;
+    }
+
+    /// The hashes of the current round so far
+    pub fn get_round_hashes(&self) -> u64 {
+        self.round_hashes
+    }
+
+    /// The hashes since the hasher was created
+    pub fn get_total_hashes(&self) -> u64 {
+        self.total_hashes
+    }
+
+    /// How far the search is and its rate
+    pub fn get_stats(&self) -> HasherStats {
+        let y_total = self
+            .get_y_count()
+            .saturating_sub(self.y_start_position as u64);
+        let y_done = if self.y_finished {
+            y_total
+        } else {
+            (self.y_position - self.y_start_position) as u64
+        };
+        let remaining_hashes = ((y_total - y_done).saturating_mul(self.get_x_size()))
+            .saturating_sub(self.x_offset as u64);
+
+        HasherStats {
+            y_done,
+            y_total,
+            hashes_per_second: self.hashes_per_second,
+            remaining_hashes,
+            y_round_time: self.y_round_time,
+        }
+    }
+
+    fn update_hashes_per_second(&mut self, hashes: u64, elapsed: std::time::Duration) {
+        const SMOOTHING: f64 = 0.2;
+
+        let seconds = elapsed.as_secs_f64();
+        if seconds <= 0.0 {
+            return;
+        }
+
+        let hashes_per_second = hashes as f64 / seconds;
+
+        self.hashes_per_second = if self.hashes_per_second == 0.0 {
+            hashes_per_second
+        } else {
+            (SMOOTHING * hashes_per_second) + ((1.0 - SMOOTHING) * self.hashes_per_second)
+        };
+    }
+
+    /// Checks the CPU round checksum at the current Y and X offset against the checksum over all the
+    /// words
+    pub fn self_check(&self) -> Result<(), HasherError> {
+        let round_checksum =
+            self.cpu
+                .round_checksum(&self.y_bits, self.y, &self.x_bits, self.x_offset);
+        let checksum = self
+            .cpu
+            .verify(&self.y_bits, self.y, &self.x_bits, self.x_offset);
+
+        if round_checksum != checksum {
+            return Err(HasherError::SelfCheckError(
+                self.y,
+                self.x_offset,
+                round_checksum,
+                checksum,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks X offsets spread over the round with the shader on every GPU, each dispatch starts at
+    /// the X offset and targets the CPU checksum of its X value, so it must report that offset
+    pub fn shader_check(
+        &mut self,
+        shader: &gpu::GPUHasherShader,
+        samples: u32,
+    ) -> Result<(), HasherError> {
+        let round_state = self.get_round_state();
+        let x_size = self.get_x_size();
+
+        for gpu in self.get_gpus() {
+            let mut gpu = gpu.with_shader(shader.clone())?;
+
+            for i in 0..samples {
+                let x = ((i as u64 * x_size) / samples as u64) as u32;
+                let checksum =
+                    self.cpu
+                        .verify(&self.y_bits, self.y, &self.x_bits, self.get_x_at(x));
+
+                let result = match &round_state {
+                    RoundState::XWord(y_offset, state) => {
+                        gpu.x_round(checksum, *y_offset, x, None, *state)?
+                    }
+                    RoundState::XBits(rounds) => {
+                        gpu.x_bits_round(checksum, x, None, &rounds[..1], &self.x_bits)?
+                    }
+                };
+
+                if !matches!(result, gpu::GPUHasherResult::Found(found, _) if found.contains(&x)) {
+                    return Err(HasherError::ShaderCheckError(shader.get_name(), self.y, x));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks random Y and X values on every GPU with the configured shader, each dispatch starts
+    /// at the X offset and targets the CPU checksum of its X value, so it must report exactly that
+    /// offset. The values follow from the seed, so a failure is rerun with the same samples. The
+    /// search position is left as is
+    pub fn self_test(&mut self, samples: u32, seed: u64) -> Result<(), HasherError> {
+        let mut random = differential::Random(seed);
+
+        let y_count = self.get_y_count();
+        let x_size = self.get_x_size();
+
+        for _ in 0..samples {
+            let y = self.y_first + random.below(y_count) as u32;
+            let x_offset = random.below(x_size) as u32;
+            let checksum = self
+                .cpu
+                .verify(&self.y_bits, y, &self.x_bits, self.get_x_at(x_offset));
+
+            let round_state = if self.cpu.is_x_word(&self.x_bits) {
+                let (y_offset, state) = self.cpu.y_round(&self.y_bits, y);
+                RoundState::XWord(y_offset, state)
+            } else {
+                let (state, ipl3) = self.cpu.y_round_x_bits(&self.y_bits, y, &self.x_bits);
+                RoundState::XBits(vec![(state, ipl3)])
+            };
+
+            let Backend::Gpu(gpus) = &mut self.backend else {
+                return Ok(());
+            };
+
+            for (index, gpu) in gpus.lock().iter_mut().enumerate() {
+                let result = match &round_state {
+                    RoundState::XWord(y_offset, state) => {
+                        gpu.x_round(checksum, *y_offset, x_offset, None, *state)?
+                    }
+                    RoundState::XBits(rounds) => {
+                        gpu.x_bits_round(checksum, x_offset, None, rounds, &self.x_bits)?
+                    }
+                };
+
+                if !matches!(result, gpu::GPUHasherResult::Found(found, _) if found == [x_offset]) {
+                    return Err(HasherError::GPUSelfTestError(
+                        index,
+                        gpu.get_gpu_info().backend.to_string(),
+                        gpu.get_shader_name(),
+                        y,
+                        x_offset,
+                        seed,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calculates the states of the Y and X values (masked to their bits) on the CPU and with a
+    /// state dump dispatch on every GPU, the GPU pipelines are rebuilt for the search afterwards
+    pub fn dump_state(&mut self, y: u32, x: u32) -> Result<HasherStateDump, HasherError> {
+        let y = y & Self::get_y_max(&self.y_bits);
+        let x = (x as u64 & (self.get_x_size() - 1)) as u32;
+
+        let cpu = self.cpu.dump_state(&self.y_bits, y, &self.x_bits, x);
+
+        let round_state = if self.cpu.is_x_word(&self.x_bits) {
+            let (y_offset, state) = self.cpu.y_round(&self.y_bits, y);
+            RoundState::XWord(y_offset, state)
+        } else {
+            let (state, ipl3) = self.cpu.y_round_x_bits(&self.y_bits, y, &self.x_bits);
+            RoundState::XBits(vec![(state, ipl3)])
+        };
+
+        let mut gpu_states = vec![];
+
+        if let Backend::Gpu(gpus) = &mut self.backend {
+            for gpu in gpus.lock().iter_mut() {
+                gpu.set_dump_state(true);
+                let result = match &round_state {
+                    RoundState::XWord(y_offset, state) => {
+                        gpu.x_round(0, *y_offset, x, None, *state)
+                    }
+                    RoundState::XBits(rounds) => gpu.x_bits_round(0, x, None, rounds, &self.x_bits),
+                };
+                gpu.set_dump_state(false);
+
+                match result? {
+                    gpu::GPUHasherResult::State(state) => gpu_states.push(state),
+                    _ => unreachable!("state dump dispatches return the state"),
+                }
+            }
+        }
+
+        Ok(HasherStateDump {
+            cpu,
+            gpus: gpu_states,
+        })
+    }
+
+    /// Runs the differential test case on every GPU: the checksum of the case is calculated on the
+    /// CPU and searched for with a dispatch starting before X, which must find X and nothing the
+    /// CPU disagrees with. The hasher must be created for an IPL3 of the case length
+    pub fn differential_test(
+        &mut self,
+        case: &differential::DifferentialCase,
+    ) -> Result<(), HasherError> {
+        let mut cpu = cpu::CPUHasher::new(&case.get_image(), case.seed);
+        cpu.set_y_bits(&case.y_bits);
+
+        let checksum = cpu.verify(&case.y_bits, case.y, &case.x_bits, case.x);
+
+        let round_state = if cpu.is_x_word(&case.x_bits) {
+            let (y_offset, state) = cpu.y_round(&case.y_bits, case.y);
+            RoundState::XWord(y_offset, state)
+        } else {
+            let (state, ipl3) = cpu.y_round_x_bits(&case.y_bits, case.y, &case.x_bits);
+            RoundState::XBits(vec![(state, ipl3)])
+        };
+
+        let Backend::Gpu(gpus) = &mut self.backend else {
+            return Ok(());
+        };
+
+        for (index, gpu) in gpus.lock().iter_mut().enumerate() {
+            let lead = case.x_lead % gpu.get_dispatch_size().min(case.x as u64 + 1);
+            let x_offset = case.x - lead as u32;
+
+            let result = match &round_state {
+                RoundState::XWord(y_offset, state) => {
+                    gpu.x_round(checksum, *y_offset, x_offset, None, *state)?
+                }
+                RoundState::XBits(rounds) => {
+                    gpu.x_bits_round(checksum, x_offset, None, rounds, &case.x_bits)?
+                }
+            };
+
+            let reported = match result {
+                gpu::GPUHasherResult::Found(found, _) if !found.contains(&case.x) => Some(format!(
+                    "missed X={:08X}, found {}",
+                    case.x,
+                    found
+                        .iter()
+                        .map(|x| format!("X={x:08X}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+                gpu::GPUHasherResult::Found(found, _) => found
+                    .iter()
+                    .find(|&&x| cpu.verify(&case.y_bits, case.y, &case.x_bits, x) != checksum)
+                    .map(|x| format!("also found X={x:08X}, which the CPU doesn't match")),
+                _ => Some(format!("missed X={:08X}", case.x)),
+            };
+
+            if let Some(reported) = reported {
+                return Err(HasherError::GPUDifferentialTestError(
+                    index,
+                    gpu.get_gpu_info().backend.to_string(),
+                    gpu.get_shader_name(),
+                    case.case_seed,
+                    case.to_string(),
+                    reported,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What the search covers from the current position
+    pub fn get_plan(&self) -> HasherPlan {
+        let y_count = self.get_y_count().saturating_sub(self.y_position as u64);
+        let x_size = self.get_x_size();
+
+        let mut shared_words: Vec<u32> = self
+            .y_bits
+            .iter()
+            .map(|offset| offset / 32)
+            .filter(|word| self.x_bits.iter().any(|offset| offset / 32 == *word))
+            .collect();
+        shared_words.dedup();
+
+        HasherPlan {
+            dispatch_size: self.get_dispatch_size(),
+            y_init: self.get_y_start(),
+            y_end: self.y_end,
+            x_size,
+            total_hashes: (y_count as u128 * x_size as u128).saturating_sub(self.x_offset as u128),
+            y_bits: self.y_bits.clone(),
+            x_bits: self.x_bits.clone(),
+            y_bits_set: self.cpu.count_set_bits(&self.y_bits),
+            x_bits_set: self.cpu.count_set_bits(&self.x_bits),
+            shared_words,
+        }
+    }
+
+    /// The last Y value of the range
+    pub fn get_y_end(&self) -> u32 {
+        self.y_end
+    }
+
+    /// The X values of every Y
+    pub fn get_x_size(&self) -> u64 {
+        1 << self.x_bits.len()
+    }
+
+    /// The highest Y value of the Y bits
+    pub fn get_y_max(y_bits: &[u32]) -> u32 {
+        ((1u64 << y_bits.len()) - 1) as u32
+    }
+
+    /// The first and last Y value of the shard at the index, out of count equal shards of the Y
+    /// values
+    pub fn get_shard_range(y_bits: &[u32], index: u32, count: u32) -> (u32, u32) {
+        let y_size = Self::get_y_max(y_bits) as u64 + 1;
+        let y_start = y_size * index as u64 / count as u64;
+        let y_end = y_size * (index as u64 + 1) / count as u64 - 1;
+        (y_start as u32, y_end as u32)
+    }
+
+    /// Every Y was searched
+    pub fn is_y_finished(&self) -> bool {
+        self.y_finished
+    }
+
+    fn get_gpus(&self) -> Vec<std::sync::MutexGuard<'_, gpu::GPUHasher>> {
+        match &self.backend {
+            Backend::Gpu(gpus) => gpus.lock(),
+            Backend::Cpu(_) => vec![],
+        }
+    }
+
+    /// All the adapters the GPUs were selected from, in --gpu-adapter index order
+    pub fn get_gpu_adapter_list(&self) -> &[wgpu::AdapterInfo] {
+        &self.gpu_adapter_list
+    }
+
+    /// The adapter of every GPU
+    pub fn get_gpu_info(&self) -> Vec<wgpu::AdapterInfo> {
+        self.get_gpus()
+            .iter()
+            .map(|gpu| gpu.get_gpu_info())
+            .collect()
+    }
+
+    /// The capabilities and configuration of every GPU
+    pub fn get_gpu_device_info(&self) -> Vec<gpu::GPUDeviceInfo> {
+        self.get_gpus()
+            .iter()
+            .map(|gpu| gpu.get_device_info())
+            .collect()
+    }
+
+    /// Whether the pipelines of every GPU came from the pipeline cache
+    pub fn get_gpu_pipeline_cache(&self) -> Vec<gpu::GPUPipelineCache> {
+        self.get_gpus()
+            .iter()
+            .map(|gpu| gpu.get_pipeline_cache())
+            .collect()
+    }
+
+    /// The device limits of every GPU
+    pub fn get_gpu_limits(&self) -> Vec<wgpu::Limits> {
+        self.get_gpus()
+            .iter()
+            .map(|gpu| gpu.get_gpu_limits())
+            .collect()
+    }
+
+    /// The workgroups and local size of every GPU
+    pub fn get_gpu_workgroups(&self) -> Vec<((u32, u32, u32), u32)> {
+        self.get_gpus()
+            .iter()
+            .map(|gpu| (gpu.get_workgroups(), gpu.get_local_size()))
+            .collect()
+    }
+
+    /// Enables kernel timings on all GPUs that support them, returns false if any of them doesn't
+    pub fn enable_gpu_timings(&mut self) -> bool {
+        let Backend::Gpu(gpus) = &mut self.backend else {
+            return false;
+        };
+
+        let mut supported = true;
+        for gpu in gpus.lock().iter_mut() {
+            supported &= gpu.enable_timings();
+        }
+        supported
+    }
+
+    /// Bakes the target checksum into the shaders of all GPUs that compile them at runtime, returns
+    /// false if any of them can't
+    pub fn set_gpu_specialized(&mut self, specialize: bool) -> bool {
+        let Backend::Gpu(gpus) = &mut self.backend else {
+            return false;
+        };
+
+        let mut supported = true;
+        for gpu in gpus.lock().iter_mut() {
+            supported &= gpu.set_specialized(specialize);
+        }
+        supported
+    }
+
+    /// Lets the shaders of every GPU return early once a match was found in the dispatch, the X bits
+    /// checksum checks every period words, 0 disables it
+    pub fn set_gpu_early_exit_period(&mut self, early_exit_period: u32) {
+        if let Backend::Gpu(gpus) = &mut self.backend {
+            for gpu in gpus.lock().iter_mut() {
+                gpu.set_early_exit_period(early_exit_period);
+            }
+        }
+    }
+
+    /// Multiplies with 64-bit integers in the shaders of the GPUs with support for them
+    pub fn set_gpu_shader_int64(&mut self, shader_int64: bool) {
+        if let Backend::Gpu(gpus) = &mut self.backend {
+            for gpu in gpus.lock().iter_mut() {
+                gpu.set_shader_int64(shader_int64);
+            }
+        }
+    }
+
+    /// Passes the GLSL SPIR-V to the drivers of the GPUs with support for it as it is
+    pub fn set_gpu_passthrough(&mut self, passthrough: bool) {
+        if let Backend::Gpu(gpus) = &mut self.backend {
+            for gpu in gpus.lock().iter_mut() {
+                gpu.set_passthrough(passthrough);
+            }
+        }
+    }
+
+    fn get_gpu_early_exit_period(&self) -> u32 {
+        self.get_gpus()
+            .iter()
+            .map(|gpu| gpu.get_early_exit_period())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The kernel time and the dispatch size of the last dispatch of each GPU, None for a GPU that
+    /// wasn't timed or didn't dispatch since the last call
+    pub fn take_gpu_kernel_times(&mut self) -> Vec<Option<(std::time::Duration, u64)>> {
+        match &mut self.backend {
+            Backend::Gpu(gpus) => gpus.take_kernel_times(),
+            Backend::Cpu(_) => vec![],
+        }
+    }
+
+    /// Halves the workgroups of the GPUs that ran out of memory, false when none of them can shrink
+    fn downsize_gpus(&mut self) -> Result<bool, HasherError> {
+        let Backend::Gpu(gpus) = &mut self.backend else {
+            return Ok(false);
+        };
+
+        let mut downsized = false;
+        for gpu in gpus.lock().iter_mut().filter(|gpu| gpu.is_out_of_memory()) {
+            downsized |= gpu.downsize()?;
+        }
+        Ok(downsized)
+    }
+
+    /// The GPU index and the requested workgroups of each GPU downsized since the last call
+    pub fn take_gpu_downsized(&mut self) -> Vec<(usize, (u32, u32, u32))> {
+        let Backend::Gpu(gpus) = &mut self.backend else {
+            return vec![];
+        };
+
+        gpus.lock()
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, gpu)| gpu.take_downsized().map(|requested| (index, requested)))
+            .collect()
+    }
+
+    /// Splits the dispatches of every GPU into submissions under the time budget (the OS watchdog
+    /// resets the device on long ones), None disables it
+    pub fn set_gpu_max_dispatch_time(&mut self, max_dispatch_time: Option<std::time::Duration>) {
+        if let Backend::Gpu(gpus) = &mut self.backend {
+            for gpu in gpus.lock().iter_mut() {
+                gpu.set_max_dispatch_time(max_dispatch_time);
+            }
+        }
+    }
+
+    /// Scales the workgroups of every GPU to dispatches of about the target time, None keeps them
+    pub fn set_gpu_target_dispatch_time(
+        &mut self,
+        target_dispatch_time: Option<std::time::Duration>,
+    ) {
+        if let Backend::Gpu(gpus) = &mut self.backend {
+            for gpu in gpus.lock().iter_mut() {
+                gpu.set_target_dispatch_time(target_dispatch_time);
+            }
+        }
+    }
+
+    /// Recreates every GPU with the workgroups and local size
+    pub fn set_gpu_workgroups(
+        &mut self,
+        workgroups: (u32, u32, u32),
+        local_size: u32,
+    ) -> Result<(), HasherError> {
+        if let Backend::Gpu(gpus) = &mut self.backend {
+            gpus.stop();
+            let gpus = gpus
+                .lock()
+                .iter()
+                .map(|gpu| gpu.with_workgroups(workgroups, local_size))
+                .collect::<Result<_, _>>()?;
+            self.backend = Backend::Gpu(GPUWorkers::spawn(gpus));
+        }
+        Ok(())
+    }
+
+    /// The threads of the CPU search, None on the GPUs
+    pub fn get_cpu_threads(&self) -> Option<usize> {
+        match &self.backend {
+            Backend::Gpu(_) => None,
+            Backend::Cpu(cpu) => Some(cpu.pool.current_num_threads()),
+        }
+    }
+
+    /// The X values hashed per dispatch, of every GPU together (or the CPU round size)
+    pub fn get_dispatch_size(&self) -> u64 {
+        match &self.backend {
+            Backend::Gpu(gpus) => gpus.get_dispatch_size(),
+            Backend::Cpu(_) => cpu::CPUHasher::X_ROUND_SIZE,
+        }
+    }
+
+    fn get_round_state(&mut self) -> RoundState {
+        let ys = self.get_round_state_ys();
+        RoundPrefetch::compute(
+            &self.cpu,
+            &self.y_bits,
+            &self.x_bits,
+            self.y_order,
+            &ys,
+            &mut self.y_round_cache,
+        )
+    }
+
+    /// The Y values the round state of the current round is computed for
+    fn get_round_state_ys(&self) -> Vec<u32> {
+        if self.cpu.is_x_word(&self.x_bits) {
+            vec![self.y]
+        } else {
+            self.get_round_ys()
+        }
+    }
+
+    /// The round state of the current round, from the worker when it was prefetched, the time
+    /// spent waiting for it is added to the stats
+    fn take_round_state(&mut self) -> RoundState {
+        let time = std::time::Instant::now();
+        let ys = self.get_round_state_ys();
+
+        let round_state = match self
+            .round_prefetch
+            .as_mut()
+            .and_then(|prefetch| prefetch.take(&ys))
+        {
+            Some(round_state) => round_state,
+            None => self.get_round_state(),
+        };
+
+        self.y_round_time += time.elapsed();
+        round_state
+    }
+
+    /// Starts computing the round state of the round after the current one on the worker, so that
+    /// it's ready by the time the GPUs finish this one. The CPU backend already keeps every thread
+    /// busy, and there is nothing to prefetch in the last round
+    fn prefetch_next_round(&mut self) {
+        if !matches!(self.backend, Backend::Gpu(_)) {
+            return;
+        }
+
+        let y_count = self.get_y_count();
+        let position = self.y_position as u64 + self.get_round_ys().len() as u64;
+        if position >= y_count {
+            return;
+        }
+
+        let batch = if self.cpu.is_x_word(&self.x_bits) {
+            1
+        } else {
+            self.get_round_ys().len() as u64
+        };
+        let ys = (position..(position + batch).min(y_count))
+            .map(|position| self.y_first + self.y_order.get_y_offset(position as u32, y_count))
+            .collect();
+
+        self.round_prefetch
+            .get_or_insert_with(|| {
+                RoundPrefetch::spawn(
+                    self.cpu.clone(),
+                    self.y_bits.clone(),
+                    self.x_bits.clone(),
+                    self.y_order,
+                )
+            })
+            .request(ys);
+    }
+
+    /// Runs a throwaway dispatch of the current Y round on the GPUs with the unmatchable benchmark
+    /// checksum, the result is discarded and the search position is left as is
+    pub fn warm_up(&mut self) -> Result<(), HasherError> {
+        if let Backend::Gpu(_) = self.backend {
+            let round_state = std::sync::Arc::new(self.get_round_state());
+            self.backend.get_search().x_round(
+                Self::BENCHMARK_CHECKSUM,
+                0,
+                &round_state,
+                &self.x_bits,
+            )?;
+            self.backend.stop();
+        }
+        Ok(())
+    }
+
+    /// Times the dispatches of the current Y round with the unmatchable benchmark checksum, the
+    /// search position is left as is
+    pub fn benchmark(&mut self, dispatches: u32) -> Result<HasherBenchmark, HasherError> {
+        let round_state = std::sync::Arc::new(self.get_round_state());
+        let x_size = self.get_round_x_size();
+
+        let mut x_offset = 0;
+        let mut hashes = 0;
+        let mut elapsed = std::time::Duration::ZERO;
+
+        // The first dispatch is not timed, it only warms up the backend
+        for i in 0..=dispatches {
+            let time = std::time::Instant::now();
+
+            let result = self.backend.get_search().x_round(
+                Self::BENCHMARK_CHECKSUM,
+                x_offset,
+                &round_state,
+                &self.x_bits,
+            )?;
+
+            let x_step = match result {
+                XRoundResult::Found(x_results, _) => {
+                    let x = x_results[0];
+                    let verify_checksum = self.cpu.verify(&self.y_bits, self.y, &self.x_bits, x);
+                    return Err(HasherError::ChecksumVerifyError(self.y, x, verify_checksum));
+                }
+                XRoundResult::Continue(x_step) => x_step as u64,
+                XRoundResult::End => x_size - x_offset as u64,
+            };
+
+            if i > 0 {
+                elapsed += time.elapsed();
+                hashes += x_step;
+            }
+
+            x_offset = ((x_offset as u64 + x_step) % x_size) as u32;
+        }
+
+        self.backend.stop();
+
+        Ok(HasherBenchmark {
+            gpu_workgroups: self.get_gpu_workgroups(),
+            hashes,
+            dispatches,
+            elapsed,
+            specialized: self.get_gpus().iter().any(|gpu| gpu.is_specialized()),
+            early_exit_period: self.get_gpu_early_exit_period(),
+            early_match: false,
+        })
+    }
+
+    /// Times dispatches that find a match at their first X value, one GPU at a time and without
+    /// submitting the next dispatch ahead, to measure the work an early exit saves
+    pub fn benchmark_early_match(
+        &mut self,
+        dispatches: u32,
+    ) -> Result<HasherBenchmark, HasherError> {
+        let round_state = self.get_round_state();
+        let x = 0;
+        let target_checksum = self.cpu.verify(&self.y_bits, self.y, &self.x_bits, x);
+
+        let mut gpus = self.get_gpus();
+
+        let mut hashes = 0;
+        let mut elapsed = std::time::Duration::ZERO;
+
+        // The first dispatch is not timed, it only warms up the backend
+        for i in 0..=dispatches {
+            for gpu in gpus.iter_mut() {
+                let time = std::time::Instant::now();
+
+                let result = match &round_state {
+                    RoundState::XWord(y_offset, state) => {
+                        gpu.x_round(target_checksum, *y_offset, x, None, *state)?
+                    }
+                    RoundState::XBits(rounds) => {
+                        gpu.x_bits_round(target_checksum, x, None, &rounds[..1], &self.x_bits)?
+                    }
+                };
+
+                if !matches!(result, gpu::GPUHasherResult::Found(found, _) if found.contains(&x)) {
+                    return Err(HasherError::ShaderCheckError(
+                        gpu.get_shader_name(),
+                        self.y,
+                        x,
+                    ));
+                }
+
+                if i > 0 {
+                    elapsed += time.elapsed();
+                    hashes += gpu.get_dispatch_size();
+                }
+            }
+        }
+
+        drop(gpus);
+
+        Ok(HasherBenchmark {
+            gpu_workgroups: self.get_gpu_workgroups(),
+            hashes,
+            dispatches: dispatches * self.get_gpus().len() as u32,
+            elapsed,
+            specialized: self.get_gpus().iter().any(|gpu| gpu.is_specialized()),
+            early_exit_period: self.get_gpu_early_exit_period(),
+            early_match: true,
+        })
+    }
+
+    /// No more dispatches are issued, the ones submitted ahead are left to finish before the search
+    /// position is handed back to resume from
+    fn stop(&mut self, result: HasherResult) -> Result<HasherResult, HasherError> {
+        self.x_round_start = None;
+        self.backend.stop();
+        if let Backend::Gpu(gpus) = &self.backend {
+            for gpu in gpus.lock().iter_mut() {
+                gpu.drain()?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Runs the next dispatches of the search, from the current position. A match is verified on
+    /// the CPU (unless disabled) and the search stays at it until `skip_found`
+    pub fn compute_round(&mut self) -> Result<HasherResult, HasherError> {
+        if self.is_y_finished() {
+            return Ok(HasherResult::End);
+        }
+
+        if let Some(deadline) = self.deadline
+            && std::time::Instant::now() >= deadline
+        {
+            return self.stop(HasherResult::TimeLimit);
+        }
+
+        if let Some(interrupt) = &self.interrupt
+            && interrupt.load(std::sync::atomic::Ordering::SeqCst)
+        {
+            return self.stop(HasherResult::Interrupted);
+        }
+
+        let round_state = match self.round_state.take() {
+            Some(round_state) => round_state,
+            None => {
+                self.round_hashes = 0;
+                std::sync::Arc::new(self.take_round_state())
+            }
+        };
+
+        self.prefetch_next_round();
+
+        let time = self
+            .x_round_start
+            .take()
+            .unwrap_or_else(std::time::Instant::now);
+
+        let result = self.backend.get_search().x_round(
+            self.target_checksum,
+            self.x_offset,
+            &round_state,
+            &self.x_bits,
+        );
+
+        self.round_state = Some(round_state);
+
+        // The failed dispatch is retried from the same X offset on the recreated devices
+        let result = match result {
+            Err(HasherError::GPUDeviceLost(reason))
+                if self.device_resets < self.max_device_resets =>
+            {
+                self.device_resets += 1;
+                if let Backend::Gpu(gpus) = &mut self.backend {
+                    for gpu in gpus.lock().iter_mut() {
+                        gpu.reset()?;
+                    }
+                }
+                return Ok(HasherResult::DeviceReset(reason));
+            }
+            Err(error @ HasherError::GPUOutOfMemory(..)) => {
+                if !self.downsize_gpus()? {
+                    return Err(error);
+                }
+                return Ok(HasherResult::Downsized);
+            }
+            result => result?,
+        };
+
+        self.device_resets = 0;
+
+        // Only the matches of the first Y are returned, the rest are found again after skip_found
+        let round_ys = self.get_round_ys();
+        let result = match result {
+            XRoundResult::Found(x_results, dropped) => {
+                let index = x_results[0] as u64 / self.get_x_size();
+                let x_results = x_results
+                    .into_iter()
+                    .filter(|&x| x as u64 / self.get_x_size() == index)
+                    .collect();
+                XRoundResult::Found(x_results, dropped)
+            }
+            result => result,
+        };
+
+        let dispatch_hashes = match &result {
+            XRoundResult::Found(x_results, _) => {
+                x_results[x_results.len() - 1].wrapping_sub(self.x_offset) as u64 + 1
+            }
+            XRoundResult::Continue(x_step) => *x_step as u64,
+            XRoundResult::End => self.get_round_x_size() - self.x_offset as u64,
+        };
+
+        self.round_hashes += dispatch_hashes;
+        self.total_hashes += dispatch_hashes;
+        self.update_hashes_per_second(dispatch_hashes, time.elapsed());
+
+        match result {
+            XRoundResult::Found(x_results, dropped) => {
+                let index = (x_results[0] as u64 / self.get_x_size()) as usize;
+                let y = round_ys[index];
+                let x_results: Vec<u32> = x_results
+                    .into_iter()
+                    .map(|x| self.get_x_at((x as u64 % self.get_x_size()) as u32))
+                    .collect();
+
+                if self.verify {
+                    let verify_checksums = if x_results.len() > 1 {
+                        let candidates: Vec<(u32, u32)> =
+                            x_results.iter().map(|&x| (y, x)).collect();
+                        self.cpu.verify_batch(
+                            &self.y_bits,
+                            &self.x_bits,
+                            &candidates,
+                            self.verify_threads,
+                        )
+                    } else {
+                        vec![self.cpu.verify(&self.y_bits, y, &self.x_bits, x_results[0])]
+                    };
+                    for (&x, verify_checksum) in x_results.iter().zip(verify_checksums) {
+                        if verify_checksum != self.target_checksum {
+                            return Err(HasherError::ChecksumVerifyError(y, x, verify_checksum));
+                        }
+                    }
+                }
+                return Ok(HasherResult::Found(y, x_results, dropped));
+            }
+            XRoundResult::Continue(x_step) => {
+                self.x_offset += x_step;
+                if let Backend::Gpu(_) = self.backend {
+                    self.x_round_start = Some(std::time::Instant::now());
+                }
+                return Ok(HasherResult::Progress);
+            }
+            XRoundResult::End => {}
+        }
+
+        self.next_y();
+
+        Ok(HasherResult::Continue)
+    }
+
+    /// Continues the search after the X value found for the Y of the current round
+    pub fn skip_found(&mut self, y: u32, x: u32) {
+        let index = self
+            .get_round_ys()
+            .iter()
+            .position(|&round_y| round_y == y)
+            .unwrap_or(0) as u64;
+        let x_next = (index * self.get_x_size()) + self.get_x_offset_of(x) as u64 + 1;
+
+        if x_next >= self.get_round_x_size() {
+            self.next_y();
+        } else {
+            self.x_offset = x_next as u32;
+        }
+    }
+
+    fn next_y(&mut self) {
+        let rounds = self.get_round_ys().len() as u64;
+
+        self.round_state = None;
+        self.x_offset = 0;
+
+        let y_count = self.get_y_count();
+
+        if self.y_position as u64 + rounds >= y_count {
+            self.y_finished = true;
+        } else {
+            self.y_position += rounds as u32;
+            self.y = self.y_first + self.y_order.get_y_offset(self.y_position, y_count);
+        }
     }
 }
@@ -0,0 +1,129 @@
+use crate::{error::HasherError, rom};
+use std::io::{Read, Seek, Write};
+
+/// The byte offset of CRC1 in the ROM header, CRC2 follows it
+pub const CRC_OFFSET: u64 = 0x10;
+
+/// The first byte covered by the CRCs, right after the IPL3
+pub const CRC_START: usize = 0x1000;
+
+/// The bytes covered by the CRCs, a ROM must be at least `CRC_START + CRC_LENGTH` long
+pub const CRC_LENGTH: usize = 0x100000;
+
+/// The IPL3 variants of the header CRC, told apart by the CIC seed
+#[derive(Clone, Copy, PartialEq)]
+enum CrcVariant {
+    Cic6102,
+    Cic6103,
+    Cic6105,
+    Cic6106,
+}
+
+impl CrcVariant {
+    fn from_seed(seed: u8) -> Self {
+        match seed {
+            0x78 => Self::Cic6103,
+            0x91 => Self::Cic6105,
+            0x85 => Self::Cic6106,
+            // Every other seed, custom ones included, is taken for a 6102 compatible IPL3
+            _ => Self::Cic6102,
+        }
+    }
+
+    /// The initial value of the six CRC accumulators
+    fn initial_value(&self, seed: u8) -> u32 {
+        let magic = match self {
+            Self::Cic6103 | Self::Cic6106 => 0x6C078965,
+            Self::Cic6102 | Self::Cic6105 => 0x5D588B65,
+        };
+        (seed as u32).wrapping_mul(magic).wrapping_add(1)
+    }
+}
+
+/// CRC1 and CRC2 of the ROM (big endian, from the start of the header) as the IPL3 of the CIC
+/// with the seed checks them at boot
+pub fn calculate_crc(rom: &[u8], seed: u8) -> Result<(u32, u32), HasherError> {
+    if rom.len() < CRC_START + CRC_LENGTH {
+        return Err(HasherError::HeaderCrcRomTooSmall(
+            rom.len() as u64,
+            (CRC_START + CRC_LENGTH) as u64,
+        ));
+    }
+
+    let word = |offset: usize| u32::from_be_bytes(rom[offset..(offset + 4)].try_into().unwrap());
+
+    let variant = CrcVariant::from_seed(seed);
+    let initial_value = variant.initial_value(seed);
+    let [mut t1, mut t2, mut t3, mut t4, mut t5, mut t6] = [initial_value; 6];
+
+    for offset in (CRC_START..(CRC_START + CRC_LENGTH)).step_by(4) {
+        let data = word(offset);
+
+        let (sum, carry) = t6.overflowing_add(data);
+        if carry {
+            t4 = t4.wrapping_add(1);
+        }
+        t6 = sum;
+        t3 ^= data;
+        let rotated = data.rotate_left(data & 0x1F);
+        t5 = t5.wrapping_add(rotated);
+        if t2 > data {
+            t2 ^= rotated;
+        } else {
+            t2 ^= t6 ^ data;
+        }
+        // The 6105 IPL3 mixes in its own words, 0x710 bytes into the IPL3
+        t1 = t1.wrapping_add(match variant {
+            CrcVariant::Cic6105 => word(0x40 + 0x710 + (offset & 0xFF)) ^ data,
+            _ => t5 ^ data,
+        });
+    }
+
+    Ok(match variant {
+        CrcVariant::Cic6103 => ((t6 ^ t4).wrapping_add(t3), (t5 ^ t2).wrapping_add(t1)),
+        CrcVariant::Cic6106 => (
+            t6.wrapping_mul(t4).wrapping_add(t3),
+            t5.wrapping_mul(t2).wrapping_add(t1),
+        ),
+        CrcVariant::Cic6102 | CrcVariant::Cic6105 => (t6 ^ t4 ^ t3, t5 ^ t2 ^ t1),
+    })
+}
+
+/// Recalculates the CRCs of the ROM file for the CIC with the seed and writes them to the header,
+/// in place and in the byte order of the file. Returns CRC1 and CRC2
+pub fn fix_crc(
+    path: &std::path::Path,
+    format: Option<rom::RomFormat>,
+    seed: u8,
+) -> Result<(u32, u32), HasherError> {
+    let mut f = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+
+    let size = f.metadata()?.len();
+    if size < (CRC_START + CRC_LENGTH) as u64 {
+        return Err(HasherError::HeaderCrcRomTooSmall(
+            size,
+            (CRC_START + CRC_LENGTH) as u64,
+        ));
+    }
+
+    let format = rom::RomFormat::read(&mut f, format)?;
+
+    let mut data = vec![0u8; CRC_START + CRC_LENGTH];
+    f.seek(std::io::SeekFrom::Start(0))?;
+    f.read_exact(&mut data)?;
+    format.swap(&mut data);
+
+    let (crc1, crc2) = calculate_crc(&data, seed)?;
+
+    let mut header = [crc1.to_be_bytes(), crc2.to_be_bytes()].concat();
+    format.swap(&mut header);
+
+    f.seek(std::io::SeekFrom::Start(CRC_OFFSET))?;
+    f.write_all(&header)?;
+    f.flush()?;
+
+    Ok((crc1, crc2))
+}
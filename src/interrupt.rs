@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::HasherError;
+
+/// Exit code of a second signal, the shell convention for SIGINT
+const FORCE_EXIT_CODE: i32 = 130;
+
+/// Ctrl+C and SIGTERM (SIGHUP too on Unix) handler, the first signal stops the search between
+/// dispatches and the second one exits right away, once the ROM being signed (if any) is fully
+/// written
+#[derive(Clone)]
+pub struct Interrupt {
+    requested: Arc<AtomicBool>,
+    signing: Arc<Mutex<()>>,
+}
+
+impl Interrupt {
+    pub fn install() -> Result<Self, HasherError> {
+        let interrupt = Self {
+            requested: Arc::new(AtomicBool::new(false)),
+            signing: Arc::new(Mutex::new(())),
+        };
+
+        let handler = interrupt.clone();
+        ctrlc::set_handler(move || {
+            if handler.requested.swap(true, Ordering::SeqCst) {
+                let _signing = handler.lock_signing();
+                std::process::exit(FORCE_EXIT_CODE);
+            }
+        })?;
+
+        Ok(interrupt)
+    }
+
+    pub fn get_flag(&self) -> Arc<AtomicBool> {
+        self.requested.clone()
+    }
+
+    /// Held while the ROM is signed so that a forced exit never leaves it half written
+    pub fn lock_signing(&self) -> MutexGuard<'_, ()> {
+        self.signing
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
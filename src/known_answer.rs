@@ -0,0 +1,102 @@
+use crate::{cpu::CPUHasher, error::HasherError};
+
+/// A synthetic IPL3 image with a seed and the checksum the algorithm must calculate for them
+pub struct KnownAnswer {
+    /// The xorshift state the image is generated from, 0 keeps every word at 0
+    pub generator: u32,
+    /// The image length in 32-bit words
+    pub ipl3_words: usize,
+    /// The CIC seed
+    pub seed: u8,
+    /// The expected checksum
+    pub checksum: u64,
+}
+
+/// The built-in vectors. The images are generated rather than embedded so that no bootcode ships
+/// with the crate, the random ones take both sides of every comparison in the word loop and in
+/// the final mix, the all-zero one the zero multiplier and zero difference cases of the sum
+pub const KNOWN_ANSWERS: &[KnownAnswer] = &[
+    KnownAnswer {
+        generator: 0,
+        ipl3_words: 1008,
+        seed: 0x3F,
+        checksum: 0x2982AD8C201D,
+    },
+    KnownAnswer {
+        generator: 0x6C078965,
+        ipl3_words: 1008,
+        seed: 0x3F,
+        checksum: 0xD2767A88F557,
+    },
+    KnownAnswer {
+        generator: 0x6C078965,
+        ipl3_words: 1008,
+        seed: 0x78,
+        checksum: 0x08A230ABF257,
+    },
+    KnownAnswer {
+        generator: 0x12345678,
+        ipl3_words: 1008,
+        seed: 0x91,
+        checksum: 0xC896643657AC,
+    },
+    KnownAnswer {
+        generator: 0x12345678,
+        ipl3_words: 1008,
+        seed: 0x85,
+        checksum: 0x0795B24ED230,
+    },
+    KnownAnswer {
+        generator: 0xDEADBEEF,
+        ipl3_words: 64,
+        seed: 0xDD,
+        checksum: 0xFC4D1C050741,
+    },
+];
+
+impl KnownAnswer {
+    /// The image of the vector, see `generate_image`
+    pub fn get_image(&self) -> Vec<u8> {
+        generate_image(self.generator, self.ipl3_words)
+    }
+
+    /// Calculates the checksum of the image on the CPU and compares it with the expected one
+    pub fn check(&self) -> Result<(), HasherError> {
+        let checksum = CPUHasher::new(&self.get_image(), self.seed).checksum();
+
+        if checksum != self.checksum {
+            return Err(HasherError::KnownAnswerMismatch(
+                self.generator,
+                self.ipl3_words,
+                self.seed,
+                self.checksum,
+                checksum,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// A synthetic image, big-endian words of the 32-bit xorshift sequence after the generator state
+pub fn generate_image(generator: u32, ipl3_words: usize) -> Vec<u8> {
+    let mut state = generator;
+
+    (0..ipl3_words)
+        .flat_map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state.to_be_bytes()
+        })
+        .collect()
+}
+
+/// Checks every built-in vector, returns how many were checked
+pub fn check_all() -> Result<usize, HasherError> {
+    for known_answer in KNOWN_ANSWERS {
+        known_answer.check()?;
+    }
+
+    Ok(KNOWN_ANSWERS.len())
+}
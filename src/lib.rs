@@ -0,0 +1,40 @@
+//! IPL3 checksum collision search for N64 ROMs: finds the Y and X values for bits of the IPL3 that
+//! make it hash to a target checksum, on the GPU (wgpu) or the CPU.
+//!
+//! The IPL3 is read with [`Hasher::read_ipl3`] (or given as bytes), [`Hasher::new`] prepares the
+//! search and [`Hasher::compute_round`] runs it a round at a time. The `ipl3hasher-new` binary is
+//! a command line frontend built on this API.
+
+#![warn(missing_docs)]
+
+/// The CPU hot paths for the criterion benchmarks, built with the bench feature
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+/// Search progress saved to and resumed from a file
+pub mod checkpoint;
+#[cfg(feature = "glsl")]
+mod compiler;
+/// The IPL3 checksum algorithm and the CPU search
+pub mod cpu;
+/// Random cases that the GPU shaders are cross-checked against the CPU with
+pub mod differential;
+/// The error type of the whole crate
+pub mod error;
+/// The search on wgpu adapters
+pub mod gpu;
+/// The search over the Y and X values, on the GPUs or the CPU
+pub mod hasher;
+/// The CRC pair of the ROM header
+pub mod header;
+/// Synthetic IPL3 images with precomputed checksums that the algorithm is checked against
+pub mod known_answer;
+/// Verified collisions recorded in a results file
+pub mod results;
+/// ROM formats and the IPL3 in them
+pub mod rom;
+
+pub use cpu::CPUHasher;
+pub use error::HasherError;
+pub use gpu::{GPUAdapterSelector, GPUHasher, GPUHasherShader, GPUWorkgroupsSelector};
+pub use hasher::{Hasher, HasherDevices, HasherResult, HasherSearch};
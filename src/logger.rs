@@ -0,0 +1,1258 @@
+use crate::{
+    cli::Cic,
+    differential::DifferentialCase,
+    error::HasherError,
+    gpu::{GPUDeviceInfo, GPUPipelineCache},
+    hasher::{HasherBenchmark, HasherPlan, HasherStateDump, HasherStats},
+    results::ResultEntry,
+    rom::{self, RomFormat},
+};
+use serde_json::json;
+use std::io::{IsTerminal, Write};
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+pub struct Logger {
+    json: bool,
+    verbosity: Verbosity,
+    tty: bool,
+    progress_line: std::cell::Cell<bool>,
+    progress_time: std::cell::Cell<Option<std::time::Instant>>,
+    log_file: std::cell::RefCell<Option<std::io::LineWriter<std::fs::File>>>,
+}
+
+fn format_duration(duration: std::time::Duration) -> String {
+    let seconds = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        seconds / 86400,
+        (seconds / 3600) % 24,
+        (seconds / 60) % 60,
+        seconds % 60,
+    );
+
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m {seconds}s")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+pub fn format_timestamp(time: std::time::SystemTime) -> String {
+    let seconds = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
+
+fn format_hash_rate(hashes_per_second: f64) -> String {
+    const UNITS: [&str; 5] = ["H/s", "kH/s", "MH/s", "GH/s", "TH/s"];
+
+    let mut rate = hashes_per_second;
+    let mut unit = 0;
+
+    while rate >= 1000.0 && unit < UNITS.len() - 1 {
+        rate /= 1000.0;
+        unit += 1;
+    }
+
+    format!("{rate:.2} {}", UNITS[unit])
+}
+
+fn format_workgroups(gpu_workgroups: &[((u32, u32, u32), u32)]) -> String {
+    if gpu_workgroups.is_empty() {
+        return String::from("CPU");
+    }
+
+    gpu_workgroups
+        .iter()
+        .map(|((wx, wy, wz), local_size)| format!("{wx},{wy},{wz} x {local_size}"))
+        .collect::<Vec<String>>()
+        .join(" + ")
+}
+
+fn workgroups_json(
+    gpu_workgroups: &[((u32, u32, u32), u32)],
+) -> (serde_json::Value, serde_json::Value) {
+    let workgroups: Vec<[u32; 3]> = gpu_workgroups
+        .iter()
+        .map(|&((wx, wy, wz), _)| [wx, wy, wz])
+        .collect();
+    let local_size: Vec<u32> = gpu_workgroups
+        .iter()
+        .map(|&(_, local_size)| local_size)
+        .collect();
+    (json!(workgroups), json!(local_size))
+}
+
+/// The shader variant and early exit settings of a benchmark, in parentheses when there are any
+fn benchmark_notes(result: &HasherBenchmark) -> String {
+    let mut notes = vec![];
+    if result.specialized {
+        notes.push(String::from("specialized shader"));
+    }
+    if result.early_match {
+        notes.push(String::from("early match"));
+    }
+    if result.early_exit_period > 0 {
+        notes.push(format!("early exit period {}", result.early_exit_period));
+    }
+    if notes.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", notes.join(", "))
+    }
+}
+
+fn benchmark_json(result: &HasherBenchmark) -> serde_json::Value {
+    let (workgroups, local_size) = workgroups_json(&result.gpu_workgroups);
+    json!({
+        "workgroups": workgroups,
+        "local_size": local_size,
+        "hashes": result.hashes,
+        "elapsed_ms": result.elapsed.as_millis() as u64,
+        "hashes_per_second": result.get_hashes_per_second().round() as u64,
+        "dispatches": result.dispatches,
+        "dispatch_latency_us": result.get_dispatch_latency().as_micros() as u64,
+        "specialized": result.specialized,
+        "early_exit_period": result.early_exit_period,
+        "early_match": result.early_match,
+    })
+}
+
+impl Logger {
+    pub fn new(json: bool, verbosity: Verbosity) -> Self {
+        Self {
+            json,
+            verbosity,
+            tty: std::io::stdout().is_terminal(),
+            progress_line: std::cell::Cell::new(false),
+            progress_time: std::cell::Cell::new(None),
+            log_file: std::cell::RefCell::new(None),
+        }
+    }
+
+    pub fn open_log_file(&self, path: &std::path::Path) -> Result<(), HasherError> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        self.log_file.replace(Some(std::io::LineWriter::new(file)));
+        Ok(())
+    }
+
+    fn record(&self, line: std::fmt::Arguments) {
+        if let Some(log_file) = self.log_file.borrow_mut().as_mut() {
+            let timestamp = format_timestamp(std::time::SystemTime::now());
+            // A failing log file must not abort the search, the same lines also go to stdout
+            for line in line.to_string().lines() {
+                let _ = writeln!(log_file, "[{timestamp}] {line}");
+            }
+            let _ = log_file.flush();
+        }
+    }
+
+    fn log_recorded(&self, level: Verbosity, human: std::fmt::Arguments, json: serde_json::Value) {
+        self.record(human);
+        self.log(level, human, json);
+    }
+
+    fn log(&self, level: Verbosity, human: std::fmt::Arguments, json: serde_json::Value) {
+        if level > self.verbosity {
+            return;
+        }
+        if self.progress_line.replace(false) {
+            println!();
+        }
+        if self.json {
+            println!("{json}");
+        } else {
+            println!("{human}");
+        }
+    }
+
+    pub fn gpu(&self, gpu_info: &wgpu::AdapterInfo) {
+        self.log(
+            Verbosity::Normal,
+            format_args!(
+                "GPU: \"{}\", backend: \"{}\", type: {:?}, vendor: 0x{:04X}, device: 0x{:04X}, driver: \"{}\" \"{}\"",
+                gpu_info.name,
+                gpu_info.backend,
+                gpu_info.device_type,
+                gpu_info.vendor,
+                gpu_info.device,
+                gpu_info.driver,
+                gpu_info.driver_info
+            ),
+            json!({
+                "event": "gpu",
+                "name": gpu_info.name,
+                "backend": gpu_info.backend.to_string(),
+                "type": format!("{:?}", gpu_info.device_type),
+                "vendor": gpu_info.vendor,
+                "device": gpu_info.device,
+                "driver": gpu_info.driver,
+                "driver_info": gpu_info.driver_info,
+            }),
+        );
+    }
+
+    pub fn gpus(&self, adapters: &[wgpu::AdapterInfo], selected: &[usize]) {
+        let objects: Vec<serde_json::Value> = adapters
+            .iter()
+            .enumerate()
+            .map(|(i, info)| {
+                json!({
+                    "index": i,
+                    "name": info.name,
+                    "backend": info.backend.to_string(),
+                    "type": format!("{:?}", info.device_type),
+                    "selected": selected.contains(&i),
+                })
+            })
+            .collect();
+
+        let table = if adapters.is_empty() {
+            String::from("No GPU adapters found")
+        } else {
+            let name_width = adapters
+                .iter()
+                .map(|info| info.name.len())
+                .max()
+                .unwrap_or(0)
+                .max("Name".len());
+
+            let mut table = format!("  Index  {:<name_width$}  Backend  Type", "Name");
+            for (i, info) in adapters.iter().enumerate() {
+                let marker = if selected.contains(&i) { '*' } else { ' ' };
+                table.push_str(&format!(
+                    "\n{marker} {i:<5}  {:<name_width$}  {:<7}  {:?}",
+                    info.name,
+                    info.backend.to_string(),
+                    info.device_type
+                ));
+            }
+            table.push_str("\n(* - adapter selected with current --gpu-adapter value)");
+            table
+        };
+
+        self.log(
+            Verbosity::Quiet,
+            format_args!("{table}"),
+            json!({"event": "gpus", "adapters": objects}),
+        );
+    }
+
+    pub fn cics(&self, cics: &[Cic]) {
+        let names: Vec<String> = cics.iter().map(|cic| cic.get_name()).collect();
+        let name_width = names
+            .iter()
+            .map(|name| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("CIC".len());
+
+        let mut table = format!("{:<name_width$}  Seed  Checksum", "CIC");
+        let mut objects = vec![];
+        for (name, cic) in names.iter().zip(cics) {
+            table.push_str(&format!(
+                "\n{name:<name_width$}  0x{:02X}  0x{:012X}",
+                cic.seed, cic.checksum
+            ));
+            objects.push(json!({"name": name, "seed": cic.seed, "checksum": cic.checksum}));
+        }
+
+        self.log(
+            Verbosity::Quiet,
+            format_args!("{table}"),
+            json!({"event": "cics", "cics": objects}),
+        );
+    }
+
+    pub fn cpu_fallback(&self, threads: usize, slowdown: u32) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: no GPU adapter found, searching on the CPU with {threads} threads instead, expect it to be about {slowdown} times slower than on a discrete GPU"
+            ),
+            json!({"event": "cpu_fallback", "threads": threads, "slowdown": slowdown}),
+        );
+    }
+
+    pub fn slow_adapter_warning(
+        &self,
+        gpu: usize,
+        info: &wgpu::AdapterInfo,
+        discrete_index: usize,
+        discrete_info: &wgpu::AdapterInfo,
+    ) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: GPU {gpu} is {} ({:?}), many times slower than the discrete GPU {} available with --gpu-adapter {discrete_index} (--no-adapter-warning hides this)",
+                info.name, info.device_type, discrete_info.name
+            ),
+            json!({
+                "event": "slow_adapter_warning",
+                "gpu": gpu,
+                "name": info.name,
+                "device_type": format!("{:?}", info.device_type),
+                "discrete_adapter": discrete_index,
+                "discrete_name": discrete_info.name,
+            }),
+        );
+    }
+
+    pub fn device_info(&self, gpu: usize, info: &GPUDeviceInfo) {
+        let mut features = vec![];
+        for (supported, name) in [
+            (info.push_constants, "push constants"),
+            (info.timestamp_query, "timestamp queries"),
+            (info.shader_int64, "shader int64"),
+            (info.pipeline_cache, "pipeline cache"),
+            (info.passthrough, "SPIR-V passthrough"),
+        ] {
+            if supported {
+                features.push(String::from(name));
+            }
+        }
+        if let Some((min, max)) = info.subgroup_size {
+            features.push(format!("subgroups ({min}..{max})"));
+        }
+        let (wx, wy, wz) = info.workgroups;
+        let (min_subgroup_size, max_subgroup_size) = info.subgroup_size.unwrap_or((0, 0));
+        self.log(
+            Verbosity::Verbose,
+            format_args!(
+                "GPU {gpu} device: backend: {}, shader: {}, features: {}, max buffer size: {}, max storage buffer binding size: {}, workgroups: {}, dispatches: {}",
+                info.backend,
+                info.shader,
+                if features.is_empty() {
+                    String::from("none")
+                } else {
+                    features.join(", ")
+                },
+                info.max_buffer_size,
+                info.max_storage_buffer_binding_size,
+                format_workgroups(&[(info.workgroups, info.local_size)]),
+                info.dispatches
+            ),
+            json!({
+                "event": "device_info",
+                "gpu": gpu,
+                "backend": info.backend.to_string(),
+                "shader": info.shader,
+                "passthrough": info.passthrough,
+                "push_constants": info.push_constants,
+                "timestamp_query": info.timestamp_query,
+                "shader_int64": info.shader_int64,
+                "pipeline_cache": info.pipeline_cache,
+                "subgroups": info.subgroup_size.is_some(),
+                "min_subgroup_size": min_subgroup_size,
+                "max_subgroup_size": max_subgroup_size,
+                "max_buffer_size": info.max_buffer_size,
+                "max_storage_buffer_binding_size": info.max_storage_buffer_binding_size,
+                "workgroups": [wx, wy, wz],
+                "local_size": info.local_size,
+                "dispatches": info.dispatches,
+            }),
+        );
+    }
+
+    pub fn gpu_limits(&self, limits: &wgpu::Limits) {
+        self.log(
+            Verbosity::Verbose,
+            format_args!(
+                "GPU limits: max workgroups per dimension: {}, max invocations per workgroup: {}, max push constant size: {}",
+                limits.max_compute_workgroups_per_dimension,
+                limits.max_compute_invocations_per_workgroup,
+                limits.max_push_constant_size
+            ),
+            json!({
+                "event": "gpu_limits",
+                "max_compute_workgroups_per_dimension": limits.max_compute_workgroups_per_dimension,
+                "max_compute_invocations_per_workgroup": limits.max_compute_invocations_per_workgroup,
+                "max_push_constant_size": limits.max_push_constant_size,
+            }),
+        );
+    }
+
+    pub fn pipeline_cache(&self, gpu: usize, pipeline_cache: GPUPipelineCache) {
+        self.log(
+            Verbosity::Verbose,
+            format_args!("GPU {gpu} pipeline cache: {}", pipeline_cache.get_name()),
+            json!({"event": "pipeline_cache", "gpu": gpu, "status": pipeline_cache.get_name()}),
+        );
+    }
+
+    pub fn cpu(&self, threads: usize) {
+        self.log(
+            Verbosity::Normal,
+            format_args!("CPU: {threads} threads"),
+            json!({"event": "cpu", "threads": threads}),
+        );
+    }
+
+    pub fn threads(&self, threads: usize) {
+        self.log(
+            Verbosity::Verbose,
+            format_args!("CPU threads limit: {threads}"),
+            json!({"event": "threads", "threads": threads}),
+        );
+    }
+
+    pub fn auto_workgroups(&self, gpu_workgroups: &[((u32, u32, u32), u32)]) {
+        let (workgroups, local_size) = workgroups_json(gpu_workgroups);
+        self.log(
+            Verbosity::Normal,
+            format_args!("Auto workgroups: {}", format_workgroups(gpu_workgroups)),
+            json!({"event": "auto_workgroups", "workgroups": workgroups, "local_size": local_size}),
+        );
+    }
+
+    pub fn dispatch_size(&self, size: u64) {
+        self.log(
+            Verbosity::Verbose,
+            format_args!("Hashes per dispatch: {size}"),
+            json!({"event": "dispatch_size", "hashes": size}),
+        );
+    }
+
+    pub fn dry_run(&self, plan: &HasherPlan, threads: Option<usize>) {
+        let mut warnings = vec![];
+
+        for word in &plan.shared_words {
+            warnings.push(format!("Y and X bits share ROM word {}", word + 16));
+        }
+        if plan.y_bits_set > 0 {
+            warnings.push(format!(
+                "{} of {} Y bits are already set in the source ROM and will be overwritten",
+                plan.y_bits_set,
+                plan.y_bits.len()
+            ));
+        }
+        if plan.x_bits_set > 0 {
+            warnings.push(format!(
+                "{} of {} X bits are already set in the source ROM and will be overwritten",
+                plan.x_bits_set,
+                plan.x_bits.len()
+            ));
+        }
+
+        let threads_line = match threads {
+            Some(threads) => format!("CPU threads: {threads}"),
+            None => format!("GPU threads per dispatch: {}", plan.dispatch_size),
+        };
+        let warnings_lines: String = warnings
+            .iter()
+            .map(|warning| format!("\nWarning: {warning}"))
+            .collect();
+
+        self.log(
+            Verbosity::Quiet,
+            format_args!(
+                "Search plan:\n  {threads_line}\n  Hashes per dispatch: {}\n  Y values: {} (0x{:08X} - 0x{:08X})\n  X space per Y: {}\n  Total hashes: {}\n  Y bits: {}\n  X bits: {}{warnings_lines}",
+                plan.dispatch_size,
+                (plan.y_end as u64 + 1).saturating_sub(plan.y_init as u64),
+                plan.y_init,
+                plan.y_end,
+                plan.x_size,
+                plan.total_hashes,
+                rom::format_bits(&plan.y_bits),
+                rom::format_bits(&plan.x_bits)
+            ),
+            json!({
+                "event": "dry_run",
+                "threads": threads.unwrap_or(plan.dispatch_size as usize),
+                "dispatch_size": plan.dispatch_size,
+                "y_init": plan.y_init,
+                "y_end": plan.y_end,
+                "x_size": plan.x_size,
+                "total_hashes": plan.total_hashes,
+                "y_bits": rom::format_bits(&plan.y_bits),
+                "x_bits": rom::format_bits(&plan.x_bits),
+                "warnings": warnings,
+            }),
+        );
+    }
+
+    pub fn format_mismatch(&self, format: RomFormat, detected: RomFormat) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: ROM format forced to {} but the header looks like {}",
+                format.get_name(),
+                detected.get_name()
+            ),
+            json!({
+                "event": "format_mismatch",
+                "format": format.get_name(),
+                "detected": detected.get_name(),
+            }),
+        );
+    }
+
+    pub fn no_verify_ignored(&self) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: --no-verify is ignored with --sign, found collisions are verified"
+            ),
+            json!({"event": "no_verify_ignored"}),
+        );
+    }
+
+    pub fn bits(&self, y_bits: &[u32], x_bits: &[u32]) {
+        self.log(
+            Verbosity::Normal,
+            format_args!(
+                "Y bits: {}, X bits: {}",
+                rom::format_bits(y_bits),
+                rom::format_bits(x_bits)
+            ),
+            json!({
+                "event": "bits",
+                "y_bits": rom::format_bits(y_bits),
+                "x_bits": rom::format_bits(x_bits),
+            }),
+        );
+    }
+
+    pub fn config(&self, path: &std::path::Path, values: &[String]) {
+        self.log(
+            Verbosity::Normal,
+            format_args!(
+                "Config file \"{}\": {}",
+                path.display(),
+                if values.is_empty() {
+                    String::from("no values used")
+                } else {
+                    format!("values used for {}", values.join(", "))
+                }
+            ),
+            json!({"event": "config", "path": path.to_string_lossy(), "values": values}),
+        );
+    }
+
+    pub fn target(&self, seed: u8, target_checksum: u64) {
+        self.log_recorded(
+            Verbosity::Normal,
+            format_args!("Target seed and checksum: 0x{seed:02X} 0x{target_checksum:012X}"),
+            json!({"event": "target", "seed": seed, "target_checksum": target_checksum}),
+        );
+    }
+
+    pub fn shard(&self, index: u32, count: u32, y_start: u32, y_end: u32) {
+        self.log_recorded(
+            Verbosity::Normal,
+            format_args!("Shard {index}/{count}: Y range 0x{y_start:08X} - 0x{y_end:08X}"),
+            json!({
+                "event": "shard",
+                "index": index,
+                "count": count,
+                "y_start": y_start,
+                "y_end": y_end,
+            }),
+        );
+    }
+
+    pub fn resumed(&self, y: u32, x: u32) {
+        self.log(
+            Verbosity::Normal,
+            format_args!("Resuming from checkpoint: Y={y} X={x}"),
+            json!({"event": "resumed", "y": y, "x": x}),
+        );
+    }
+
+    pub fn checkpoint(&self, path: &std::path::Path, y: u32, x: u32) {
+        self.log(
+            Verbosity::Verbose,
+            format_args!("Checkpoint saved to \"{}\": Y={y} X={x}", path.display()),
+            json!({"event": "checkpoint", "path": path.to_string_lossy(), "y": y, "x": x}),
+        );
+    }
+
+    pub fn dispatch(&self, y: u32, x: u32, elapsed: std::time::Duration) {
+        self.log(
+            Verbosity::Verbose,
+            format_args!("Y={} X={} dispatch took {:?}", y, x, elapsed),
+            json!({"event": "dispatch", "y": y, "x": x, "elapsed_us": elapsed.as_micros() as u64}),
+        );
+    }
+
+    pub fn gpu_timing(&self, gpu: usize, kernel_time: std::time::Duration, hashes: u64) {
+        let seconds = kernel_time.as_secs_f64();
+        let hashes_per_second = if seconds > 0.0 {
+            hashes as f64 / seconds
+        } else {
+            0.0
+        };
+        self.log(
+            Verbosity::Verbose,
+            format_args!(
+                "GPU {gpu} kernel took {:?}, {}",
+                kernel_time,
+                format_hash_rate(hashes_per_second)
+            ),
+            json!({
+                "event": "gpu_timing",
+                "gpu": gpu,
+                "kernel_us": kernel_time.as_micros() as u64,
+                "hashes": hashes,
+                "hashes_per_second": hashes_per_second.round() as u64,
+            }),
+        );
+    }
+
+    pub fn results_dropped(&self, y: u32, dropped: u32) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: {dropped} more collisions for Y={y:08X} didn't fit in the GPU result buffer and were dropped"
+            ),
+            json!({"event": "results_dropped", "y": y, "dropped": dropped}),
+        );
+    }
+
+    pub fn gpu_downsized(
+        &self,
+        gpu: usize,
+        requested: (u32, u32, u32),
+        gpu_workgroups: ((u32, u32, u32), u32),
+    ) {
+        let (rx, ry, rz) = requested;
+        let ((wx, wy, wz), local_size) = gpu_workgroups;
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: GPU {gpu} ran out of memory with workgroups {rx},{ry},{rz}, using {}",
+                format_workgroups(&[gpu_workgroups])
+            ),
+            json!({
+                "event": "gpu_downsized",
+                "gpu": gpu,
+                "requested": [rx, ry, rz],
+                "workgroups": [wx, wy, wz],
+                "local_size": local_size,
+            }),
+        );
+    }
+
+    pub fn device_reset(&self, y: u32, x: u32, reason: &str) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: GPU device lost ({reason}), recreated it and retrying Y={y} X={x}"
+            ),
+            json!({"event": "device_reset", "y": y, "x": x, "reason": reason}),
+        );
+    }
+
+    pub fn specialize_shader_unsupported(&self) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: --specialize-shader needs the GLSL shader compiled at runtime, continuing with the target checksum read from the input"
+            ),
+            json!({"event": "specialize_shader_unsupported"}),
+        );
+    }
+
+    pub fn glsl_unsupported(&self) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: the GLSL shader needs the glsl feature this build doesn't have, continuing with the WGSL shader"
+            ),
+            json!({"event": "glsl_unsupported"}),
+        );
+    }
+
+    pub fn gpu_timings_unsupported(&self) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: --gpu-timings is not supported by the GPU adapter (no timestamp queries), continuing without it"
+            ),
+            json!({"event": "gpu_timings_unsupported"}),
+        );
+    }
+
+    pub fn round(&self, y: u32, x: u32, elapsed: std::time::Duration, hashes: u64) {
+        self.record_round(y, x, elapsed, hashes);
+        self.log(
+            Verbosity::Normal,
+            format_args!("Y={} X={} took {:?}", y, x, elapsed),
+            json!({
+                "event": "round",
+                "y": y,
+                "x": x,
+                "elapsed_ms": elapsed.as_millis() as u64,
+                "hashes": hashes,
+            }),
+        );
+    }
+
+    pub fn record_round(&self, y: u32, x: u32, elapsed: std::time::Duration, hashes: u64) {
+        self.record(format_args!(
+            "Y={y} X={x} took {elapsed:?}, {hashes} hashes"
+        ));
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn stats(
+        &self,
+        y: u32,
+        x: u32,
+        x_size: u64,
+        dispatch_size: u64,
+        hashes_per_second: f64,
+        average_hashes_per_second: f64,
+        y_round_time: std::time::Duration,
+        elapsed: std::time::Duration,
+    ) {
+        let percent = (x as f64 / x_size as f64) * 100.0;
+        self.log(
+            Verbosity::Normal,
+            format_args!(
+                "Y={y} X={x} ({percent:.1}%), rate: {}, average: {}, dispatch size: {dispatch_size}, Y round wait: {y_round_time:?}, elapsed: {}",
+                format_hash_rate(hashes_per_second),
+                format_hash_rate(average_hashes_per_second),
+                format_duration(elapsed)
+            ),
+            json!({
+                "event": "stats",
+                "y": y,
+                "x": x,
+                "x_size": x_size,
+                "dispatch_size": dispatch_size,
+                "hashes_per_second": hashes_per_second.round() as u64,
+                "average_hashes_per_second": average_hashes_per_second.round() as u64,
+                "y_round_us": y_round_time.as_micros() as u64,
+                "elapsed_ms": elapsed.as_millis() as u64,
+            }),
+        );
+    }
+
+    pub fn progress(&self, stats: &HasherStats, round_completed: bool) {
+        const UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+        if Verbosity::Normal > self.verbosity {
+            return;
+        }
+
+        let updating_line = self.tty && !self.json;
+
+        if !round_completed
+            && (!updating_line
+                || self
+                    .progress_time
+                    .get()
+                    .is_some_and(|time| time.elapsed() < UPDATE_INTERVAL))
+        {
+            return;
+        }
+
+        let eta = stats.get_eta();
+        let percent = if stats.y_total > 0 {
+            (stats.y_done as f64 * 100.0) / stats.y_total as f64
+        } else {
+            100.0
+        };
+
+        if self.json {
+            self.log(
+                Verbosity::Normal,
+                format_args!(""),
+                json!({
+                    "event": "progress",
+                    "y_done": stats.y_done,
+                    "y_total": stats.y_total,
+                    "hashes_per_second": stats.hashes_per_second.round() as u64,
+                    "eta_s": eta.map(|eta| eta.as_secs()),
+                }),
+            );
+            return;
+        }
+
+        let line = format!(
+            "Y {}/{} ({percent:.2}%) | {} | ETA {} (worst case)",
+            stats.y_done,
+            stats.y_total,
+            format_hash_rate(stats.hashes_per_second),
+            eta.map_or(String::from("unknown"), format_duration),
+        );
+
+        if updating_line {
+            print!("\r\x1b[2K{line}");
+            std::io::stdout().flush().ok();
+            self.progress_line.set(true);
+            self.progress_time.set(Some(std::time::Instant::now()));
+        } else {
+            println!("{line}");
+        }
+    }
+
+    pub fn benchmark(&self, result: &HasherBenchmark) {
+        self.log(
+            Verbosity::Quiet,
+            format_args!(
+                "Benchmark {}{}: {} hashes took {:?}, {}, {:?} per dispatch",
+                format_workgroups(&result.gpu_workgroups),
+                benchmark_notes(result),
+                result.hashes,
+                result.elapsed,
+                format_hash_rate(result.get_hashes_per_second()),
+                result.get_dispatch_latency()
+            ),
+            json!({"event": "benchmark", "result": benchmark_json(result)}),
+        );
+    }
+
+    pub fn benchmark_ranking(&self, results: &[HasherBenchmark]) {
+        if self.json {
+            let results: Vec<serde_json::Value> = results.iter().map(benchmark_json).collect();
+            self.log(
+                Verbosity::Quiet,
+                format_args!(""),
+                json!({"event": "benchmark_ranking", "results": results}),
+            );
+            return;
+        }
+
+        let mut table = String::from(
+            "  Rank  Workgroups x local size  Shader       Hash rate           Dispatch latency",
+        );
+        for (i, result) in results.iter().enumerate() {
+            table.push_str(&format!(
+                "\n  {:<4}  {:<23}  {:<11}  {:<18}  {:?}",
+                i + 1,
+                format_workgroups(&result.gpu_workgroups),
+                if result.specialized {
+                    "specialized"
+                } else {
+                    "input"
+                },
+                format_hash_rate(result.get_hashes_per_second()),
+                result.get_dispatch_latency()
+            ));
+        }
+
+        self.log(
+            Verbosity::Quiet,
+            format_args!("{table}"),
+            serde_json::Value::Null,
+        );
+    }
+
+    pub fn shader_check(&self, shader: &str, samples: u32) {
+        self.log(
+            Verbosity::Quiet,
+            format_args!("Shader check passed: {shader} matches the CPU on {samples} X values"),
+            json!({"event": "shader_check", "shader": shader, "samples": samples}),
+        );
+    }
+
+    pub fn dump_state(&self, dump: &HasherStateDump) {
+        let hex = |words: &[u32]| {
+            words
+                .iter()
+                .map(|word| format!("{word:08X}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let cpu = &dump.cpu;
+        let gpu_lines: String = dump
+            .gpus
+            .iter()
+            .enumerate()
+            .map(|(gpu, state)| {
+                let differing: Vec<String> = (0..16)
+                    .filter(|&i| state[i] != cpu.final_state[i])
+                    .map(|i| i.to_string())
+                    .collect();
+                let comparison = if differing.is_empty() {
+                    String::from("matches the CPU")
+                } else {
+                    format!("differs from the CPU in words {}", differing.join(", "))
+                };
+                format!("\nGPU {gpu} final state: {} ({comparison})", hex(state))
+            })
+            .collect();
+
+        self.log(
+            Verbosity::Quiet,
+            format_args!(
+                "CPU round state:      {}\nCPU final state:      {}\nCPU finalize buffers: {}\nCPU checksum: 0x{:012X}{gpu_lines}",
+                hex(&cpu.round_state),
+                hex(&cpu.final_state),
+                hex(&cpu.finalize_buffers),
+                cpu.checksum
+            ),
+            json!({
+                "event": "dump_state",
+                "round_state": cpu.round_state,
+                "final_state": cpu.final_state,
+                "finalize_buffers": cpu.finalize_buffers,
+                "checksum": cpu.checksum,
+                "gpu_states": dump.gpus,
+            }),
+        );
+    }
+
+    pub fn self_test(&self, samples: u32, seed: u64) {
+        self.log(
+            Verbosity::Normal,
+            format_args!(
+                "GPU self-test passed: {samples} random Y and X values of seed 0x{seed:016X} match the CPU"
+            ),
+            json!({"event": "self_test", "samples": samples, "seed": format!("{seed:016X}")}),
+        );
+    }
+
+    pub fn differential_test_start(&self, random_seed: u64) {
+        self.log(
+            Verbosity::Normal,
+            format_args!("Differential test with random seed 0x{random_seed:016X}"),
+            json!({
+                "event": "differential_test_start",
+                "random_seed": format!("{random_seed:016X}"),
+            }),
+        );
+    }
+
+    pub fn differential_case(&self, case: &DifferentialCase) {
+        self.log(
+            Verbosity::Verbose,
+            format_args!("Differential {case} matches"),
+            json!({
+                "event": "differential_case",
+                "case_seed": format!("{:016X}", case.case_seed),
+            }),
+        );
+    }
+
+    pub fn differential_test(&self, cases: u64, elapsed: std::time::Duration) {
+        self.log(
+            Verbosity::Quiet,
+            format_args!(
+                "Differential test passed: {cases} random cases match the CPU in {elapsed:.1?}"
+            ),
+            json!({
+                "event": "differential_test",
+                "cases": cases,
+                "elapsed_ms": elapsed.as_millis() as u64,
+            }),
+        );
+    }
+
+    pub fn known_answer_test(&self, vectors: usize) {
+        self.log(
+            Verbosity::Normal,
+            format_args!("Known-answer self-test passed: {vectors} built-in checksums match"),
+            json!({"event": "known_answer_test", "vectors": vectors}),
+        );
+    }
+
+    pub fn known_answer_ignored(&self, error: &HasherError) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!("Warning: {error}"),
+            json!({"event": "known_answer_ignored", "error": error.to_string()}),
+        );
+    }
+
+    pub fn auto_tune(&self, results: &[HasherBenchmark]) {
+        let Some(best) = results.first() else {
+            return;
+        };
+        self.log(
+            Verbosity::Normal,
+            format_args!(
+                "Auto-tune picked workgroups x local size: {} ({}, best of {} configurations)",
+                format_workgroups(&best.gpu_workgroups),
+                format_hash_rate(best.get_hashes_per_second()),
+                results.len()
+            ),
+            json!({
+                "event": "auto_tune",
+                "best": benchmark_json(best),
+                "results": results.iter().map(benchmark_json).collect::<Vec<_>>(),
+            }),
+        );
+    }
+
+    pub fn verify(&self, y: u32, x: u32, checksum: u64, target_checksum: u64) {
+        let matches = checksum == target_checksum;
+        self.log(
+            Verbosity::Quiet,
+            format_args!(
+                "Y={y:08X} X={x:08X} checksum: 0x{checksum:012X}, target: 0x{target_checksum:012X}, {}",
+                if matches { "match" } else { "mismatch" }
+            ),
+            json!({
+                "event": "verify",
+                "y": y,
+                "x": x,
+                "checksum": checksum,
+                "target_checksum": target_checksum,
+                "match": matches,
+            }),
+        );
+    }
+
+    pub fn compute(&self, checksum: u64, target_checksum: u64) {
+        let matches = checksum == target_checksum;
+        self.log(
+            Verbosity::Quiet,
+            format_args!(
+                "ROM checksum: 0x{checksum:012X}, target: 0x{target_checksum:012X}, {}",
+                if matches { "match" } else { "mismatch" }
+            ),
+            json!({
+                "event": "compute",
+                "checksum": checksum,
+                "target_checksum": target_checksum,
+                "match": matches,
+            }),
+        );
+    }
+
+    pub fn seed_scan(&self, seeds: &[u8], target_checksum: u64) {
+        let list: Vec<String> = seeds.iter().map(|seed| format!("0x{seed:02X}")).collect();
+        self.log(
+            Verbosity::Quiet,
+            format_args!(
+                "Seeds matching checksum 0x{target_checksum:012X}: {}",
+                if seeds.is_empty() {
+                    String::from("none")
+                } else {
+                    list.join(", ")
+                }
+            ),
+            json!({"event": "seed_scan", "target_checksum": target_checksum, "seeds": seeds}),
+        );
+    }
+
+    pub fn cic_detected(&self, cic: &str) {
+        self.log(
+            Verbosity::Normal,
+            format_args!("Detected CIC {cic} from the bootcode in the ROM"),
+            json!({"event": "cic_detected", "cic": cic}),
+        );
+    }
+
+    pub fn cic_mismatch(&self, selected: &str, detected: &str) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: --cic {selected} was given but the ROM carries the stock {detected} bootcode, the target checksum is for {selected}"
+            ),
+            json!({"event": "cic_mismatch", "cic": selected, "detected": detected}),
+        );
+    }
+
+    pub fn already_matching(&self) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!("ROM already matches the target checksum, nothing to search"),
+            json!({"event": "already_matching"}),
+        );
+    }
+
+    pub fn found(&self, y: u32, x: u32, seed: u8, target_checksum: u64, verified: bool) {
+        let unverified = if verified {
+            ""
+        } else {
+            " (unverified, not checked on the CPU)"
+        };
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!("Found collision: Y={y:08X} X={x:08X}{unverified}"),
+            json!({
+                "event": "found",
+                "y": y,
+                "x": x,
+                "seed": seed,
+                "target_checksum": target_checksum,
+                "verified": verified,
+            }),
+        );
+    }
+
+    pub fn found_all(&self, collisions: &[(u32, u32)]) {
+        let lines: Vec<String> = collisions
+            .iter()
+            .map(|(y, x)| format!("  Y={y:08X} X={x:08X}"))
+            .collect();
+        let objects: Vec<serde_json::Value> = collisions
+            .iter()
+            .map(|(y, x)| json!({"y": y, "x": x}))
+            .collect();
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Found {} collisions:\n{}",
+                collisions.len(),
+                lines.join("\n")
+            ),
+            json!({"event": "found_all", "collisions": objects}),
+        );
+    }
+
+    pub fn result_entry(&self, path: &std::path::Path, index: i64, entry: &ResultEntry) {
+        self.log_recorded(
+            Verbosity::Normal,
+            format_args!(
+                "Result {index} of \"{}\": ROM \"{}\" found at {}, seed 0x{:02X}, checksum 0x{:012X}, Y bits {}, X bits {}",
+                path.display(),
+                entry.rom,
+                entry.timestamp,
+                entry.seed,
+                entry.target_checksum,
+                entry.y_bits,
+                entry.x_bits
+            ),
+            json!({
+                "event": "result_entry",
+                "path": path.to_string_lossy(),
+                "index": index,
+                "rom": entry.rom,
+                "timestamp": entry.timestamp,
+                "seed": entry.seed,
+                "target_checksum": entry.target_checksum,
+                "y_bits": entry.y_bits,
+                "x_bits": entry.x_bits,
+                "y": entry.y,
+                "x": entry.x,
+            }),
+        );
+    }
+
+    pub fn result_ipl3_mismatch(&self, rom: &str) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Warning: the IPL3 differs from the one of \"{rom}\" the result was found for"
+            ),
+            json!({"event": "result_ipl3_mismatch", "rom": rom}),
+        );
+    }
+
+    pub fn header_crc(&self, crc1: u32, crc2: u32) {
+        self.log_recorded(
+            Verbosity::Normal,
+            format_args!("Header CRCs updated: CRC1 0x{crc1:08X}, CRC2 0x{crc2:08X}"),
+            json!({"event": "header_crc", "crc1": crc1, "crc2": crc2}),
+        );
+    }
+
+    pub fn backup(&self, path: &std::path::Path) {
+        self.log_recorded(
+            Verbosity::Normal,
+            format_args!("ROM backed up to \"{}\"", path.display()),
+            json!({"event": "backup", "path": path.to_string_lossy()}),
+        );
+    }
+
+    pub fn signed(&self, path: &std::path::Path, checksum: u64) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "ROM has been successfully signed: \"{}\", read back with checksum 0x{checksum:012X}",
+                path.display()
+            ),
+            json!({"event": "signed", "path": path.to_string_lossy(), "checksum": checksum}),
+        );
+    }
+
+    pub fn time_limit(&self, seed: u8, y: u32, x: u32) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Time limit reached at seed 0x{seed:02X} Y={y} X={x}, resume with --y-init {y} --x-init {x}"
+            ),
+            json!({"event": "time_limit", "seed": seed, "y": y, "x": x}),
+        );
+    }
+
+    pub fn interrupted(&self, seed: u8, y: u32, x: u32) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!(
+                "Interrupted at seed 0x{seed:02X} Y={y} X={x}, resume with --y-init {y} --x-init {x}"
+            ),
+            json!({"event": "interrupted", "seed": seed, "y": y, "x": x}),
+        );
+    }
+
+    pub fn round_limit(&self, rounds: u32, y_next: u32) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!("Round limit of {rounds} reached, continue with --y-init {y_next}"),
+            json!({"event": "round_limit", "rounds": rounds, "y_next": y_next}),
+        );
+    }
+
+    pub fn end(&self, y_init: u32, y_end: u32) {
+        self.log_recorded(
+            Verbosity::Quiet,
+            format_args!("Searched Y range: 0x{y_init:08X} - 0x{y_end:08X}\nSorry nothing"),
+            json!({"event": "end", "y_init": y_init, "y_end": y_end}),
+        );
+    }
+
+    pub fn error(&self, error: &HasherError) {
+        self.record(format_args!("IPL3 hasher error: {error}"));
+        if self.progress_line.replace(false) {
+            println!();
+        }
+        if self.json {
+            eprintln!(
+                "{}",
+                json!({"event": "error", "message": error.to_string()})
+            );
+        } else {
+            eprintln!("IPL3 hasher error: {error}");
+        }
+    }
+
+    pub fn exit(&self, status: &str, code: u8) {
+        if !self.json {
+            return;
+        }
+        self.log(
+            Verbosity::Quiet,
+            format_args!(""),
+            json!({"event": "exit", "status": status, "code": code}),
+        );
+    }
+}
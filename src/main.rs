@@ -1,82 +1,1237 @@
 mod cli;
-mod cpu;
-mod error;
-mod gpu;
-mod hasher;
+mod config;
+mod interrupt;
+mod logger;
 
-fn print_round_execution_time(y: u32, time: std::time::Instant) {
-    println!("Y={} took {:?}", y, time.elapsed());
+use ipl3hasher_new::{
+    checkpoint, differential, error, gpu, hasher, header, known_answer, results, rom,
+};
+
+const AUTO_TUNE_DISPATCHES: u32 = 2;
+const SHADER_CHECK_SAMPLES: u32 = 4096;
+
+const SELF_TEST_SAMPLES: u32 = 8;
+
+const DIFFERENTIAL_TEST_ITERATIONS: u64 = 100;
+
+// Rough speed of a discrete GPU over the CPU search on a desktop processor, for --fallback-cpu
+const CPU_FALLBACK_SLOWDOWN: u32 = 100;
+
+/// How the run ended, reported as the process exit code (errors exit with code 1)
+#[derive(Clone, Copy)]
+enum ExitStatus {
+    Success,
+    Found,
+    Mismatch,
+    TimeLimit,
+    RoundLimit,
+    Exhausted,
+    Interrupted,
+}
+
+impl ExitStatus {
+    const ERROR_CODE: u8 = 1;
+
+    fn get_code(self) -> u8 {
+        match self {
+            Self::Success | Self::Found => 0,
+            Self::Mismatch => 5,
+            Self::TimeLimit => 2,
+            Self::RoundLimit => 3,
+            Self::Exhausted => 4,
+            Self::Interrupted => 130,
+        }
+    }
+
+    fn get_name(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Found => "found",
+            Self::Mismatch => "mismatch",
+            Self::TimeLimit => "time_limit",
+            Self::RoundLimit => "round_limit",
+            Self::Exhausted => "exhausted",
+            Self::Interrupted => "interrupted",
+        }
+    }
+}
+
+fn list_gpus(
+    backends: wgpu::Backends,
+    power_preference: wgpu::PowerPreference,
+    allow_software_adapter: bool,
+    gpu_adapters: &[gpu::GPUAdapterSelector],
+    logger: &logger::Logger,
+) -> Result<(), error::HasherError> {
+    let adapters = gpu::GPUHasher::list_gpu_adapters(
+        backends,
+        power_preference,
+        allow_software_adapter,
+        false,
+    )?;
+    let selected_adapters: Vec<usize> = gpu_adapters
+        .iter()
+        .filter_map(|gpu_adapter| gpu::GPUHasher::select_gpu_adapter(&adapters, gpu_adapter).ok())
+        .collect();
+    let adapters: Vec<wgpu::AdapterInfo> =
+        adapters.iter().map(|adapter| adapter.get_info()).collect();
+
+    logger.gpus(&adapters, &selected_adapters);
+
+    Ok(())
+}
+
+fn benchmark_configurations(
+    limits: &wgpu::Limits,
+    workgroups: (u32, u32, u32),
+) -> Vec<((u32, u32, u32), u32)> {
+    const MIN_SIZE: u32 = 32;
+    const MAX_WORKGROUPS_X: u32 = 1024;
+
+    let (_, wy, wz) = workgroups;
+    let max_local_size = limits
+        .max_compute_invocations_per_workgroup
+        .min(limits.max_compute_workgroup_size_x);
+    let max_workgroups_x = limits
+        .max_compute_workgroups_per_dimension
+        .min(MAX_WORKGROUPS_X);
+
+    let powers_of_two = |max: u32| {
+        std::iter::successors(Some(MIN_SIZE), |size| size.checked_mul(2))
+            .take_while(move |size| *size <= max)
+    };
+
+    let mut configurations = vec![];
+
+    for local_size in powers_of_two(max_local_size) {
+        for wx in powers_of_two(max_workgroups_x) {
+            let dispatch_size = wx as u64 * wy as u64 * wz as u64 * local_size as u64;
+            if dispatch_size <= (1u64 << 32) {
+                configurations.push(((wx, wy, wz), local_size));
+            }
+        }
+    }
+
+    configurations
+}
+
+/// The largest benchmark configuration for each local size, a short list worth timing before a search
+fn auto_tune_configurations(
+    limits: &wgpu::Limits,
+    workgroups: (u32, u32, u32),
+) -> Vec<((u32, u32, u32), u32)> {
+    let mut configurations = benchmark_configurations(limits, workgroups);
+    configurations.reverse();
+    configurations.dedup_by_key(|(_, local_size)| *local_size);
+    configurations
+}
+
+fn check_output(output: Option<&std::path::Path>, force: bool) -> Result<(), error::HasherError> {
+    match output {
+        Some(output) if output.exists() && !force => {
+            Err(error::HasherError::OutputFileExists(output.to_path_buf()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The path the source ROM is copied to before it's signed in place, None when it's signed to the
+/// output or without a backup
+fn backup_path(
+    rom: &std::path::Path,
+    output: Option<&std::path::Path>,
+    backup: &cli::BackupArgs,
+) -> Result<Option<std::path::PathBuf>, error::HasherError> {
+    if output.is_some() || backup.no_backup {
+        return Ok(None);
+    }
+
+    let mut path = rom.as_os_str().to_owned();
+    path.push(".bak");
+    let path = std::path::PathBuf::from(path);
+
+    if path.exists() && !backup.force_backup {
+        return Err(error::HasherError::BackupFileExists(path));
+    }
+
+    Ok(Some(path))
+}
+
+/// The ROM file to sign, a copy written to the output or the source itself once it's backed up
+fn output_rom(
+    rom: std::path::PathBuf,
+    output: Option<std::path::PathBuf>,
+    force: bool,
+    backup: &cli::BackupArgs,
+    logger: &logger::Logger,
+) -> Result<std::path::PathBuf, error::HasherError> {
+    match output {
+        Some(output) => {
+            check_output(Some(&output), force)?;
+            std::fs::copy(&rom, &output)?;
+            Ok(output)
+        }
+        None => {
+            if let Some(backup) = backup_path(&rom, None, backup)? {
+                std::fs::copy(&rom, &backup)?;
+                logger.backup(&backup);
+            }
+            Ok(rom)
+        }
+    }
+}
+
+/// The results file entry of a collision found in the ROM, time stamped now
+#[allow(clippy::too_many_arguments)]
+fn result_entry(
+    rom: &std::path::Path,
+    ipl3: &[u8],
+    seed: u8,
+    target_checksum: u64,
+    y_bits: &[u32],
+    x_bits: &[u32],
+    y: u32,
+    x: u32,
+) -> results::ResultEntry {
+    results::ResultEntry {
+        timestamp: logger::format_timestamp(std::time::SystemTime::now()),
+        rom: rom
+            .file_name()
+            .map_or(rom.to_string_lossy(), |name| name.to_string_lossy())
+            .into_owned(),
+        ipl3_sha256: results::ResultEntry::get_ipl3_sha256(ipl3),
+        seed,
+        target_checksum,
+        y_bits: rom::format_bits_spec(y_bits),
+        x_bits: rom::format_bits_spec(x_bits),
+        y,
+        x,
+    }
+}
+
+fn log_gpu_downsized(hasher: &mut hasher::Hasher, logger: &logger::Logger) {
+    let gpu_workgroups = hasher.get_gpu_workgroups();
+    for (gpu, requested) in hasher.take_gpu_downsized() {
+        logger.gpu_downsized(gpu, requested, gpu_workgroups[gpu]);
+    }
+}
+
+fn backends(backend: &cli::BackendType) -> wgpu::Backends {
+    match backend {
+        cli::BackendType::Auto => wgpu::Backends::all(),
+        cli::BackendType::Vulkan => wgpu::Backends::VULKAN,
+        cli::BackendType::Dx12 => wgpu::Backends::DX12,
+        cli::BackendType::Metal => wgpu::Backends::METAL,
+        cli::BackendType::Gl => wgpu::Backends::GL,
+    }
+}
+
+fn power_preference(power_preference: &cli::PowerPreferenceType) -> wgpu::PowerPreference {
+    match power_preference {
+        cli::PowerPreferenceType::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        cli::PowerPreferenceType::LowPower => wgpu::PowerPreference::LowPower,
+        cli::PowerPreferenceType::None => wgpu::PowerPreference::None,
+    }
+}
+
+fn gpu_shader(
+    shader: cli::ShaderType,
+    shader_path: Option<std::path::PathBuf>,
+) -> gpu::GPUHasherShader {
+    match (shader, shader_path) {
+        (_, Some(shader_path)) => gpu::GPUHasherShader::GlslFile(shader_path),
+        (cli::ShaderType::Auto, None) => gpu::GPUHasherShader::Auto,
+        (cli::ShaderType::Glsl, None) => gpu::GPUHasherShader::Glsl,
+        (cli::ShaderType::Wgsl, None) => gpu::GPUHasherShader::Wgsl,
+    }
+}
+
+fn rom_layout(
+    args: &cli::RomArgs,
+    logger: &logger::Logger,
+) -> Result<rom::RomLayout, error::HasherError> {
+    let format = match args.format {
+        cli::FormatType::Auto => None,
+        cli::FormatType::Z64 => Some(rom::RomFormat::Z64),
+        cli::FormatType::V64 => Some(rom::RomFormat::V64),
+        cli::FormatType::N64 => Some(rom::RomFormat::N64),
+    };
+
+    // The stream can be read only once, raw IPL3 and format mismatch detection need the file
+    let stdin = rom::is_stdin(&args.rom);
+    let ipl3_words = args.ipl3_words as usize;
+    let raw_ipl3 = args.raw_ipl3 || (!stdin && rom::is_raw_ipl3(&args.rom, ipl3_words * 4)?);
+
+    if !raw_ipl3
+        && !stdin
+        && let Some(format) = format
+        && let Some(detected) = rom::RomFormat::detect_file(&args.rom)?
+        && detected != format
+    {
+        logger.format_mismatch(format, detected);
+    }
+
+    Ok(if raw_ipl3 {
+        rom::RomLayout {
+            format: format.or(Some(rom::RomFormat::Z64)),
+            ipl3_offset: 0,
+            ipl3_words,
+        }
+    } else {
+        rom::RomLayout {
+            format,
+            ipl3_offset: args.ipl3_offset,
+            ipl3_words,
+        }
+    })
+}
+
+fn target(args: &cli::TargetArgs, ipl3: &[u8], logger: &logger::Logger) -> (u8, u64) {
+    let detected = cli::detect_cic(ipl3);
+
+    // An explicit --cic always wins, but a ROM carrying another stock bootcode likely won't boot
+    let cic = match (args.cic, detected) {
+        (Some(cic), Some(detected)) => {
+            if cic != (detected.seed, detected.checksum) {
+                let selected = cli::CICS
+                    .iter()
+                    .find(|known| (known.seed, known.checksum) == cic)
+                    .map(|known| known.get_name())
+                    .unwrap_or_default();
+                logger.cic_mismatch(&selected, &detected.get_name());
+            }
+            cic
+        }
+        (Some(cic), None) => cic,
+        (None, Some(detected)) => {
+            if args.custom_seed.is_none() {
+                logger.cic_detected(&detected.get_name());
+            }
+            (detected.seed, detected.checksum)
+        }
+        (None, None) => cli::find_cic(cli::DEFAULT_CIC)
+            .map(|cic| (cic.seed, cic.checksum))
+            .expect("the default CIC is a known one"),
+    };
+
+    match (args.custom_seed, args.custom_checksum) {
+        (Some(seed), Some(checksum)) => (seed, checksum),
+        (None, Some(checksum)) => (cic.0, checksum),
+        _ => cic,
+    }
 }
 
-fn run_hasher() -> Result<(), error::HasherError> {
-    let cli::Cli {
-        rom,
+fn run_verify(
+    args: cli::VerifyArgs,
+    logger: &logger::Logger,
+) -> Result<ExitStatus, error::HasherError> {
+    let layout = rom_layout(&args.rom, logger)?;
+    let ipl3 = hasher::Hasher::read_ipl3(&args.rom.rom, layout)?;
+    let (seed, target_checksum) = target(&args.target, &ipl3, logger);
+    let cli::PairArgs { y, x } = args.pair;
+
+    let checksum =
+        hasher::Hasher::verify_ipl3(&ipl3, seed, &args.bits.y_bits, &args.bits.x_bits, y, x)?;
+    logger.verify(y, x, checksum, target_checksum);
+
+    Ok(if checksum == target_checksum {
+        ExitStatus::Success
+    } else {
+        ExitStatus::Mismatch
+    })
+}
+
+fn run_sign(
+    args: cli::SignArgs,
+    logger: &logger::Logger,
+) -> Result<ExitStatus, error::HasherError> {
+    let layout = rom_layout(&args.rom, logger)?;
+    check_output(args.output.as_deref(), args.force)?;
+    backup_path(&args.rom.rom, args.output.as_deref(), &args.backup)?;
+    let ipl3 = hasher::Hasher::read_ipl3(&args.rom.rom, layout)?;
+
+    // The entry of the results file brings the whole search with it, the pair alone is checked
+    // against the target and bits of the arguments
+    let (seed, target_checksum, y_bits, x_bits, y, x) = match (args.pair, args.result) {
+        (_, Some(index)) => {
+            let results_file = args
+                .results_file
+                .expect("results file is required for --result");
+            let entry = results::ResultEntry::load(&results_file, index)?;
+            logger.result_entry(&results_file, index, &entry);
+            if entry.ipl3_sha256 != results::ResultEntry::get_ipl3_sha256(&ipl3) {
+                logger.result_ipl3_mismatch(&entry.rom);
+            }
+
+            let entry_bits = |bits: &str, parser: fn(&str) -> Result<Vec<u32>, String>| {
+                parser(bits).map_err(|error| {
+                    error::HasherError::ResultsParseError(format!("entry {index}: {error}"))
+                })
+            };
+            (
+                entry.seed,
+                entry.target_checksum,
+                entry_bits(&entry.y_bits, cli::y_bits_parser)?,
+                entry_bits(&entry.x_bits, cli::x_bits_parser)?,
+                entry.y,
+                entry.x,
+            )
+        }
+        (Some(cli::PairArgs { y, x }), None) => {
+            let (seed, target_checksum) = target(&args.target, &ipl3, logger);
+            (
+                seed,
+                target_checksum,
+                args.bits.y_bits,
+                args.bits.x_bits,
+                y,
+                x,
+            )
+        }
+        (None, None) => unreachable!("--y or --result is required"),
+    };
+
+    let checksum = hasher::Hasher::verify_ipl3(&ipl3, seed, &y_bits, &x_bits, y, x)?;
+    logger.verify(y, x, checksum, target_checksum);
+
+    if checksum != target_checksum {
+        return Ok(ExitStatus::Mismatch);
+    }
+
+    let interrupt = interrupt::Interrupt::install()?;
+    let signing = interrupt.lock_signing();
+    let signed_rom = output_rom(args.rom.rom, args.output, args.force, &args.backup, logger)?;
+    hasher::Hasher::sign_rom(&signed_rom, layout, &y_bits, &x_bits, y, x)?;
+    if args.fix_header_crc {
+        let (crc1, crc2) = header::fix_crc(&signed_rom, layout.format, seed)?;
+        logger.header_crc(crc1, crc2);
+    }
+    drop(signing);
+    let checksum = hasher::Hasher::verify_rom(&signed_rom, layout, seed, target_checksum)?;
+    logger.signed(&signed_rom, checksum);
+
+    Ok(ExitStatus::Success)
+}
+
+fn run_compute(
+    args: cli::ComputeArgs,
+    logger: &logger::Logger,
+) -> Result<ExitStatus, error::HasherError> {
+    let layout = rom_layout(&args.rom, logger)?;
+    let ipl3 = hasher::Hasher::read_ipl3(&args.rom.rom, layout)?;
+    let (seed, target_checksum) = target(&args.target, &ipl3, logger);
+
+    if args.seed_scan {
+        let seeds = hasher::Hasher::seed_scan_ipl3(&ipl3, target_checksum);
+        logger.seed_scan(&seeds, target_checksum);
+        return Ok(if seeds.is_empty() {
+            ExitStatus::Mismatch
+        } else {
+            ExitStatus::Success
+        });
+    }
+
+    let checksum = hasher::Hasher::compute_ipl3(&ipl3, seed);
+    logger.compute(checksum, target_checksum);
+
+    Ok(ExitStatus::Success)
+}
+
+fn run_search(
+    args: cli::SearchArgs,
+    logger: &logger::Logger,
+) -> Result<ExitStatus, error::HasherError> {
+    let cli::SearchArgs {
+        rom: rom_args,
         sign,
-        cic,
-        y_bits,
+        find_all,
+        no_verify,
+        output,
+        force,
+        fix_header_crc,
+        backup,
+        results_file,
+        target: target_args,
+        seed_scan,
+        bits: cli::BitsArgs { y_bits, x_bits },
         y_init,
-        gpu_adapter,
+        x_init,
+        y_end,
+        order,
+        order_seed,
+        shard,
+        cpu_only,
+        fallback_cpu,
+        threads,
+        gpu:
+            cli::GpuArgs {
+                gpu_adapter,
+                backend,
+                power_preference: power_preference_type,
+                allow_software_adapter,
+            },
         workgroups,
+        local_size,
         shader,
-    } = cli::parse();
-    let (seed, target_checksum) = cic;
+        shader_path,
+        checkpoint,
+        checkpoint_interval,
+        resume,
+        time_limit,
+        max_rounds,
+        stats_interval,
+        progress,
+        dry_run,
+        benchmark,
+        benchmark_dispatches,
+        benchmark_sweep,
+        shader_check,
+        dump_state,
+        dump_y,
+        dump_x,
+        auto_tune,
+        gpu_timings,
+        max_device_resets,
+        max_dispatch_ms,
+        target_dispatch_ms,
+        specialize_shader,
+        no_shader_int64,
+        no_spirv_passthrough,
+        no_adapter_warning,
+        gpu_debug,
+        no_warmup,
+        skip_self_test,
+        self_test_seed,
+        early_exit_period,
+        x_stride,
+        y_batch,
+        benchmark_early_match,
+    } = args;
+
+    let layout = rom_layout(&rom_args, logger)?;
+    let rom = rom_args.rom;
+
+    check_output(output.as_deref(), force)?;
+    if sign {
+        backup_path(&rom, output.as_deref(), &backup)?;
+    }
+
+    let ipl3 = hasher::Hasher::read_ipl3(&rom, layout)?;
+    let (seed, target_checksum) = target(&target_args, &ipl3, logger);
+
+    let (y_init, y_end) = match shard {
+        Some((index, count)) => {
+            let (y_start, y_end) = hasher::Hasher::get_shard_range(&y_bits, index, count);
+            logger.shard(index, count, y_start, y_end);
+            (y_start, Some(y_end))
+        }
+        None => (y_init, y_end),
+    };
+
+    let y_order = match order {
+        cli::OrderType::Sequential => hasher::YOrder::Sequential,
+        cli::OrderType::Random => {
+            hasher::YOrder::Random(order_seed.expect("order seed is required for random order"))
+        }
+        cli::OrderType::Gray => hasher::YOrder::Gray,
+    };
+
+    let threads = threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1)
+    });
+
+    logger.threads(threads);
 
-    let shader = match shader {
-        cli::ShaderType::Glsl => gpu::GPUHasherShader::Glsl,
-        cli::ShaderType::Wgsl => gpu::GPUHasherShader::Wgsl,
+    let shader = gpu_shader(shader, shader_path);
+    if !cfg!(feature = "glsl")
+        && matches!(
+            shader,
+            gpu::GPUHasherShader::Glsl | gpu::GPUHasherShader::GlslFile(_)
+        )
+    {
+        logger.glsl_unsupported();
+    }
+    let glsl_shader = match &shader {
+        gpu::GPUHasherShader::GlslFile(_) => shader.clone(),
+        _ => gpu::GPUHasherShader::Glsl,
+    };
+
+    let adapters = if cpu_only {
+        vec![]
+    } else {
+        gpu::GPUHasher::list_gpu_adapters(
+            backends(&backend),
+            power_preference(&power_preference_type),
+            allow_software_adapter,
+            gpu_debug,
+        )?
     };
 
+    let devices = if cpu_only {
+        hasher::HasherDevices::Cpu { threads }
+    } else {
+        hasher::HasherDevices::Gpu {
+            adapters,
+            selectors: gpu_adapter,
+            workgroups: workgroups.clone(),
+            local_size,
+            shader,
+            debug: gpu_debug,
+        }
+    };
+    let devices = if fallback_cpu {
+        devices.with_cpu_fallback(threads)
+    } else {
+        devices
+    };
+
+    let cpu_fallback = !cpu_only && matches!(devices, hasher::HasherDevices::Cpu { .. });
+    if cpu_fallback {
+        logger.cpu_fallback(threads, CPU_FALLBACK_SLOWDOWN);
+    }
+
     let mut hasher = hasher::Hasher::new(
-        rom.clone().into(),
-        gpu_adapter,
-        workgroups,
-        shader,
-        seed,
-        target_checksum,
-        y_bits.clone(),
-        y_init,
+        &ipl3,
+        hasher::HasherSearch {
+            seed,
+            target_checksum,
+            y_bits: y_bits.clone(),
+            x_bits: x_bits.clone(),
+            y_init,
+            y_end,
+            y_order,
+            x_init,
+        },
+        devices,
     )?;
 
+    hasher.set_gpu_max_dispatch_time(
+        (max_dispatch_ms > 0).then(|| std::time::Duration::from_millis(max_dispatch_ms)),
+    );
+
+    log_gpu_downsized(&mut hasher, logger);
+    hasher.set_gpu_passthrough(!no_spirv_passthrough);
+
     let gpu_info = hasher.get_gpu_info();
 
-    println!(
-        "GPU: \"{}\", backend: \"{}\"",
-        gpu_info.name, gpu_info.backend
-    );
+    for gpu_info in &gpu_info {
+        logger.gpu(gpu_info);
+    }
+
+    // Hybrid graphics laptops often list the integrated GPU first, which is many times slower, as
+    // is a software adapter picked up with --allow-software-adapter
+    if !no_adapter_warning {
+        let adapter_list = hasher.get_gpu_adapter_list();
+        let discrete_gpu = adapter_list.iter().enumerate().find(|(_, info)| {
+            info.device_type == wgpu::DeviceType::DiscreteGpu && !gpu_info.contains(info)
+        });
+
+        if let Some((index, discrete_info)) = discrete_gpu {
+            for (gpu, info) in gpu_info.iter().enumerate() {
+                if matches!(
+                    info.device_type,
+                    wgpu::DeviceType::IntegratedGpu
+                        | wgpu::DeviceType::Cpu
+                        | wgpu::DeviceType::Other
+                ) {
+                    logger.slow_adapter_warning(gpu, info, index, discrete_info);
+                }
+            }
+        }
+    }
+
+    for gpu_limits in hasher.get_gpu_limits() {
+        logger.gpu_limits(&gpu_limits);
+    }
+
+    for (gpu, device_info) in hasher.get_gpu_device_info().iter().enumerate() {
+        logger.device_info(gpu, device_info);
+    }
+
+    for (gpu, pipeline_cache) in hasher.get_gpu_pipeline_cache().into_iter().enumerate() {
+        logger.pipeline_cache(gpu, pipeline_cache);
+    }
+
+    if workgroups
+        .iter()
+        .any(|workgroups| matches!(workgroups, gpu::GPUWorkgroupsSelector::Auto))
+        && !hasher.get_gpu_workgroups().is_empty()
+    {
+        logger.auto_workgroups(&hasher.get_gpu_workgroups());
+    }
+
+    if let Some(threads) = hasher.get_cpu_threads() {
+        logger.cpu(threads);
+    }
 
-    println!("Target seed and checksum: 0x{seed:02X} 0x{target_checksum:012X}");
+    logger.dispatch_size(hasher.get_dispatch_size());
 
-    loop {
-        let time = std::time::Instant::now();
+    logger.bits(&y_bits, &x_bits);
 
-        let y_current = hasher.get_y();
+    hasher.set_x_stride(x_stride);
+
+    if dry_run {
+        if let Some(resume) = &resume {
+            hasher.restore_checkpoint(&checkpoint::Checkpoint::load(resume)?)?;
+        }
+        hasher.self_check()?;
+        logger.dry_run(&hasher.get_plan(), hasher.get_cpu_threads());
+        return Ok(ExitStatus::Success);
+    }
+
+    hasher.set_gpu_shader_int64(!no_shader_int64);
+
+    if shader_check {
+        // Without the glsl feature the GLSL shader is the WGSL one
+        let shaders = if cfg!(feature = "glsl") {
+            vec![glsl_shader, gpu::GPUHasherShader::Wgsl]
+        } else {
+            vec![gpu::GPUHasherShader::Wgsl]
+        };
+        for shader in shaders {
+            hasher.shader_check(&shader, SHADER_CHECK_SAMPLES)?;
+            logger.shader_check(shader.get_name(), SHADER_CHECK_SAMPLES);
+        }
+        return Ok(ExitStatus::Success);
+    }
+
+    if dump_state {
+        let dump = hasher.dump_state(
+            dump_y.expect("--y is required with --dump-state"),
+            dump_x.unwrap_or(0),
+        )?;
+        logger.dump_state(&dump);
+        return Ok(ExitStatus::Success);
+    }
+
+    let specialize_shader = specialize_shader && {
+        let supported = hasher.set_gpu_specialized(true);
+        if !supported {
+            logger.specialize_shader_unsupported();
+        }
+        supported
+    };
+
+    hasher.set_gpu_early_exit_period(early_exit_period);
+    hasher.set_y_batch(y_batch);
+
+    // A miscompiled shader would otherwise only show up as a verify error hours in, or never
+    if !skip_self_test && !hasher.get_gpu_info().is_empty() {
+        let seed = self_test_seed.unwrap_or_else(|| {
+            std::hash::BuildHasher::hash_one(&std::collections::hash_map::RandomState::new(), 0)
+        });
+        hasher.self_test(SELF_TEST_SAMPLES, seed)?;
+        logger.self_test(SELF_TEST_SAMPLES, seed);
+    }
+
+    if benchmark {
+        let configurations = match (
+            hasher.get_gpu_limits().first(),
+            hasher.get_gpu_workgroups().first(),
+        ) {
+            (Some(gpu_limits), Some((workgroups, _))) if benchmark_sweep => {
+                benchmark_configurations(gpu_limits, *workgroups)
+                    .into_iter()
+                    .map(Some)
+                    .collect()
+            }
+            _ => vec![None],
+        };
 
-        match hasher.compute_round()? {
-            hasher::HasherResult::Found(y, x) => {
-                print_round_execution_time(y_current, time);
-                println!("Found collision: Y={y:08X} X={x:08X}");
-                if sign {
-                    hasher::Hasher::sign_rom(rom.into(), y_bits, y, x)?;
-                    println!("ROM has been successfully signed");
+        let mut results = vec![];
+
+        for gpu_workgroups in configurations {
+            if let Some((workgroups, local_size)) = gpu_workgroups {
+                hasher.set_gpu_workgroups(workgroups, local_size)?;
+            }
+            // Both variants are measured on the same configuration to compare them side by side
+            let variants: &[bool] = if specialize_shader {
+                &[false, true]
+            } else {
+                &[false]
+            };
+            let early_exit_periods: &[u32] = if benchmark_early_match && early_exit_period > 0 {
+                &[0, early_exit_period]
+            } else {
+                &[early_exit_period]
+            };
+            for &specialized in variants {
+                hasher.set_gpu_specialized(specialized);
+                for &early_exit_period in early_exit_periods {
+                    hasher.set_gpu_early_exit_period(early_exit_period);
+                    let result = if benchmark_early_match {
+                        hasher.benchmark_early_match(benchmark_dispatches)?
+                    } else {
+                        hasher.benchmark(benchmark_dispatches)?
+                    };
+                    logger.benchmark(&result);
+                    results.push(result);
                 }
-                return Ok(());
             }
-            hasher::HasherResult::Continue => {
-                print_round_execution_time(y_current, time);
+        }
+
+        if benchmark_sweep {
+            results.sort_by(|a, b| {
+                b.get_hashes_per_second()
+                    .total_cmp(&a.get_hashes_per_second())
+            });
+            logger.benchmark_ranking(&results);
+        }
+
+        return Ok(ExitStatus::Success);
+    }
+
+    if auto_tune
+        && let (Some(gpu_limits), Some((workgroups, _))) = (
+            hasher.get_gpu_limits().first(),
+            hasher.get_gpu_workgroups().first(),
+        )
+    {
+        let mut results = vec![];
+
+        // Benchmark dispatches use an unmatchable checksum, a find is reported as a GPU error
+        for (workgroups, local_size) in auto_tune_configurations(gpu_limits, *workgroups) {
+            hasher.set_gpu_workgroups(workgroups, local_size)?;
+            results.push(hasher.benchmark(AUTO_TUNE_DISPATCHES)?);
+        }
+
+        results.sort_by(|a, b| {
+            b.get_hashes_per_second()
+                .total_cmp(&a.get_hashes_per_second())
+        });
+
+        if let Some((workgroups, local_size)) = results[0].gpu_workgroups.first() {
+            hasher.set_gpu_workgroups(*workgroups, *local_size)?;
+        }
+        logger.auto_tune(&results);
+        logger.dispatch_size(hasher.get_dispatch_size());
+    }
+
+    // Many drivers compile the shaders and make the buffers resident only on the first dispatch,
+    // which would count in the timings of the first Y round
+    if !no_warmup {
+        hasher.warm_up()?;
+    }
+
+    hasher.set_gpu_target_dispatch_time(target_dispatch_ms.map(std::time::Duration::from_millis));
+
+    if gpu_timings && !hasher.enable_gpu_timings() {
+        logger.gpu_timings_unsupported();
+    }
+
+    let seeds = if seed_scan { 0..=u8::MAX } else { seed..=seed };
+
+    let checkpoint = checkpoint.or(resume.clone());
+    let checkpoint_interval = std::time::Duration::from_secs(checkpoint_interval);
+    let mut checkpoint_time = std::time::Instant::now();
+
+    let mut collisions = vec![];
+    let mut found = false;
+    let mut rounds = 0;
+
+    let search_time = std::time::Instant::now();
+    let stats_interval = std::time::Duration::from_secs(stats_interval);
+    let mut stats_time = search_time;
+    let mut stats_hashes = 0;
+
+    hasher.set_deadline(time_limit.map(|time_limit| std::time::Instant::now() + time_limit));
+
+    let interrupt = interrupt::Interrupt::install()?;
+    hasher.set_interrupt(Some(interrupt.get_flag()));
+
+    // An unverified collision must never end up in a signed ROM
+    let verify = !no_verify || sign;
+    if no_verify && sign {
+        logger.no_verify_ignored();
+    }
+    hasher.set_verify(verify);
+    hasher.set_verify_threads(threads);
+    hasher.set_max_device_resets(max_device_resets);
+
+    for seed in seeds {
+        if seed_scan {
+            hasher.set_seed(seed);
+            hasher.set_y_position(0);
+            hasher.set_x_offset(x_init);
+        }
+
+        logger.target(seed, target_checksum);
+
+        if hasher.is_rom_matching() {
+            logger.already_matching();
+            return Ok(ExitStatus::Success);
+        }
+
+        if let Some(resume) = &resume {
+            hasher.restore_checkpoint(&checkpoint::Checkpoint::load(resume)?)?;
+            logger.resumed(hasher.get_y(), hasher.get_x_offset());
+        }
+
+        let y_start = hasher.get_y_start();
+
+        let mut round_time = std::time::Instant::now();
+        let mut round_x = hasher.get_x_offset();
+
+        loop {
+            let dispatch_time = std::time::Instant::now();
+
+            let y_current = hasher.get_y();
+            let x_current = hasher.get_x_offset();
+
+            let result = hasher.compute_round()?;
+
+            if !matches!(
+                result,
+                hasher::HasherResult::DeviceReset(_)
+                    | hasher::HasherResult::Downsized
+                    | hasher::HasherResult::End
+                    | hasher::HasherResult::TimeLimit
+                    | hasher::HasherResult::Interrupted
+            ) {
+                logger.dispatch(y_current, x_current, dispatch_time.elapsed());
+            }
+
+            if gpu_timings {
+                for (gpu, kernel_time) in hasher.take_gpu_kernel_times().into_iter().enumerate() {
+                    if let Some((kernel_time, hashes)) = kernel_time {
+                        logger.gpu_timing(gpu, kernel_time, hashes);
+                    }
+                }
             }
-            hasher::HasherResult::End => {
-                break;
+
+            if let Some(checkpoint) = &checkpoint
+                && checkpoint_time.elapsed() >= checkpoint_interval
+            {
+                hasher.get_checkpoint().save(checkpoint)?;
+                logger.checkpoint(checkpoint, hasher.get_y(), hasher.get_x_offset());
+                checkpoint_time = std::time::Instant::now();
+            }
+
+            if !stats_interval.is_zero() && stats_time.elapsed() >= stats_interval {
+                let total_hashes = hasher.get_total_hashes();
+                logger.stats(
+                    hasher.get_y(),
+                    hasher.get_x_offset(),
+                    hasher.get_x_size(),
+                    hasher.get_dispatch_size(),
+                    (total_hashes - stats_hashes) as f64 / stats_time.elapsed().as_secs_f64(),
+                    total_hashes as f64 / search_time.elapsed().as_secs_f64(),
+                    hasher.get_stats().y_round_time,
+                    search_time.elapsed(),
+                );
+                stats_time = std::time::Instant::now();
+                stats_hashes = total_hashes;
+            }
+
+            match result {
+                hasher::HasherResult::Found(y, x_results, dropped) if find_all => {
+                    for &x in &x_results {
+                        logger.found(y, x, seed, target_checksum, verify);
+                        if verify && let Some(results_file) = &results_file {
+                            result_entry(
+                                &rom,
+                                &ipl3,
+                                seed,
+                                target_checksum,
+                                &y_bits,
+                                &x_bits,
+                                y,
+                                x,
+                            )
+                            .append(results_file)?;
+                        }
+                        collisions.push((y, x));
+                    }
+                    if dropped > 0 {
+                        logger.results_dropped(y, dropped);
+                    }
+                    hasher.skip_found(y, x_results[x_results.len() - 1]);
+                }
+                hasher::HasherResult::Found(y, x_results, _) => {
+                    let x = x_results[0];
+                    logger.round(
+                        y_current,
+                        round_x,
+                        round_time.elapsed(),
+                        hasher.get_round_hashes(),
+                    );
+                    logger.found(y, x, seed, target_checksum, verify);
+                    if verify && let Some(results_file) = &results_file {
+                        result_entry(&rom, &ipl3, seed, target_checksum, &y_bits, &x_bits, y, x)
+                            .append(results_file)?;
+                    }
+                    if sign {
+                        let signing = interrupt.lock_signing();
+                        let signed_rom = output_rom(rom, output, force, &backup, logger)?;
+                        hasher::Hasher::sign_rom(&signed_rom, layout, &y_bits, &x_bits, y, x)?;
+                        if fix_header_crc {
+                            let (crc1, crc2) = header::fix_crc(&signed_rom, layout.format, seed)?;
+                            logger.header_crc(crc1, crc2);
+                        }
+                        drop(signing);
+                        let checksum =
+                            hasher::Hasher::verify_rom(&signed_rom, layout, seed, target_checksum)?;
+                        logger.signed(&signed_rom, checksum);
+                    }
+                    return Ok(ExitStatus::Found);
+                }
+                hasher::HasherResult::DeviceReset(reason) => {
+                    logger.device_reset(hasher.get_y(), hasher.get_x_offset(), &reason);
+                }
+                hasher::HasherResult::Downsized => {
+                    log_gpu_downsized(&mut hasher, logger);
+                    logger.dispatch_size(hasher.get_dispatch_size());
+                }
+                hasher::HasherResult::Progress => {
+                    if progress {
+                        logger.progress(&hasher.get_stats(), false);
+                    }
+                }
+                hasher::HasherResult::Continue => {
+                    if progress {
+                        logger.progress(&hasher.get_stats(), true);
+                        logger.record_round(
+                            y_current,
+                            round_x,
+                            round_time.elapsed(),
+                            hasher.get_round_hashes(),
+                        );
+                    } else {
+                        logger.round(
+                            y_current,
+                            round_x,
+                            round_time.elapsed(),
+                            hasher.get_round_hashes(),
+                        );
+                    }
+                    round_time = std::time::Instant::now();
+                    round_x = hasher.get_x_offset();
+
+                    rounds += 1;
+                    if let Some(max_rounds) = max_rounds
+                        && rounds >= max_rounds
+                        && !hasher.is_y_finished()
+                    {
+                        if find_all && !collisions.is_empty() {
+                            logger.found_all(&collisions);
+                        }
+                        logger.round_limit(rounds, hasher.get_y());
+                        return Ok(ExitStatus::RoundLimit);
+                    }
+                }
+                hasher::HasherResult::TimeLimit => {
+                    if let Some(checkpoint) = &checkpoint {
+                        hasher.get_checkpoint().save(checkpoint)?;
+                        logger.checkpoint(checkpoint, hasher.get_y(), hasher.get_x_offset());
+                    }
+                    if find_all && !collisions.is_empty() {
+                        logger.found_all(&collisions);
+                    }
+                    logger.time_limit(seed, hasher.get_y(), hasher.get_x_offset());
+                    return Ok(ExitStatus::TimeLimit);
+                }
+                hasher::HasherResult::Interrupted => {
+                    if let Some(checkpoint) = &checkpoint {
+                        hasher.get_checkpoint().save(checkpoint)?;
+                        logger.checkpoint(checkpoint, hasher.get_y(), hasher.get_x_offset());
+                    }
+                    if find_all && !collisions.is_empty() {
+                        logger.found_all(&collisions);
+                    }
+                    logger.interrupted(seed, hasher.get_y(), hasher.get_x_offset());
+                    return Ok(ExitStatus::Interrupted);
+                }
+                hasher::HasherResult::End => {
+                    break;
+                }
             }
         }
+
+        if find_all && !collisions.is_empty() {
+            logger.found_all(&collisions);
+            collisions.clear();
+            found = true;
+        } else {
+            logger.end(y_start, hasher.get_y_end());
+        }
     }
 
-    println!("Sorry nothing");
+    Ok(if found {
+        ExitStatus::Found
+    } else {
+        ExitStatus::Exhausted
+    })
+}
 
-    Ok(())
+fn run_differential_test(
+    args: cli::DifferentialTestArgs,
+    logger: &logger::Logger,
+) -> Result<ExitStatus, error::HasherError> {
+    let cli::DifferentialTestArgs {
+        gpu:
+            cli::GpuArgs {
+                gpu_adapter,
+                backend,
+                power_preference: power_preference_type,
+                allow_software_adapter,
+            },
+        workgroups,
+        local_size,
+        shader,
+        iterations,
+        duration,
+        random_seed,
+        no_spirv_passthrough,
+    } = args;
+
+    let adapters = gpu::GPUHasher::list_gpu_adapters(
+        backends(&backend),
+        power_preference(&power_preference_type),
+        allow_software_adapter,
+        false,
+    )?;
+
+    // The GPUs only take the IPL3 length from the hasher, every case brings its own image and bits
+    let ipl3_words = differential::IPL3_WORDS as u32;
+    let mut hasher = hasher::Hasher::new(
+        &vec![0; differential::IPL3_WORDS * 4],
+        hasher::HasherSearch {
+            seed: 0,
+            target_checksum: 0,
+            y_bits: vec![],
+            x_bits: (((ipl3_words - 1) * 32)..(ipl3_words * 32)).collect(),
+            y_init: 0,
+            y_end: None,
+            y_order: hasher::YOrder::Sequential,
+            x_init: 0,
+        },
+        hasher::HasherDevices::Gpu {
+            adapters,
+            selectors: gpu_adapter,
+            workgroups: workgroups.clone(),
+            local_size,
+            shader: gpu_shader(shader, None),
+            debug: false,
+        },
+    )?;
+
+    log_gpu_downsized(&mut hasher, logger);
+    hasher.set_gpu_passthrough(!no_spirv_passthrough);
+
+    for gpu_info in hasher.get_gpu_info() {
+        logger.gpu(&gpu_info);
+    }
+
+    if workgroups
+        .iter()
+        .any(|workgroups| matches!(workgroups, gpu::GPUWorkgroupsSelector::Auto))
+    {
+        logger.auto_workgroups(&hasher.get_gpu_workgroups());
+    }
+
+    let random_seed = random_seed.unwrap_or_else(|| {
+        std::hash::BuildHasher::hash_one(&std::collections::hash_map::RandomState::new(), 0)
+    });
+    let iterations = iterations.unwrap_or(if duration.is_some() {
+        u64::MAX
+    } else {
+        DIFFERENTIAL_TEST_ITERATIONS
+    });
+    let start = std::time::Instant::now();
+
+    logger.differential_test_start(random_seed);
+
+    let mut case_seed = random_seed;
+    let mut cases = 0;
+
+    while cases < iterations && duration.is_none_or(|duration| start.elapsed() < duration) {
+        let case = differential::DifferentialCase::new(case_seed);
+        hasher.differential_test(&case)?;
+        logger.differential_case(&case);
+
+        case_seed = case_seed.wrapping_add(1);
+        cases += 1;
+    }
+
+    logger.differential_test(cases, start.elapsed());
+
+    Ok(ExitStatus::Success)
+}
+
+fn run_hasher(cli: cli::Cli, logger: &logger::Logger) -> Result<ExitStatus, error::HasherError> {
+    if let Some(log_file) = &cli.log_file {
+        logger.open_log_file(log_file)?;
+    }
+
+    if cli.self_test {
+        match known_answer::check_all() {
+            Ok(vectors) => logger.known_answer_test(vectors),
+            Err(error) if cli.ignore_self_test => logger.known_answer_ignored(&error),
+            Err(error) => return Err(error),
+        }
+    }
+
+    let dry_run = matches!(&cli.command, cli::Command::Search(args) if args.dry_run);
+
+    if let Some(config) = &cli.config
+        && (cli.verbose || dry_run)
+    {
+        logger.config(config, &cli.config_values);
+    }
+
+    match cli.command {
+        cli::Command::Search(args) => run_search(*args, logger),
+        cli::Command::Sign(args) => run_sign(args, logger),
+        cli::Command::Verify(args) => run_verify(args, logger),
+        cli::Command::Compute(args) => run_compute(args, logger),
+        cli::Command::DifferentialTest(args) => run_differential_test(args, logger),
+        cli::Command::Gpus(args) => {
+            list_gpus(
+                backends(&args.gpu.backend),
+                power_preference(&args.gpu.power_preference),
+                args.gpu.allow_software_adapter,
+                &args.gpu.gpu_adapter,
+                logger,
+            )?;
+            Ok(ExitStatus::Success)
+        }
+        cli::Command::Cics => {
+            logger.cics(cli::CICS);
+            Ok(ExitStatus::Success)
+        }
+        cli::Command::Completions { .. } => unreachable!("completions are printed in main"),
+    }
 }
 
-fn main() {
-    if let Err(error) = run_hasher() {
-        println!("IPL3 hasher error: {error}");
+fn main() -> std::process::ExitCode {
+    let cli = cli::parse();
+
+    // The script alone, without the exit event of --json
+    if let cli::Command::Completions { shell } = cli.command {
+        cli::print_completions(shell);
+        return std::process::ExitCode::SUCCESS;
+    }
+
+    let verbosity = if cli.quiet {
+        logger::Verbosity::Quiet
+    } else if cli.verbose {
+        logger::Verbosity::Verbose
+    } else {
+        logger::Verbosity::Normal
+    };
+    let logger = logger::Logger::new(cli.json, verbosity);
+
+    match run_hasher(cli, &logger) {
+        Ok(status) => {
+            logger.exit(status.get_name(), status.get_code());
+            std::process::ExitCode::from(status.get_code())
+        }
+        Err(error) => {
+            logger.error(&error);
+            logger.exit("error", ExitStatus::ERROR_CODE);
+            std::process::ExitCode::from(ExitStatus::ERROR_CODE)
+        }
     }
 }
@@ -0,0 +1,96 @@
+use crate::error::HasherError;
+use sha2::Digest;
+use std::io::{Read, Seek, Write};
+
+/// A verified collision, one JSON object per line of the results file
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ResultEntry {
+    /// When the collision was found, ISO 8601 in UTC
+    pub timestamp: String,
+    /// The file name of the ROM searched
+    pub rom: String,
+    /// SHA-256 of the IPL3 as read from the ROM, in hex
+    pub ipl3_sha256: String,
+    /// The CIC seed
+    pub seed: u8,
+    /// The checksum searched for
+    pub target_checksum: u64,
+    /// The Y bits in the format of the bits arguments
+    pub y_bits: String,
+    /// The X bits in the format of the bits arguments
+    pub x_bits: String,
+    /// The Y value found
+    pub y: u32,
+    /// The X value found
+    pub x: u32,
+}
+
+impl ResultEntry {
+    /// The SHA-256 of the IPL3 in the format of the entries
+    pub fn get_ipl3_sha256(ipl3: &[u8]) -> String {
+        sha2::Sha256::digest(ipl3)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Parses every entry of a results file written by `append`, the blank lines are skipped
+    pub fn load_all(path: &std::path::Path) -> Result<Vec<Self>, HasherError> {
+        std::fs::read_to_string(path)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line.trim()).map_err(|error| {
+                    HasherError::ResultsParseError(format!("line {}: {error}", i + 1))
+                })
+            })
+            .collect()
+    }
+
+    /// The entry at the index of the results file, counting from the end when negative (-1 is the
+    /// last one)
+    pub fn load(path: &std::path::Path, index: i64) -> Result<Self, HasherError> {
+        let mut entries = Self::load_all(path)?;
+        let count = entries.len();
+
+        let position = if index < 0 {
+            count.checked_sub(index.unsigned_abs() as usize)
+        } else {
+            Some(index as usize).filter(|&position| position < count)
+        };
+
+        position
+            .map(|position| entries.swap_remove(position))
+            .ok_or(HasherError::ResultsParseError(format!(
+                "no entry at index {index}, the file has {count}"
+            )))
+    }
+
+    /// Appends the entry to the results file as a line of its own and flushes it to the disk, a
+    /// line left unterminated by an earlier run is ended first
+    pub fn append(&self, path: &std::path::Path) -> Result<(), HasherError> {
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+
+        let mut line = String::new();
+        if f.metadata()?.len() > 0 {
+            let mut last = [0u8; 1];
+            f.seek(std::io::SeekFrom::End(-1))?;
+            f.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.push('\n');
+            }
+        }
+        line.push_str(&serde_json::to_string(self).map_err(std::io::Error::from)?);
+        line.push('\n');
+
+        f.write_all(line.as_bytes())?;
+        f.sync_data()?;
+
+        Ok(())
+    }
+}
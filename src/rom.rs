@@ -0,0 +1,286 @@
+use crate::error::HasherError;
+use std::io::{Read, Seek, Write};
+
+/// The IPL3 length hashed by default, in 32-bit words
+pub const IPL3_WORDS: usize = 1008;
+
+/// ROM path that reads the ROM from stdin instead of a file
+pub const STDIN_PATH: &str = "-";
+
+/// The path is `STDIN_PATH`
+pub fn is_stdin(path: &std::path::Path) -> bool {
+    path == std::path::Path::new(STDIN_PATH)
+}
+
+/// The file is exactly the IPL3 size, a raw IPL3 without the rest of the ROM
+pub fn is_raw_ipl3(path: &std::path::Path, ipl3_size: usize) -> Result<bool, HasherError> {
+    Ok(std::fs::metadata(path)?.len() == ipl3_size as u64)
+}
+
+/// Returns the byte index into the IPL3 and the bit number in that byte for a Y or X bit offset
+pub fn bit_position(offset: u32) -> (usize, u32) {
+    ((offset / 8) as usize, 7 - (offset % 8))
+}
+
+/// Stores the value in the IPL3 bits, most significant bit first
+pub fn apply_bits(ipl3: &mut [u8], bits: &[u32], value: u32) {
+    store_bits(ipl3, bits, value, bit_position);
+}
+
+/// Stores the value in the bits of the data, the position gives the byte index and bit number of
+/// every bit offset
+fn store_bits(data: &mut [u8], bits: &[u32], value: u32, position: impl Fn(u32) -> (usize, u32)) {
+    for (i, offset) in bits.iter().enumerate() {
+        let (index, bit) = position(*offset);
+        let shift = bits.len() - 1;
+        let value = ((value >> (shift - i)) & (1 << 0)) as u8;
+
+        data[index] &= !(1 << bit);
+        data[index] |= value << bit;
+    }
+}
+
+/// The ROM word index, first bit and last bit of every run of consecutive bits
+fn bit_runs(bits: &[u32]) -> Vec<(u32, u32, u32)> {
+    let mut runs: Vec<(u32, u32, u32)> = vec![];
+
+    for offset in bits {
+        let (index, bit) = ((offset / 32) + 16, 31 - (offset % 32));
+        match runs.last_mut() {
+            Some((run_index, _, last)) if *run_index == index && *last == bit + 1 => *last = bit,
+            _ => runs.push((index, bit, bit)),
+        }
+    }
+
+    runs
+}
+
+/// The bits as index[first..last] runs like in the bits arguments, each with the ROM byte offset of
+/// its word
+pub fn format_bits(bits: &[u32]) -> String {
+    bit_runs(bits)
+        .iter()
+        .map(|(index, first, last)| format!("{index}[{first}..{last}] (0x{:03X})", index * 4))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// The bits in the format of the bits arguments, which parses back to the same bits
+pub fn format_bits_spec(bits: &[u32]) -> String {
+    bit_runs(bits)
+        .iter()
+        .map(|(index, first, last)| format!("{index}[{first}..{last}]"))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Checks that the Y bits are before the last word of the IPL3 and the X bits within it
+pub fn check_bits(ipl3_words: usize, y_bits: &[u32], x_bits: &[u32]) -> Result<(), HasherError> {
+    for (name, bits, words) in [("Y", y_bits, ipl3_words - 1), ("X", x_bits, ipl3_words)] {
+        if let Some(&offset) = bits.iter().find(|&&offset| offset as usize >= words * 32) {
+            return Err(HasherError::BitsOutOfBounds(
+                name,
+                offset / 32 + 16,
+                words as u32 + 15,
+                ipl3_words,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Where the IPL3 is in the ROM and how long it is
+#[derive(Clone, Copy)]
+pub struct RomLayout {
+    /// The byte order, detected from the header when None
+    pub format: Option<RomFormat>,
+    /// The byte offset of the IPL3 in the file, 0x40 in a ROM
+    pub ipl3_offset: u64,
+    /// The IPL3 length in words
+    pub ipl3_words: usize,
+}
+
+impl RomLayout {
+    /// The IPL3 length in bytes
+    pub fn get_ipl3_size(&self) -> usize {
+        self.ipl3_words * 4
+    }
+
+    /// Returns the byte offset into the file and the bit number in that byte for a Y or X bit
+    /// offset, in the byte order of the file. Signing goes through it, so the bits land where the
+    /// IPL3 was read and searched from
+    pub fn get_file_position(&self, format: RomFormat, offset: u32) -> (u64, u32) {
+        let (index, bit) = bit_position(offset);
+
+        (self.ipl3_offset + format.get_byte_index(index) as u64, bit)
+    }
+
+    /// Stores the Y and X values in the IPL3 bits of the ROM file, in place with a single write
+    pub(crate) fn write_bits(
+        &self,
+        f: &mut std::fs::File,
+        values: &[(&[u32], u32)],
+    ) -> Result<(), HasherError> {
+        let format = RomFormat::read(f, self.format)?;
+
+        RomFormat::check_ipl3_offset(f, self.ipl3_offset, self.get_ipl3_size())?;
+
+        let mut ipl3 = vec![0u8; self.get_ipl3_size()];
+
+        f.seek(std::io::SeekFrom::Start(self.ipl3_offset))?;
+        f.read_exact(&mut ipl3)?;
+
+        for (bits, value) in values {
+            store_bits(&mut ipl3, bits, *value, |offset| {
+                let (position, bit) = self.get_file_position(format, offset);
+                ((position - self.ipl3_offset) as usize, bit)
+            });
+        }
+
+        f.seek(std::io::SeekFrom::Start(self.ipl3_offset))?;
+        f.write_all(&ipl3)?;
+
+        Ok(())
+    }
+}
+
+/// The byte order of a ROM file
+#[derive(Clone, Copy, PartialEq)]
+pub enum RomFormat {
+    /// Big endian, the order the IPL3 is hashed in
+    Z64,
+    /// 16-bit byteswapped
+    V64,
+    /// 32-bit little endian
+    N64,
+}
+
+impl RomFormat {
+    /// The byte order of the ROM from its first word, None if it isn't a ROM header
+    pub fn detect(header: [u8; 4]) -> Option<Self> {
+        match u32::from_be_bytes(header) {
+            0x80371240 => Some(Self::Z64),
+            0x37804012 => Some(Self::V64),
+            0x40123780 => Some(Self::N64),
+            _ => None,
+        }
+    }
+
+    /// Reads the IPL3 from a stream without seeking, the data before the IPL3 offset is skipped
+    pub(crate) fn read_ipl3_stream(
+        f: impl Read,
+        layout: RomLayout,
+    ) -> Result<Vec<u8>, HasherError> {
+        let end = layout.ipl3_offset + layout.get_ipl3_size() as u64;
+
+        let mut header = [0u8; 4];
+        let mut ipl3 = vec![0u8; layout.get_ipl3_size()];
+        let mut buffer = [0u8; 4096];
+        let mut received = 0;
+
+        let mut f = f.take(end);
+
+        loop {
+            let length = match f.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(length) => length,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            };
+
+            for (i, byte) in buffer[..length].iter().enumerate() {
+                let position = received + i as u64;
+                if position < header.len() as u64 {
+                    header[position as usize] = *byte;
+                }
+                if position >= layout.ipl3_offset {
+                    ipl3[(position - layout.ipl3_offset) as usize] = *byte;
+                }
+            }
+
+            received += length as u64;
+        }
+
+        if received < end {
+            return Err(HasherError::RomTruncated(
+                received,
+                layout.ipl3_offset,
+                layout.get_ipl3_size() as u64,
+            ));
+        }
+
+        let format = match layout.format {
+            Some(format) => format,
+            None => Self::detect(header)
+                .ok_or(HasherError::UnknownRomFormat(u32::from_be_bytes(header)))?,
+        };
+
+        format.swap(&mut ipl3);
+
+        Ok(ipl3)
+    }
+
+    /// The byte order of the ROM file from its header
+    pub fn detect_file(path: &std::path::Path) -> Result<Option<Self>, HasherError> {
+        let mut header = [0u8; 4];
+
+        std::fs::File::open(path)?.read_exact(&mut header)?;
+
+        Ok(Self::detect(header))
+    }
+
+    /// The name reported in the output
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Z64 => "z64 (big-endian)",
+            Self::V64 => "v64 (byteswapped)",
+            Self::N64 => "n64 (little-endian)",
+        }
+    }
+
+    pub(crate) fn read(f: &mut std::fs::File, format: Option<Self>) -> Result<Self, HasherError> {
+        let mut header = [0u8; 4];
+
+        f.seek(std::io::SeekFrom::Start(0))?;
+        f.read_exact(&mut header)?;
+
+        match format {
+            Some(format) => Ok(format),
+            None => Self::detect(header)
+                .ok_or(HasherError::UnknownRomFormat(u32::from_be_bytes(header))),
+        }
+    }
+
+    fn check_ipl3_offset(
+        f: &std::fs::File,
+        offset: u64,
+        ipl3_size: usize,
+    ) -> Result<(), HasherError> {
+        let size = f.metadata()?.len();
+
+        if offset.saturating_add(ipl3_size as u64) > size {
+            return Err(HasherError::IPL3OutOfBounds(offset, size, ipl3_size as u64));
+        }
+
+        Ok(())
+    }
+
+    /// The index in this format of the byte at the index in big-endian, works in both directions
+    pub fn get_byte_index(&self, index: usize) -> usize {
+        match self {
+            Self::Z64 => index,
+            Self::V64 => index ^ 1,
+            Self::N64 => index ^ 3,
+        }
+    }
+
+    /// Converts 4-byte aligned data between this format and big-endian, works in both directions
+    pub fn swap(&self, data: &mut [u8]) {
+        match self {
+            Self::Z64 => {}
+            Self::V64 => data.chunks_exact_mut(2).for_each(|chunk| chunk.swap(0, 1)),
+            Self::N64 => data.chunks_exact_mut(4).for_each(|chunk| chunk.reverse()),
+        }
+    }
+}
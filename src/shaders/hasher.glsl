@@ -1,27 +1,81 @@
 #version 450 core
+#ifdef SHADER_INT64
 #extension GL_ARB_gpu_shader_int64 : enable
+#endif
 
-#define LOCAL_WORKGROUP_SIZE 256
+#ifndef LOCAL_SIZE_X
+#define LOCAL_SIZE_X 256
+#endif
 
 #define MAGIC 0x6C078965
 
-layout(local_size_x = LOCAL_WORKGROUP_SIZE, local_size_y = 1, local_size_z = 1) in;
+#define MAX_RESULTS 16
 
+// The IPL3 length in words, the X word is the last one
+#ifndef IPL3_WORDS
+#define IPL3_WORDS 1008
+#endif
+
+// Invocations return early once a match was found in the dispatch, the X bits loop checks again
+// every EARLY_EXIT_PERIOD words (0 disables it, the check costs a memory read)
+#ifndef EARLY_EXIT_PERIOD
+#define EARLY_EXIT_PERIOD 0
+#endif
+
+// DUMP_STATE makes the invocation at the X offset write its state before the final mix into the
+// results instead of searching, for the CPU to compare word by word
+
+layout(local_size_x = LOCAL_SIZE_X, local_size_y = 1, local_size_z = 1) in;
+
+#ifdef INPUT_UNIFORM_BUFFER
+layout(binding = 3) uniform input_data {
+#else
 layout(push_constant) uniform input_data {
+#endif
     uint target_hi;
     uint target_lo;
     uint y_offset;
     uint x_offset;
-    uint state_in[16];
+    uint y_top_shift;
+    uint y_top_complement;
+    uint y_bottom_shift;
+    uint y_bottom_complement;
+    uint x_stride;
 };
 
-layout(binding = 0) buffer output_data {
-    int found;
-    uint x_result;
+// The target checksum can be compiled in as constants instead of read from the input
+#ifndef TARGET_HI
+#define TARGET_HI target_hi
+#endif
+
+#ifndef TARGET_LO
+#define TARGET_LO target_lo
+#endif
+
+layout(binding = 0) coherent buffer output_data {
+    uint found_count;
+    uint x_results[MAX_RESULTS];
+};
+
+#ifdef X_BITS
+// Holds the IPL3 words of every Y round searched in the dispatch, IPL3_WORDS words each
+layout(binding = 1) readonly buffer x_bits_data {
+    uint x_bits_count;
+    uint x_bits_word_index;
+    uint x_bits_rounds;
+    uint x_bits_offsets[32];
+    uint x_bits_ipl3[];
+};
+#endif
+
+layout(binding = 2) readonly buffer state_data {
+    uint state_in[];
 };
 
 uint state[16];
 
+uint ipl3_base = 0;
+
 uint sum(uint a0, uint a1, uint a2) {
     uint v1 = a1;
 
@@ -29,9 +83,19 @@ uint sum(uint a0, uint a1, uint a2) {
         v1 = a2;
     }
 
+#if defined(SHADER_INT64) && defined(USE_UNPACK_UINT_2X32)
+    uvec2 prod = unpackUint2x32(uint64_t(a0) * uint64_t(v1));
+    uint hi = prod.y;
+    uint lo = prod.x;
+#elif defined(SHADER_INT64)
     uint64_t prod = uint64_t(a0) * uint64_t(v1);
     uint hi = uint(prod >> 32);
     uint lo = uint(prod);
+#else
+    uint hi;
+    uint lo;
+    umulExtended(a0, v1, hi, lo);
+#endif
     uint diff = hi - lo;
 
     if (diff == 0) {
@@ -41,39 +105,126 @@ uint sum(uint a0, uint a1, uint a2) {
     return diff;
 }
 
+#ifdef X_BITS
+uint ror(uint a, uint s) {
+    return (a >> s) | (a << (32 - s));
+}
+
+uint rol(uint a, uint s) {
+    return (a << s) | (a >> (32 - s));
+}
+
+void calculate_data(uint i, uint prev, uint data) {
+    state[0] += sum((IPL3_WORDS - 1) - i, data, i);
+    state[1] = sum(state[1], data, i);
+    state[2] ^= data;
+    state[3] += sum(data + 5, MAGIC, i);
+    state[4] += ror(data, prev & 0x1F);
+    state[5] += rol(data, prev >> 27);
+    if (data < state[6]) {
+        state[6] = (state[3] + state[6]) ^ (data + i);
+    } else {
+        state[6] = (state[4] + data) ^ state[6];
+    }
+    state[7] = sum(state[7], rol(data, prev & 0x1F), i);
+    state[8] = sum(state[8], ror(data, prev >> 27), i);
+    if (prev < data) {
+        state[9] = sum(state[9], data, i);
+    } else {
+        state[9] += data;
+    }
+}
+
+void calculate_next(uint i, uint prev, uint data, uint next) {
+    state[10] = sum(state[10] + data, next, i);
+    state[11] = sum(state[11] ^ data, next, i);
+    state[12] += state[8] ^ data;
+    state[13] += ror(data, data & 0x1F) + ror(next, next & 0x1F);
+    state[14] = sum(sum(state[14], ror(data, prev & 0x1F), i), ror(next, data & 0x1F), i);
+    state[15] = sum(sum(state[15], rol(data, prev >> 27), i), rol(next, data >> 27), i);
+}
+
+uint x_bits_word(uint i, uint x) {
+    uint data = x_bits_ipl3[ipl3_base + i];
+
+    for (uint j = 0; j < x_bits_count; j++) {
+        uint offset = x_bits_offsets[j];
+
+        if ((offset >> 5) == i) {
+            data |= ((x >> (x_bits_count - 1 - j)) & 1) << (31 - (offset & 0x1F));
+        }
+    }
+
+    return data;
+}
+
+bool x_bits_checksum(uint x) {
+    uint word = x_bits_word_index;
+
+    uint prev = x_bits_word(max(word, 2u) - 2, x);
+    uint data = x_bits_word(word - 1, x);
+    uint next = x_bits_word(word, x);
+
+    calculate_next(word, prev, data, next);
+
+    for (uint i = word + 1; i <= IPL3_WORDS; i++) {
+#if EARLY_EXIT_PERIOD > 0
+        if ((i % EARLY_EXIT_PERIOD) == 0 && found_count != 0) {
+            return false;
+        }
+#endif
+
+        prev = data;
+        data = next;
+
+        calculate_data(i, prev, data);
+
+        if (i == IPL3_WORDS) {
+            break;
+        }
+
+        next = x_bits_word(i, x);
+
+        calculate_next(i, prev, data, next);
+    }
+
+    return true;
+}
+#endif
+
 void finalize_checksum(uint y, uint x) {
-    uint yts = y >> 27;
-    uint ytc = 32 - yts;
+    uint yts = y_top_shift;
+    uint ytc = y_top_complement;
 
-    uint ybs = y & 0x1F;
-    uint ybc = 32 - ybs;
+    uint ybs = y_bottom_shift;
+    uint ybc = y_bottom_complement;
 
     uint xbs = x & 0x1F;
     uint xbc = 32 - xbs;
 
-    state[0] += sum(0xFFFFFFFF, x, 1008);
-    state[1] = sum(state[1], x, 1008);
+    state[0] += sum(0xFFFFFFFF, x, IPL3_WORDS);
+    state[1] = sum(state[1], x, IPL3_WORDS);
     state[2] ^= x;
-    state[3] += sum(x + 5, MAGIC, 1008);
+    state[3] += sum(x + 5, MAGIC, IPL3_WORDS);
     state[4] += (x >> ybs) | (x << ybc);
     state[5] += (x << yts) | (x >> ytc);
     if (x < state[6]) {
-        state[6] = (x + 1008) ^ (state[3] + state[6]);
+        state[6] = (x + IPL3_WORDS) ^ (state[3] + state[6]);
     } else {
         state[6] ^= (state[4] + x);
     }
-    state[7] = sum(state[7], (x << ybs) | (x >> ybc), 1008);
-    state[8] = sum(state[8], (x >> yts) | (x << ytc), 1008);
+    state[7] = sum(state[7], (x << ybs) | (x >> ybc), IPL3_WORDS);
+    state[8] = sum(state[8], (x >> yts) | (x << ytc), IPL3_WORDS);
     if (y < x) {
-        state[9] = sum(state[9], x, 1008);
+        state[9] = sum(state[9], x, IPL3_WORDS);
     } else {
         state[9] += x;
     }
-    state[10] = sum(state[10], x, 1007);
-    state[11] = sum(state[11], x, 1007);
+    state[10] = sum(state[10], x, IPL3_WORDS - 1);
+    state[11] = sum(state[11], x, IPL3_WORDS - 1);
     state[13] += (x >> xbs) | (x << xbc);
-    state[14] = sum(state[14], (x >> ybs) | (x << ybc), 1007);
-    state[15] = sum(state[15], (x << yts) | (x >> ytc), 1007);
+    state[14] = sum(state[14], (x >> ybs) | (x << ybc), IPL3_WORDS - 1);
+    state[15] = sum(state[15], (x << yts) | (x >> ytc), IPL3_WORDS - 1);
 }
 
 uint finalize_hi(void) {
@@ -124,19 +275,70 @@ uint finalize_lo(void) {
 }
 
 void main(void) {
-    state = state_in;
-    uint y = y_offset;
     uint x =
-        (gl_GlobalInvocationID.z * gl_NumWorkGroups.y * gl_NumWorkGroups.x * LOCAL_WORKGROUP_SIZE) +
-        (gl_GlobalInvocationID.y * gl_NumWorkGroups.x * LOCAL_WORKGROUP_SIZE) +
+        (gl_GlobalInvocationID.z * gl_NumWorkGroups.y * gl_NumWorkGroups.x * LOCAL_SIZE_X) +
+        (gl_GlobalInvocationID.y * gl_NumWorkGroups.x * LOCAL_SIZE_X) +
         (gl_GlobalInvocationID.x) +
         x_offset;
 
-    finalize_checksum(y, x);
-    if (finalize_hi() == target_hi) {
-        if (finalize_lo() == target_lo) {
-            if (atomicOr(found, 1) == 0) {
-                x_result = x;
+    if (x < x_offset) {
+        return;
+    }
+
+#ifdef DUMP_STATE
+    if (x != x_offset) {
+        return;
+    }
+#endif
+
+#if EARLY_EXIT_PERIOD > 0
+    if (found_count != 0) {
+        return;
+    }
+#endif
+
+#ifdef X_BITS
+    // The X range of every Y round follows the previous one and the X value hashed is the offset
+    // times the stride, the result is the offset
+    uint y_round = 0;
+    uint x_round = x * x_stride;
+    if (x_bits_count < 32) {
+        y_round = x >> x_bits_count;
+        x_round &= (1u << x_bits_count) - 1;
+    }
+
+    if (y_round >= x_bits_rounds) {
+        return;
+    }
+
+    for (uint i = 0; i < 16; i++) {
+        state[i] = state_in[(y_round * 16) + i];
+    }
+    ipl3_base = y_round * IPL3_WORDS;
+
+    if (!x_bits_checksum(x_round)) {
+        return;
+    }
+#else
+    for (uint i = 0; i < 16; i++) {
+        state[i] = state_in[i];
+    }
+
+    finalize_checksum(y_offset, x * x_stride);
+#endif
+
+#ifdef DUMP_STATE
+    for (uint i = 0; i < 16; i++) {
+        x_results[i] = state[i];
+    }
+    return;
+#endif
+
+    if (finalize_hi() == TARGET_HI) {
+        if (finalize_lo() == TARGET_LO) {
+            uint index = atomicAdd(found_count, 1);
+            if (index < MAX_RESULTS) {
+                x_results[index] = x;
             }
         }
     }
@@ -1,26 +1,77 @@
-override LOCAL_WORKGROUP_SIZE: u32 = 256;
+override LOCAL_SIZE_X: u32 = 256;
+
+// Invocations return early once a match was found in the dispatch, the X bits loop checks again
+// every EARLY_EXIT_PERIOD words (0 disables it, the check costs a memory read)
+override EARLY_EXIT_PERIOD: u32 = 0;
+
+// Makes the invocation at the X offset write its state before the final mix into the results
+// instead of searching, for the CPU to compare word by word
+override DUMP_STATE: bool = false;
 
 const MAGIC: u32 = 0x6C078965;
 
+const MAX_RESULTS: u32 = 16;
+
+// The IPL3 length in words, the X word is the last one
+override IPL3_WORDS: u32 = 1008;
+
 struct Input {
     target_hi: u32,
     target_lo: u32,
     y_offset: u32,
     x_offset: u32,
-    state: array<u32, 16>,
+    y_top_shift: u32,
+    y_top_complement: u32,
+    y_bottom_shift: u32,
+    y_bottom_complement: u32,
+    x_stride: u32,
 }
 
 struct Output {
-    found: atomic<i32>,
-    x_result: u32,
+    found_count: atomic<u32>,
+    x_results: array<u32, MAX_RESULTS>,
+}
+
+// Holds the IPL3 words of every Y round searched in the dispatch, IPL3_WORDS words each
+struct XBits {
+    count: u32,
+    word: u32,
+    rounds: u32,
+    offsets: array<u32, 32>,
+    ipl3: array<u32>,
 }
 
 var<push_constant> input: Input;
 
 @group(0) @binding(0) var<storage, read_write> output: Output;
 
+@group(0) @binding(1) var<storage, read> x_bits: XBits;
+
+@group(0) @binding(2) var<storage, read> state_in: array<u32>;
+
 var<private> state: array<u32, 16>;
 
+var<private> ipl3_base: u32;
+
+// The low and high words of the 64-bit product from 16-bit halves, the line calling it in sum is
+// replaced with a native 64-bit multiplication on devices with SHADER_INT64
+fn mul_wide(a: u32, b: u32) -> vec2<u32> {
+    let a_lo: u32 = a & 0xFFFF;
+    let a_hi: u32 = a >> 16;
+    let b_lo: u32 = b & 0xFFFF;
+    let b_hi: u32 = b >> 16;
+
+    let lo_lo: u32 = a_lo * b_lo;
+    let lo_hi: u32 = a_lo * b_hi;
+    let hi_lo: u32 = a_hi * b_lo;
+    let mid: u32 = (lo_lo >> 16) + (lo_hi & 0xFFFF) + (hi_lo & 0xFFFF);
+
+    return vec2(
+        (lo_lo & 0xFFFF) | (mid << 16),
+        (a_hi * b_hi) + (lo_hi >> 16) + (hi_lo >> 16) + (mid >> 16),
+    );
+}
+
 fn sum(a0: u32, a1: u32, a2: u32) -> u32 {
     var v1: u32 = a1;
 
@@ -28,9 +79,9 @@ fn sum(a0: u32, a1: u32, a2: u32) -> u32 {
         v1 = a2;
     }
 
-    let prod: u64 = u64(a0) * u64(v1);
-    let hi: u32 = u32(prod >> 32);
-    let lo: u32 = u32(prod);
+    let prod: vec2<u32> = mul_wide(a0, v1);
+    let hi: u32 = prod.y;
+    let lo: u32 = prod.x;
     let diff: u32 = hi - lo;
 
     if diff == 0 {
@@ -40,39 +91,126 @@ fn sum(a0: u32, a1: u32, a2: u32) -> u32 {
     return diff;
 }
 
+fn ror(a: u32, s: u32) -> u32 {
+    return (a >> s) | (a << (32 - s));
+}
+
+fn rol(a: u32, s: u32) -> u32 {
+    return (a << s) | (a >> (32 - s));
+}
+
+fn calculate_data(i: u32, prev: u32, data: u32) {
+    state[0] += sum((IPL3_WORDS - 1) - i, data, i);
+    state[1] = sum(state[1], data, i);
+    state[2] ^= data;
+    state[3] += sum(data + 5, MAGIC, i);
+    state[4] += ror(data, prev & 0x1F);
+    state[5] += rol(data, prev >> 27);
+    if (data < state[6]) {
+        state[6] = (state[3] + state[6]) ^ (data + i);
+    } else {
+        state[6] = (state[4] + data) ^ state[6];
+    }
+    state[7] = sum(state[7], rol(data, prev & 0x1F), i);
+    state[8] = sum(state[8], ror(data, prev >> 27), i);
+    if (prev < data) {
+        state[9] = sum(state[9], data, i);
+    } else {
+        state[9] += data;
+    }
+}
+
+fn calculate_next(i: u32, prev: u32, data: u32, next: u32) {
+    state[10] = sum(state[10] + data, next, i);
+    state[11] = sum(state[11] ^ data, next, i);
+    state[12] += state[8] ^ data;
+    state[13] += ror(data, data & 0x1F) + ror(next, next & 0x1F);
+    state[14] = sum(sum(state[14], ror(data, prev & 0x1F), i), ror(next, data & 0x1F), i);
+    state[15] = sum(sum(state[15], rol(data, prev >> 27), i), rol(next, data >> 27), i);
+}
+
+fn x_bits_word(i: u32, x: u32) -> u32 {
+    var data: u32 = x_bits.ipl3[ipl3_base + i];
+
+    for (var j: u32 = 0; j < x_bits.count; j++) {
+        let offset: u32 = x_bits.offsets[j];
+
+        if (offset >> 5) == i {
+            data |= ((x >> (x_bits.count - 1 - j)) & 1) << (31 - (offset & 0x1F));
+        }
+    }
+
+    return data;
+}
+
+fn match_found() -> bool {
+    return EARLY_EXIT_PERIOD > 0 && atomicLoad(&output.found_count) != 0;
+}
+
+fn x_bits_checksum(x: u32) -> bool {
+    let word: u32 = x_bits.word;
+
+    var prev: u32 = x_bits_word(max(word, 2) - 2, x);
+    var data: u32 = x_bits_word(word - 1, x);
+    var next: u32 = x_bits_word(word, x);
+
+    calculate_next(word, prev, data, next);
+
+    for (var i: u32 = word + 1; i <= IPL3_WORDS; i++) {
+        if EARLY_EXIT_PERIOD > 0 && (i % EARLY_EXIT_PERIOD) == 0 && match_found() {
+            return false;
+        }
+
+        prev = data;
+        data = next;
+
+        calculate_data(i, prev, data);
+
+        if i == IPL3_WORDS {
+            break;
+        }
+
+        next = x_bits_word(i, x);
+
+        calculate_next(i, prev, data, next);
+    }
+
+    return true;
+}
+
 fn finalize_checksum(y: u32, x: u32) {
-    let yts: u32 = y >> 27;
-    let ytc: u32 = 32 - yts;
+    let yts: u32 = input.y_top_shift;
+    let ytc: u32 = input.y_top_complement;
 
-    let ybs: u32 = y & 0x1F;
-    let ybc: u32 = 32 - ybs;
+    let ybs: u32 = input.y_bottom_shift;
+    let ybc: u32 = input.y_bottom_complement;
 
     let xbs: u32 = x & 0x1F;
     let xbc: u32 = 32 - xbs;
 
-    state[0] += sum(0xFFFFFFFF, x, 1008);
-    state[1] = sum(state[1], x, 1008);
+    state[0] += sum(0xFFFFFFFF, x, IPL3_WORDS);
+    state[1] = sum(state[1], x, IPL3_WORDS);
     state[2] ^= x;
-    state[3] += sum(x + 5, MAGIC, 1008);
+    state[3] += sum(x + 5, MAGIC, IPL3_WORDS);
     state[4] += (x >> ybs) | (x << ybc);
     state[5] += (x << yts) | (x >> ytc);
     if (x < state[6]) {
-        state[6] = (x + 1008) ^ (state[3] + state[6]);
+        state[6] = (x + IPL3_WORDS) ^ (state[3] + state[6]);
     } else {
         state[6] ^= (state[4] + x);
     }
-    state[7] = sum(state[7], (x << ybs) | (x >> ybc), 1008);
-    state[8] = sum(state[8], (x >> yts) | (x << ytc), 1008);
+    state[7] = sum(state[7], (x << ybs) | (x >> ybc), IPL3_WORDS);
+    state[8] = sum(state[8], (x >> yts) | (x << ytc), IPL3_WORDS);
     if (y < x) {
-        state[9] = sum(state[9], x, 1008);
+        state[9] = sum(state[9], x, IPL3_WORDS);
     } else {
         state[9] += x;
     }
-    state[10] = sum(state[10], x, 1007);
-    state[11] = sum(state[11], x, 1007);
+    state[10] = sum(state[10], x, IPL3_WORDS - 1);
+    state[11] = sum(state[11], x, IPL3_WORDS - 1);
     state[13] += (x >> xbs) | (x << xbc);
-    state[14] = sum(state[14], (x >> ybs) | (x << ybc), 1007);
-    state[15] = sum(state[15], (x << yts) | (x >> ytc), 1007);
+    state[14] = sum(state[14], (x >> ybs) | (x << ybc), IPL3_WORDS - 1);
+    state[15] = sum(state[15], (x << yts) | (x >> ytc), IPL3_WORDS - 1);
 }
 
 fn finalize_hi() -> u32 {
@@ -122,25 +260,99 @@ fn finalize_lo() -> u32 {
     return buf[0] ^ buf[1];
 }
 
-@compute @workgroup_size(LOCAL_WORKGROUP_SIZE)
-fn main(
-    @builtin(global_invocation_id) global_id: vec3<u32>,
-    @builtin(num_workgroups) num_workgroups: vec3<u32>,
-) {
-    state = input.state;
-    let y: u32 = input.y_offset;
-    let x: u32 =
-        (global_id.z * num_workgroups.y * num_workgroups.x * LOCAL_WORKGROUP_SIZE) +
-        (global_id.y * num_workgroups.x * LOCAL_WORKGROUP_SIZE) +
+fn get_x(global_id: vec3<u32>, num_workgroups: vec3<u32>) -> u32 {
+    return
+        (global_id.z * num_workgroups.y * num_workgroups.x * LOCAL_SIZE_X) +
+        (global_id.y * num_workgroups.x * LOCAL_SIZE_X) +
         (global_id.x) +
         input.x_offset;
+}
+
+fn load_state(y_round: u32) {
+    for (var i: u32 = 0; i < 16; i++) {
+        state[i] = state_in[(y_round * 16) + i];
+    }
+}
+
+fn dump_state() {
+    for (var i: u32 = 0; i < 16; i++) {
+        output.x_results[i] = state[i];
+    }
+}
 
-    finalize_checksum(y, x);
+fn check_result(x: u32) {
     if finalize_hi() == input.target_hi {
         if finalize_lo() == input.target_lo {
-            if atomicOr(&output.found, 1) == 0 {
-                output.x_result = x;
+            let index: u32 = atomicAdd(&output.found_count, 1u);
+            if index < MAX_RESULTS {
+                output.x_results[index] = x;
             }
         }
     }
 }
+
+@compute @workgroup_size(LOCAL_SIZE_X)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    load_state(0);
+    let y: u32 = input.y_offset;
+    let x: u32 = get_x(global_id, num_workgroups);
+
+    if x < input.x_offset || match_found() {
+        return;
+    }
+
+    if DUMP_STATE && x != input.x_offset {
+        return;
+    }
+
+    finalize_checksum(y, x * input.x_stride);
+
+    if DUMP_STATE {
+        dump_state();
+        return;
+    }
+
+    check_result(x);
+}
+
+@compute @workgroup_size(LOCAL_SIZE_X)
+fn main_x_bits(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let x: u32 = get_x(global_id, num_workgroups);
+
+    // The X range of every Y round follows the previous one and the X value hashed is the offset
+    // times the stride, the result is the offset
+    var y_round: u32 = 0;
+    var x_round: u32 = x * input.x_stride;
+    if x_bits.count < 32 {
+        y_round = x >> x_bits.count;
+        x_round &= (1u << x_bits.count) - 1;
+    }
+
+    if x < input.x_offset || y_round >= x_bits.rounds || match_found() {
+        return;
+    }
+
+    if DUMP_STATE && x != input.x_offset {
+        return;
+    }
+
+    load_state(y_round);
+    ipl3_base = y_round * IPL3_WORDS;
+
+    if !x_bits_checksum(x_round) {
+        return;
+    }
+
+    if DUMP_STATE {
+        dump_state();
+        return;
+    }
+
+    check_result(x);
+}
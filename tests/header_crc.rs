@@ -0,0 +1,90 @@
+//! The header CRCs of synthetic ROMs against the values of the reference implementation
+//! (n64crc) for every CIC variant
+
+use ipl3hasher_new::{header, rom::RomFormat};
+
+const ROM_SIZE: usize = header::CRC_START + header::CRC_LENGTH;
+
+// The seeds of the 6102, 6103, 6105 and 6106 CICs
+const SEEDS: [u8; 4] = [0x3F, 0x78, 0x91, 0x85];
+
+const ZERO_CRCS: [(u32, u32); 4] = [
+    (0xF8CA4DDC, 0x303A4DDC),
+    (0xA3886759, 0x40EC6759),
+    (0xDF26F436, 0xDF26F436),
+    (0x04100F9E, 0x89F80F9E),
+];
+
+const RANDOM_CRCS: [(u32, u32); 4] = [
+    (0x1700E4CD, 0xAFF0055C),
+    (0x05B6DE8C, 0xD12944BF),
+    (0x3DB44D6B, 0x8BCBE5FE),
+    (0x4BF358FC, 0x84D3D495),
+];
+
+fn random_rom() -> Vec<u8> {
+    let mut state: u32 = 0x6C078965;
+    (0..ROM_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn crc_of_synthetic_roms() {
+    let zero = vec![0u8; ROM_SIZE];
+    let random = random_rom();
+
+    for (i, seed) in SEEDS.into_iter().enumerate() {
+        assert_eq!(header::calculate_crc(&zero, seed).unwrap(), ZERO_CRCS[i]);
+        assert_eq!(
+            header::calculate_crc(&random, seed).unwrap(),
+            RANDOM_CRCS[i]
+        );
+    }
+
+    assert!(header::calculate_crc(&random[..(ROM_SIZE - 4)], SEEDS[0]).is_err());
+}
+
+/// The CRCs are written to the header in the byte order of the file, nothing else changes
+#[test]
+fn fix_crc_in_every_format() {
+    let path = std::env::temp_dir().join(format!(
+        "ipl3hasher-test-header-crc-{}.bin",
+        std::process::id()
+    ));
+
+    let mut rom = random_rom();
+    rom[..4].copy_from_slice(&0x80371240u32.to_be_bytes());
+    // Past the range covered, left as is
+    rom.extend_from_slice(&[0x5A; 16]);
+
+    let mut expected = rom.clone();
+    let (crc1, crc2) = header::calculate_crc(&rom, SEEDS[2]).unwrap();
+    expected[0x10..0x14].copy_from_slice(&crc1.to_be_bytes());
+    expected[0x14..0x18].copy_from_slice(&crc2.to_be_bytes());
+
+    for format in [RomFormat::Z64, RomFormat::V64, RomFormat::N64] {
+        let mut data = rom.clone();
+        format.swap(&mut data);
+        std::fs::write(&path, &data).unwrap();
+
+        assert_eq!(
+            header::fix_crc(&path, None, SEEDS[2]).unwrap(),
+            (crc1, crc2)
+        );
+
+        let mut data = std::fs::read(&path).unwrap();
+        format.swap(&mut data);
+        assert!(data == expected, "{}", format.get_name());
+    }
+
+    std::fs::write(&path, &rom[..(ROM_SIZE - 4)]).unwrap();
+    assert!(header::fix_crc(&path, None, SEEDS[0]).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,41 @@
+//! The built-in known-answer vectors, through the library and the --self-test flag
+
+use ipl3hasher_new::{Hasher, known_answer};
+
+#[test]
+fn known_answers_match() {
+    for known_answer in known_answer::KNOWN_ANSWERS {
+        let image = known_answer.get_image();
+        assert_eq!(image.len(), known_answer.ipl3_words * 4);
+        assert_eq!(
+            Hasher::compute_ipl3(&image, known_answer.seed),
+            known_answer.checksum,
+            "image {:08X} with seed {:02X}",
+            known_answer.generator,
+            known_answer.seed
+        );
+    }
+
+    assert_eq!(
+        known_answer::check_all().unwrap(),
+        known_answer::KNOWN_ANSWERS.len()
+    );
+}
+
+#[test]
+fn self_test_before_command() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_ipl3hasher-new"))
+        .args(["--json", "--self-test", "cics"])
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(
+        stdout.contains(&format!(
+            "{{\"event\":\"known_answer_test\",\"vectors\":{}}}",
+            known_answer::KNOWN_ANSWERS.len()
+        )),
+        "{stdout}"
+    );
+}
@@ -0,0 +1,324 @@
+//! The search through the library API alone, as a frontend embedding the hasher would run it
+
+use ipl3hasher_new::{
+    CPUHasher, GPUAdapterSelector, GPUHasherShader, GPUWorkgroupsSelector, Hasher, HasherDevices,
+    HasherError, HasherResult, HasherSearch, checkpoint::Checkpoint, known_answer,
+    results::ResultEntry,
+};
+
+const SEED: u8 = 0x3F;
+const Y: u32 = 1;
+const X: u32 = 0x12345;
+
+/// Bit offsets into the IPL3 of the low bits of an IPL3 word, most significant bit first
+fn low_bits(word: u32, bits: u32) -> Vec<u32> {
+    ((32 - bits)..32).map(|bit| word * 32 + bit).collect()
+}
+
+fn store(ipl3: &mut [u8], bits: &[u32], value: u32) {
+    for (i, offset) in bits.iter().enumerate() {
+        let bit = 7 - (offset % 8);
+        let byte = &mut ipl3[(offset / 8) as usize];
+        *byte = (*byte & !(1 << bit)) | ((((value >> (bits.len() - 1 - i)) & 1) as u8) << bit);
+    }
+}
+
+/// A CPU search of a pseudo-random IPL3 for the checksum with Y and X in their bits, from the
+/// cleared bits
+fn planted_search(y_init: u32, x_init: u32) -> Hasher {
+    let mut state: u32 = 0x6C078965;
+    let mut ipl3: Vec<u8> = (0..4032)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+
+    let y_bits = low_bits(1006, 2);
+    let x_bits = [low_bits(1000, 10), low_bits(1001, 10)].concat();
+
+    let target_checksum = Hasher::verify_ipl3(&ipl3, SEED, &y_bits, &x_bits, Y, X).unwrap();
+    store(&mut ipl3, &y_bits, 0);
+    store(&mut ipl3, &x_bits, 0);
+
+    Hasher::new(
+        &ipl3,
+        HasherSearch {
+            seed: SEED,
+            target_checksum,
+            y_bits,
+            x_bits,
+            y_init,
+            y_end: None,
+            y_order: ipl3hasher_new::hasher::YOrder::Sequential,
+            x_init,
+        },
+        HasherDevices::Cpu { threads: 2 },
+    )
+    .unwrap()
+}
+
+fn search_to_end(hasher: &mut Hasher) {
+    loop {
+        match hasher.compute_round().unwrap() {
+            HasherResult::Found(y, x_results, _) => {
+                assert_eq!((y, x_results[0]), (Y, X));
+                break;
+            }
+            HasherResult::End => panic!("no collision found"),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn search_on_cpu_through_library() {
+    search_to_end(&mut planted_search(0, 0));
+}
+
+/// The interrupt flag stops the search in place for as long as it's set, the position it hands
+/// back resumes the search in a new hasher and clearing the flag continues it in the same one
+#[test]
+fn interrupt_and_resume() {
+    let interrupt = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let mut hasher = planted_search(0, 0);
+    hasher.set_interrupt(Some(interrupt.clone()));
+
+    // Y=0 has no match, its whole X range is a single CPU dispatch
+    assert!(matches!(
+        hasher.compute_round().unwrap(),
+        HasherResult::Continue
+    ));
+
+    interrupt.store(true, std::sync::atomic::Ordering::SeqCst);
+    for _ in 0..2 {
+        assert!(matches!(
+            hasher.compute_round().unwrap(),
+            HasherResult::Interrupted
+        ));
+        assert_eq!((hasher.get_y(), hasher.get_x_offset()), (Y, 0));
+    }
+
+    search_to_end(&mut planted_search(hasher.get_y(), hasher.get_x_offset()));
+
+    interrupt.store(false, std::sync::atomic::Ordering::SeqCst);
+    search_to_end(&mut hasher);
+}
+
+/// A saved checkpoint loads back as it was and resumes the search where it stopped, but not a
+/// search over another Y range
+#[test]
+fn checkpoint_round_trip() {
+    let path = std::env::temp_dir().join(format!(
+        "ipl3hasher-test-checkpoint-{}.txt",
+        std::process::id()
+    ));
+
+    let mut hasher = planted_search(0, 0);
+    assert!(matches!(
+        hasher.compute_round().unwrap(),
+        HasherResult::Continue
+    ));
+    hasher.get_checkpoint().save(&path).unwrap();
+
+    let checkpoint = Checkpoint::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(checkpoint, hasher.get_checkpoint());
+    assert_eq!((checkpoint.y, checkpoint.x_offset), (Y, 0));
+    assert_eq!((checkpoint.y_first, checkpoint.y_end), (0, 3));
+
+    let mut resumed = planted_search(0, 0);
+    resumed.restore_checkpoint(&checkpoint).unwrap();
+    assert_eq!((resumed.get_y(), resumed.get_x_offset()), (Y, 0));
+    search_to_end(&mut resumed);
+
+    // Started past the Y of the checkpoint
+    assert!(matches!(
+        planted_search(Y + 1, 0).restore_checkpoint(&checkpoint),
+        Err(HasherError::CheckpointMismatch("Y range"))
+    ));
+
+    // Saved for another IPL3
+    let other_ipl3 = Checkpoint {
+        ipl3_sha256: ResultEntry::get_ipl3_sha256(b"abc"),
+        ..checkpoint.clone()
+    };
+    assert!(matches!(
+        planted_search(0, 0).restore_checkpoint(&other_ipl3),
+        Err(HasherError::CheckpointMismatch("IPL3 contents"))
+    ));
+
+    // Ended earlier, as another --y-end or shard would
+    let shorter = Checkpoint {
+        y_end: 2,
+        ..checkpoint
+    };
+    assert!(matches!(
+        planted_search(0, 0).restore_checkpoint(&shorter),
+        Err(HasherError::CheckpointMismatch("Y range"))
+    ));
+}
+
+#[test]
+fn verify_batch_matches_verify() {
+    let cpu = CPUHasher::new(&known_answer::generate_image(0x12345678, 1008), SEED);
+
+    // Repeated and interleaved Y values, some sharing their Y prefix on one thread
+    let candidates: Vec<(u32, u32)> = (0..40u32)
+        .map(|i| ((i * 7) % 5, i.wrapping_mul(0x9E3779B9)))
+        .collect();
+
+    for (y_bits, x_bits) in [
+        (low_bits(1006, 3), low_bits(1007, 32)),
+        (
+            low_bits(1006, 3),
+            [low_bits(1000, 10), low_bits(1001, 10)].concat(),
+        ),
+        (
+            [low_bits(20, 2), low_bits(1005, 1)].concat(),
+            low_bits(500, 16),
+        ),
+    ] {
+        let x_mask = ((1u64 << x_bits.len()) - 1) as u32;
+        let candidates: Vec<(u32, u32)> =
+            candidates.iter().map(|&(y, x)| (y, x & x_mask)).collect();
+        let expected: Vec<u64> = candidates
+            .iter()
+            .map(|&(y, x)| cpu.verify(&y_bits, y, &x_bits, x))
+            .collect();
+
+        for threads in [1, 4] {
+            assert_eq!(
+                cpu.verify_batch(&y_bits, &x_bits, &candidates, threads),
+                expected,
+                "{threads} threads"
+            );
+        }
+    }
+}
+
+/// Interleaved hashers reuse their scratch IPL3 between the calls, every checksum must still be the
+/// one of a fresh image with the values stored
+#[test]
+fn verify_interleaved_hashers() {
+    let images =
+        [0x6C078965, 0xDEADBEEF].map(|generator| known_answer::generate_image(generator, 1008));
+    let cpus = images.each_ref().map(|image| CPUHasher::new(image, SEED));
+
+    let y_bits = [low_bits(20, 2), low_bits(1006, 3)].concat();
+    let x_bits = low_bits(1007, 32);
+
+    for i in 0..8u32 {
+        for (image, cpu) in images.iter().zip(&cpus) {
+            let (y, x) = (i % 5, i.wrapping_mul(0x9E3779B9));
+
+            let mut ipl3 = image.clone();
+            store(&mut ipl3, &y_bits, y);
+            store(&mut ipl3, &x_bits, x);
+
+            assert_eq!(
+                cpu.verify(&y_bits, y, &x_bits, x),
+                CPUHasher::new(&ipl3, SEED).checksum()
+            );
+        }
+    }
+}
+
+/// The entries are appended after the ones of earlier runs, even after a line left unterminated,
+/// and read back by index from either end
+#[test]
+fn results_file_round_trip() {
+    let path = std::env::temp_dir().join(format!(
+        "ipl3hasher-test-results-{}.jsonl",
+        std::process::id()
+    ));
+    std::fs::write(&path, "\n").unwrap();
+
+    let entry = |y: u32, x: u32| ResultEntry {
+        timestamp: "2024-01-02T03:04:05Z".to_string(),
+        rom: "rom \"name\".z64".to_string(),
+        ipl3_sha256: ResultEntry::get_ipl3_sha256(b"abc"),
+        seed: SEED,
+        target_checksum: 0x8618A45BC2D3,
+        y_bits: "1022[31..0]".to_string(),
+        x_bits: "1016[9..0],1017[9..0]".to_string(),
+        y,
+        x,
+    };
+
+    entry(Y, X).append(&path).unwrap();
+    let mut f = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    std::io::Write::write_all(&mut f, b"{\"timestamp\"").unwrap();
+    drop(f);
+    assert!(ResultEntry::load_all(&path).is_err());
+
+    // The partial line stays on its own, dropping it leaves the entries intact
+    entry(Y + 1, X + 1).append(&path).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(lines[2], "{\"timestamp\"");
+    std::fs::write(&path, [lines[1], lines[3]].join("\n")).unwrap();
+    entry(Y + 2, X + 2).append(&path).unwrap();
+
+    assert_eq!(
+        ResultEntry::load_all(&path).unwrap(),
+        [entry(Y, X), entry(Y + 1, X + 1), entry(Y + 2, X + 2)]
+    );
+    assert_eq!(ResultEntry::load(&path, 1).unwrap(), entry(Y + 1, X + 1));
+    assert_eq!(ResultEntry::load(&path, -1).unwrap(), entry(Y + 2, X + 2));
+    assert!(ResultEntry::load(&path, 3).is_err());
+    assert!(ResultEntry::load(&path, -4).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        entry(Y, X).ipl3_sha256,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+/// Without an adapter to select from the GPU search fails with its own error, and falls back to
+/// the CPU when asked to
+#[test]
+fn no_gpu_adapter() {
+    let search = || HasherSearch {
+        seed: SEED,
+        target_checksum: 0,
+        y_bits: low_bits(1006, 2),
+        x_bits: low_bits(1007, 8),
+        y_init: 0,
+        y_end: None,
+        y_order: ipl3hasher_new::hasher::YOrder::Sequential,
+        x_init: 0,
+    };
+    let devices = || HasherDevices::Gpu {
+        adapters: vec![],
+        selectors: vec![GPUAdapterSelector::Index(0)],
+        workgroups: vec![GPUWorkgroupsSelector::Auto],
+        local_size: vec![256],
+        shader: GPUHasherShader::Auto,
+        debug: false,
+    };
+    let ipl3 = vec![0u8; 4032];
+
+    assert!(matches!(
+        Hasher::new(&ipl3, search(), devices()),
+        Err(HasherError::GPUAdapterNoneFound)
+    ));
+
+    let hasher = Hasher::new(&ipl3, search(), devices().with_cpu_fallback(3)).unwrap();
+    assert_eq!(hasher.get_cpu_threads(), Some(3));
+
+    let hasher = Hasher::new(
+        &ipl3,
+        search(),
+        HasherDevices::Cpu { threads: 2 }.with_cpu_fallback(3),
+    )
+    .unwrap();
+    assert_eq!(hasher.get_cpu_threads(), Some(2));
+}
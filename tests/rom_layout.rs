@@ -0,0 +1,122 @@
+//! Signing writes the Y and X bits where the IPL3 is read from, for every layout and byte order
+
+use ipl3hasher_new::{
+    Hasher,
+    rom::{self, RomFormat, RomLayout},
+};
+
+const FORMATS: [RomFormat; 3] = [RomFormat::Z64, RomFormat::V64, RomFormat::N64];
+
+const ROM_SIZE: usize = 0x1000 + 0x200;
+
+/// Bit offsets into the IPL3 of the bits of an IPL3 word, from the first bit to the last one
+fn word_bits(word: usize, first: u32, last: u32) -> Vec<u32> {
+    (last..=first)
+        .rev()
+        .map(|bit| (word * 32) as u32 + (31 - bit))
+        .collect()
+}
+
+/// The value of the IPL3 bits, most significant bit first
+fn load_bits(ipl3: &[u8], bits: &[u32]) -> u32 {
+    bits.iter().fold(0, |value, &offset| {
+        let (index, bit) = rom::bit_position(offset);
+        (value << 1) | ((ipl3[index] >> bit) & 1) as u32
+    })
+}
+
+/// A ROM (or a raw IPL3) holding a pattern, with the header of the format unless raw
+fn write_rom(path: &std::path::Path, format: RomFormat, size: usize, raw: bool) -> Vec<u8> {
+    let mut data: Vec<u8> = (0..size).map(|i| (i * 7 + 3) as u8).collect();
+    if !raw {
+        data[..4].copy_from_slice(&0x80371240u32.to_be_bytes());
+    }
+    format.swap(&mut data);
+    std::fs::write(path, &data).unwrap();
+
+    data
+}
+
+/// The first bit of the last word lands at byte 4092 of a z64 ROM, at the byte of the same
+/// big-endian byte in the other orders
+#[test]
+fn file_position_of_every_format() {
+    let layout = |ipl3_offset: u64| RomLayout {
+        format: None,
+        ipl3_offset,
+        ipl3_words: rom::IPL3_WORDS,
+    };
+    let offset = word_bits(rom::IPL3_WORDS - 1, 31, 31)[0];
+
+    for (format, index) in FORMATS.into_iter().zip([4092, 4093, 4095]) {
+        assert_eq!(layout(0x40).get_file_position(format, offset), (index, 7));
+        assert_eq!(
+            layout(0).get_file_position(format, offset),
+            (index - 0x40, 7)
+        );
+        assert_eq!(
+            layout(0x40).get_file_position(format, offset + 15),
+            (4092 + format.get_byte_index(1) as u64, 0)
+        );
+    }
+
+    for format in FORMATS {
+        for index in 0..8 {
+            assert_eq!(format.get_byte_index(format.get_byte_index(index)), index);
+        }
+    }
+}
+
+/// The bits signed into the file read back through the same layout, and no other byte changes
+#[test]
+fn sign_in_every_layout() {
+    let path = std::env::temp_dir().join(format!(
+        "ipl3hasher-test-rom-layout-{}.bin",
+        std::process::id()
+    ));
+    let (y, x) = (0xA5, 0x5A3);
+
+    for format in FORMATS {
+        for ipl3_words in [rom::IPL3_WORDS, 256] {
+            // A ROM with the format detected or forced, a ROM with the IPL3 moved and a raw IPL3
+            let layouts = [
+                (None, 0x40, false),
+                (Some(format), 0x40, false),
+                (Some(format), 0x100, false),
+                (Some(format), 0, true),
+            ];
+
+            for (layout_format, ipl3_offset, raw) in layouts {
+                let layout = RomLayout {
+                    format: layout_format,
+                    ipl3_offset,
+                    ipl3_words,
+                };
+                let size = if raw { ipl3_words * 4 } else { ROM_SIZE };
+                let y_bits = word_bits(ipl3_words - 2, 7, 0);
+                let x_bits = [word_bits(3, 3, 0), word_bits(ipl3_words - 1, 31, 25)].concat();
+
+                let unsigned = write_rom(&path, format, size, raw);
+                Hasher::sign_rom(&path, layout, &y_bits, &x_bits, y, x).unwrap();
+                let signed = std::fs::read(&path).unwrap();
+
+                let ipl3 = Hasher::read_ipl3(&path, layout).unwrap();
+                assert_eq!(load_bits(&ipl3, &y_bits), y);
+                assert_eq!(load_bits(&ipl3, &x_bits), x);
+
+                let positions: Vec<u64> = [&y_bits[..], &x_bits[..]]
+                    .concat()
+                    .into_iter()
+                    .map(|offset| layout.get_file_position(format, offset).0)
+                    .collect();
+                for (i, (before, after)) in unsigned.iter().zip(&signed).enumerate() {
+                    if !positions.contains(&(i as u64)) {
+                        assert_eq!(before, after, "byte 0x{i:X} changed");
+                    }
+                }
+            }
+        }
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
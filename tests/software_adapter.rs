@@ -0,0 +1,526 @@
+//! End to end search on a software GPU adapter (lavapipe, llvmpipe, WARP), so that the shaders are
+//! checked against the CPU hasher on machines without a GPU. The searches on the adapter run only
+//! with IPL3HASHER_SOFTWARE_ADAPTER_TEST set, they take a while on the CPU, the --cpu-only ones
+//! always run.
+
+const ENABLE_VARIABLE: &str = "IPL3HASHER_SOFTWARE_ADAPTER_TEST";
+
+const IPL3_WORDS: usize = 1008;
+const IPL3_OFFSET: usize = 0x40;
+
+const SEED: &str = "3F";
+const Y_BITS: &str = "1022[1..0]";
+const X_BITS: &str = "1016[9..0],1017[9..0]";
+const Y: u32 = 1;
+const X: u32 = 0x12345;
+
+// The whole last word is X, found within the first dispatches of the Y round
+const X_WORD_Y_BITS: &str = "1022[1..0]";
+const X_WORD_X_BITS: &str = "1023[31..0]";
+const X_WORD_X: u32 = 0x2C5A3;
+
+/// Stores the value in the low bits of an IPL3 word (ROM word index), big endian
+fn set_low_bits(ipl3: &mut [u8], word: usize, bits: u32, value: u32) {
+    let offset = word * 4 - IPL3_OFFSET;
+    let data = u32::from_be_bytes(ipl3[offset..(offset + 4)].try_into().unwrap());
+    let mask = ((1u64 << bits) - 1) as u32;
+    let data = (data & !mask) | (value & mask);
+    ipl3[offset..(offset + 4)].copy_from_slice(&data.to_be_bytes());
+}
+
+/// Pseudo-random IPL3 of the given length with the values stored in the low bits of their words
+fn write_ipl3(path: &std::path::Path, ipl3_words: usize, words: &[(usize, u32, u32)]) {
+    let mut state: u32 = 0x6C078965;
+    let mut ipl3: Vec<u8> = (0..(ipl3_words * 4))
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+
+    for &(word, bits, value) in words {
+        set_low_bits(&mut ipl3, word, bits, value);
+    }
+
+    std::fs::write(path, ipl3).unwrap();
+}
+
+fn run(args: &[&str]) -> String {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_ipl3hasher-new"))
+        .arg("--json")
+        .args(args)
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(
+        output.status.success(),
+        "{args:?} failed: {stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+}
+
+fn json_number(line: &str, key: &str) -> u64 {
+    let pattern = format!("\"{key}\":");
+    let start = line.find(&pattern).unwrap() + pattern.len();
+    let end = line[start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(line.len(), |end| start + end);
+    line[start..end].parse().unwrap()
+}
+
+/// The JSON array of numbers following the key
+fn json_array<'a>(line: &'a str, key: &str) -> &'a str {
+    let pattern = format!("\"{key}\":[");
+    let start = line.find(&pattern).unwrap() + pattern.len() - 1;
+    let end = start + line[start..].find(']').unwrap() + 1;
+    &line[start..end]
+}
+
+/// Searches from cleared bits for the checksum of the IPL3 with the values planted, returns the
+/// found event
+fn search(name: &str, ipl3_words: usize, planted: &[(usize, u32, u32)], args: &[&str]) -> String {
+    let path =
+        std::env::temp_dir().join(format!("ipl3hasher-test-{name}-{}.bin", std::process::id()));
+    let rom = path.to_str().unwrap();
+    let ipl3_words_arg = ipl3_words.to_string();
+
+    write_ipl3(&path, ipl3_words, planted);
+    let compute = run(&[
+        "compute",
+        rom,
+        "--seed",
+        SEED,
+        "--checksum",
+        "0",
+        "--ipl3-words",
+        &ipl3_words_arg,
+    ]);
+    let line = compute
+        .lines()
+        .find(|line| line.contains("\"event\":\"compute\""))
+        .unwrap();
+    let checksum = format!("{:X}", json_number(line, "checksum"));
+
+    let cleared: Vec<_> = planted
+        .iter()
+        .map(|&(word, bits, _)| (word, bits, 0))
+        .collect();
+    write_ipl3(&path, ipl3_words, &cleared);
+
+    let mut search_args = vec![
+        "search",
+        rom,
+        "--seed",
+        SEED,
+        "--checksum",
+        &checksum,
+        "--ipl3-words",
+        &ipl3_words_arg,
+        "--allow-software-adapter",
+    ];
+    search_args.extend(args);
+    let search = run(&search_args);
+
+    std::fs::remove_file(&path).unwrap();
+
+    let found = search
+        .lines()
+        .find(|line| line.contains("\"event\":\"found\""))
+        .unwrap_or_else(|| panic!("no collision found: {search}"))
+        .to_string();
+
+    assert!(found.contains("\"verified\":true"), "{found}");
+    found
+}
+
+fn enabled() -> bool {
+    if std::env::var_os(ENABLE_VARIABLE).is_none() {
+        eprintln!("skipped, set {ENABLE_VARIABLE} to run the search on a software GPU adapter");
+        return false;
+    }
+    true
+}
+
+fn search_x_bits(name: &str, y: u32, args: &[&str]) {
+    let mut search_args = vec![
+        "--y-bits",
+        Y_BITS,
+        "--x-bits",
+        X_BITS,
+        "--workgroups",
+        "64,1,1",
+        "--local-size",
+        "64",
+    ];
+    search_args.extend(args);
+
+    let found = search(
+        name,
+        IPL3_WORDS,
+        &[(1022, 2, y), (1016, 10, X >> 10), (1017, 10, X)],
+        &search_args,
+    );
+
+    assert_eq!(json_number(&found, "y"), y as u64);
+    assert_eq!(json_number(&found, "x"), X as u64);
+}
+
+#[test]
+fn search_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    search_x_bits("x-bits", Y, &[]);
+}
+
+/// The X offsets are found through the inverse of the stride, and the shader check hashes the X
+/// values at its offsets with both shaders
+#[test]
+fn search_with_x_stride_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    search_x_bits("x-bits-stride", Y, &["--x-stride", "0x9E3779B9"]);
+
+    let path = std::env::temp_dir().join(format!(
+        "ipl3hasher-test-shader-check-stride-{}.bin",
+        std::process::id()
+    ));
+    write_ipl3(&path, IPL3_WORDS, &[]);
+    let check = run(&[
+        "search",
+        path.to_str().unwrap(),
+        "--seed",
+        SEED,
+        "--checksum",
+        "0",
+        "--y-bits",
+        Y_BITS,
+        "--x-bits",
+        X_BITS,
+        "--allow-software-adapter",
+        "--x-stride",
+        "0x9E3779B9",
+        "--shader-check",
+    ]);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        check.matches("\"event\":\"shader_check\"").count(),
+        2,
+        "{check}"
+    );
+}
+
+/// The 32-bit multiplication fallback, the same as the default on adapters without SHADER_INT64
+#[test]
+fn search_without_shader_int64_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    search_x_bits("x-bits-int32", Y, &["--no-shader-int64"]);
+}
+
+/// The state buffer is written again at every Y round, with a stale state the collision in the
+/// last round would be missed
+#[test]
+fn search_last_y_round_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    search_x_bits("x-bits-last-y", 3, &[]);
+}
+
+/// The same search on a pool of CPU threads, the match is in the first chunk and the chunks past
+/// it are skipped
+#[test]
+fn search_on_cpu() {
+    search_x_bits("x-bits-cpu", Y, &["--cpu-only", "--threads", "3"]);
+}
+
+fn search_x_word(name: &str, args: &[&str]) {
+    let mut search_args = vec!["--y-bits", X_WORD_Y_BITS, "--x-bits", X_WORD_X_BITS];
+    search_args.extend(args);
+
+    let found = search(
+        name,
+        IPL3_WORDS,
+        &[(1022, 2, 0), (1023, 32, X_WORD_X)],
+        &search_args,
+    );
+
+    assert_eq!(json_number(&found, "y"), 0);
+    assert_eq!(json_number(&found, "x"), X_WORD_X as u64);
+}
+
+#[test]
+fn search_x_word_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    search_x_word("x-word", &["--workgroups", "256,1,1", "--local-size", "64"]);
+}
+
+/// The X word is hashed several X values at a time on the CPU, the match isn't in the first lane
+#[test]
+fn search_x_word_on_cpu() {
+    search_x_word("x-word-cpu", &["--cpu-only", "--threads", "3"]);
+}
+
+/// A shorter IPL3 (read as a raw IPL3 from its size) is hashed the same on the GPU and the CPU,
+/// the GPU self-test and the verification of the found pair compare them
+#[test]
+fn search_short_ipl3_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    let found = search(
+        "short-ipl3",
+        512,
+        &[(526, 2, Y), (520, 10, X >> 10), (521, 10, X)],
+        &[
+            "--y-bits",
+            "526[1..0]",
+            "--x-bits",
+            "520[9..0],521[9..0]",
+            "--workgroups",
+            "64,1,1",
+            "--local-size",
+            "64",
+        ],
+    );
+
+    assert_eq!(json_number(&found, "y"), Y as u64);
+    assert_eq!(json_number(&found, "x"), X as u64);
+}
+
+/// The state every GPU writes in place of the results equals the CPU state after the last word,
+/// with the whole X word and with X bits
+#[test]
+fn dump_state_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    let path = std::env::temp_dir().join(format!(
+        "ipl3hasher-test-dump-state-{}.bin",
+        std::process::id()
+    ));
+    let rom = path.to_str().unwrap();
+
+    write_ipl3(&path, IPL3_WORDS, &[]);
+
+    for x_bits in [X_BITS, X_WORD_X_BITS] {
+        let dump = run(&[
+            "search",
+            rom,
+            "--seed",
+            SEED,
+            "--checksum",
+            "0",
+            "--allow-software-adapter",
+            "--workgroups",
+            "64,1,1",
+            "--local-size",
+            "64",
+            "--y-bits",
+            Y_BITS,
+            "--x-bits",
+            x_bits,
+            "--dump-state",
+            "--y",
+            "2",
+            "--x",
+            "12345",
+        ]);
+
+        let line = dump
+            .lines()
+            .find(|line| line.contains("\"event\":\"dump_state\""))
+            .unwrap();
+        let gpu_states = format!("\"gpu_states\":[{}]", json_array(line, "final_state"));
+        assert!(line.contains(&gpu_states), "{x_bits}: {line}");
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// A short run of random cases from a fixed seed, X word and X bits ones, each a single dispatch
+#[test]
+fn differential_test_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    let output = run(&[
+        "differential-test",
+        "--allow-software-adapter",
+        "--workgroups",
+        "64,1,1",
+        "--local-size",
+        "64",
+        "--iterations",
+        "40",
+        "--random-seed",
+        "0",
+    ]);
+
+    let line = output
+        .lines()
+        .find(|line| line.contains("\"event\":\"differential_test\""))
+        .unwrap();
+    assert_eq!(json_number(line, "cases"), 40, "{line}");
+}
+
+/// The GLSL shader on Vulkan with its SPIR-V passed to the driver as it is (unpackUint2x32, where
+/// the adapter supports passthrough) and translated by wgpu, both paths match the CPU on the same
+/// random cases
+#[test]
+fn differential_test_spirv_passthrough_on_software_adapter() {
+    if !enabled() {
+        return;
+    }
+
+    for path in [&[][..], &["--no-spirv-passthrough"]] {
+        let mut args = vec![
+            "differential-test",
+            "--allow-software-adapter",
+            "--backend",
+            "vulkan",
+            "--shader",
+            "glsl",
+            "--workgroups",
+            "64,1,1",
+            "--local-size",
+            "64",
+            "--iterations",
+            "40",
+            "--random-seed",
+            "0x51",
+        ];
+        args.extend(path);
+        let output = run(&args);
+
+        let line = output
+            .lines()
+            .find(|line| line.contains("\"event\":\"differential_test\""))
+            .unwrap_or_else(|| panic!("{path:?}: {output}"));
+        assert_eq!(json_number(line, "cases"), 40, "{path:?}: {line}");
+    }
+}
+
+/// The Y round resumes from the state before the first Y word, the dry run self check compares it
+/// with the checksum calculated over every word, for all the Y values of two Y words
+#[test]
+fn dry_run_self_check_on_cpu() {
+    let path = std::env::temp_dir().join(format!(
+        "ipl3hasher-test-dry-run-{}.bin",
+        std::process::id()
+    ));
+    let rom = path.to_str().unwrap();
+
+    write_ipl3(&path, IPL3_WORDS, &[]);
+
+    for y in 0..16 {
+        run(&[
+            "search",
+            rom,
+            "--seed",
+            SEED,
+            "--checksum",
+            "0",
+            "--cpu-only",
+            "--dry-run",
+            "--y-bits",
+            "1012[1..0],1022[1..0]",
+            "--y-init",
+            &y.to_string(),
+        ]);
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// The collision found on the CPU is appended to the results file, signing with the entry alone
+/// (its seed, checksum and bits) makes the ROM hash to the target, as read back after signing
+#[test]
+fn sign_from_results_file_on_cpu() {
+    let path = |name: &str| {
+        std::env::temp_dir().join(format!(
+            "ipl3hasher-test-results-{}-{name}",
+            std::process::id()
+        ))
+    };
+    let (rom_path, results_path) = (path("rom.bin"), path("results.jsonl"));
+    let (rom, results) = (rom_path.to_str().unwrap(), results_path.to_str().unwrap());
+    let planted = [(1022, 2, Y), (1016, 10, X >> 10), (1017, 10, X)];
+
+    write_ipl3(&rom_path, IPL3_WORDS, &planted);
+    let compute = run(&["compute", rom, "--seed", SEED, "--checksum", "0"]);
+    let line = compute
+        .lines()
+        .find(|line| line.contains("\"event\":\"compute\""))
+        .unwrap();
+    let checksum = format!("{:X}", json_number(line, "checksum"));
+
+    let cleared: Vec<_> = planted
+        .iter()
+        .map(|&(word, bits, _)| (word, bits, 0))
+        .collect();
+    write_ipl3(&rom_path, IPL3_WORDS, &cleared);
+
+    for _ in 0..2 {
+        run(&[
+            "search",
+            rom,
+            "--seed",
+            SEED,
+            "--checksum",
+            &checksum,
+            "--y-bits",
+            Y_BITS,
+            "--x-bits",
+            X_BITS,
+            "--cpu-only",
+            "--results-file",
+            results,
+        ]);
+    }
+    let lines = std::fs::read_to_string(&results_path).unwrap();
+    assert_eq!(lines.lines().count(), 2, "{lines}");
+    assert_eq!(json_number(lines.lines().last().unwrap(), "x"), X as u64);
+
+    let unsigned = std::fs::read(&rom_path).unwrap();
+    let sign = run(&["sign", rom, "--results-file", results, "--result", "-1"]);
+    let line = sign
+        .lines()
+        .find(|line| line.contains("\"event\":\"signed\""))
+        .unwrap();
+    assert_eq!(format!("{:X}", json_number(line, "checksum")), checksum);
+    let verify = run(&["compute", rom, "--seed", SEED, "--checksum", &checksum]);
+    assert!(verify.contains("\"match\":true"), "{verify}");
+
+    // The source was backed up before it was signed in place, the backup is never overwritten
+    let backup_path = path("rom.bin.bak");
+    assert!(std::fs::read(&backup_path).unwrap() == unsigned);
+    let sign = std::process::Command::new(env!("CARGO_BIN_EXE_ipl3hasher-new"))
+        .args(["sign", rom, "--results-file", results, "--result", "0"])
+        .output()
+        .unwrap();
+    assert!(!sign.status.success());
+    assert!(std::fs::read(&backup_path).unwrap() == unsigned);
+
+    std::fs::remove_file(&rom_path).unwrap();
+    std::fs::remove_file(&backup_path).unwrap();
+    std::fs::remove_file(&results_path).unwrap();
+}